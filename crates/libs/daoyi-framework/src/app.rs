@@ -0,0 +1,413 @@
+//! Bootstrap shared by the module servers and the aggregate `daoyi-server`.
+//!
+//! ```ignore
+//! App::builder()
+//!     .module(system::module())
+//!     .module(infra::module())
+//!     .hoop(cors_hoop())
+//!     .run()
+//!     .await;
+//! ```
+
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "nacos")]
+use anyhow::Context;
+use salvo::catcher::Catcher;
+use salvo::conn::Acceptor;
+use salvo::conn::rustls::{Keycert, RustlsConfig};
+use salvo::prelude::*;
+use tokio::signal;
+use tracing::info;
+
+use crate::config::{self, ServerConfig};
+use crate::health::{Health, HealthCheck};
+#[cfg(feature = "nacos")]
+use crate::nacos::naming::{self, Registration};
+
+/// How long requests in flight may take to finish after a shutdown signal.
+const GRACEFUL_TIMEOUT: Duration = Duration::from_secs(60);
+
+type Hook = Box<dyn FnOnce() -> Pin<Box<dyn Future<Output = anyhow::Result<()>> + Send>> + Send>;
+
+type Check = Box<dyn FnOnce(&Health) + Send>;
+
+fn hook<F, Fut>(f: F) -> Hook
+where
+    F: FnOnce() -> Fut + Send + 'static,
+    Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+{
+    Box::new(move || Box::pin(f()))
+}
+
+/// Routes of one module plus what it needs done around them.
+///
+/// The router is built only after configuration, database and Redis are initialized, so it may
+/// read `config::get()`.
+pub struct Module {
+    name: &'static str,
+    prefix: Option<String>,
+    router: Box<dyn FnOnce() -> Router + Send>,
+    startup: Vec<Hook>,
+    shutdown: Vec<Hook>,
+    checks: Vec<Check>,
+}
+
+impl Module {
+    pub fn new<F>(name: &'static str, router: F) -> Self
+    where
+        F: FnOnce() -> Router + Send + 'static,
+    {
+        Self {
+            name,
+            prefix: None,
+            router: Box::new(router),
+            startup: Vec::new(),
+            shutdown: Vec::new(),
+            checks: Vec::new(),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Mount the routes under `prefix` instead of the root, e.g. `admin-api/infra`.
+    pub fn prefix(mut self, prefix: impl Into<String>) -> Self {
+        self.prefix = Some(prefix.into());
+        self
+    }
+
+    /// Run `f` before the server starts. An error aborts the start.
+    pub fn on_startup<F, Fut>(mut self, f: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.startup.push(hook(f));
+        self
+    }
+
+    /// Run `f` once the server has stopped accepting requests and drained those in flight.
+    pub fn on_shutdown<F, Fut>(mut self, f: F) -> Self
+    where
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = anyhow::Result<()>> + Send + 'static,
+    {
+        self.shutdown.push(hook(f));
+        self
+    }
+
+    /// Report the server not ready while `check` fails.
+    pub fn health_check(mut self, check: impl HealthCheck + 'static) -> Self {
+        self.checks
+            .push(Box::new(move |health: &Health| health.add_check(check)));
+        self
+    }
+
+    fn router(self) -> Router {
+        let router = (self.router)();
+        match self.prefix {
+            Some(prefix) => Router::with_path(prefix).push(router),
+            None => router,
+        }
+    }
+}
+
+pub struct App;
+
+impl App {
+    pub fn builder() -> AppBuilder {
+        AppBuilder::default()
+    }
+}
+
+#[derive(Default)]
+pub struct AppBuilder {
+    modules: Vec<Module>,
+    hoops: Vec<Box<dyn FnOnce(Service) -> Service + Send>>,
+    catcher: Option<Catcher>,
+}
+
+impl AppBuilder {
+    pub fn module(mut self, module: Module) -> Self {
+        self.modules.push(module);
+        self
+    }
+
+    /// Hoop run for every request, including those no module routes.
+    pub fn hoop<H: Handler>(mut self, hoop: H) -> Self {
+        self.hoops.push(Box::new(move |service| service.hoop(hoop)));
+        self
+    }
+
+    /// Catcher rendering responses without a body, such as 404s. Salvo's default otherwise.
+    pub fn catcher(mut self, catcher: Catcher) -> Self {
+        self.catcher = Some(catcher);
+        self
+    }
+
+    /// Initialize configuration, logging, database and Redis, start the modules and serve
+    /// until a shutdown signal arrives.
+    pub async fn run(self) {
+        config::init().await;
+        let config = config::get();
+        let _guard = config.log.guard();
+        info!("log level: {}", &config.log.filter_level);
+        #[cfg(feature = "db")]
+        crate::db::init(&config.db).await;
+        #[cfg(feature = "redis")]
+        if let Err(e) = crate::cache::init(&config.redis).await {
+            eprintln!("Failed to connect to Redis: {e}");
+            std::process::exit(1);
+        }
+
+        let health = Arc::new(Health::default());
+        #[cfg(feature = "db")]
+        health.add_check(crate::health::DbCheck);
+        #[cfg(feature = "redis")]
+        health.add_check(crate::health::RedisCheck);
+
+        let mut router = Router::new().push(health.router());
+        #[cfg(feature = "metrics")]
+        {
+            router = router.push(crate::metrics::router());
+        }
+        let (router, shutdown) = match start_modules(self.modules, &health, router).await {
+            Ok(started) => started,
+            Err(e) => {
+                eprintln!("{e:#}");
+                std::process::exit(1);
+            }
+        };
+        let mut service = Service::new(router).catcher(self.catcher.unwrap_or_default());
+        // Outermost, so every other hoop and the handler run inside the request spans.
+        #[cfg(feature = "otel")]
+        {
+            service = service.hoop(crate::otel::hoop);
+        }
+        service = service.hoop(crate::request_id::hoop);
+        service = service.hoop(crate::http::Http::new(&config.http));
+        for hoop in self.hoops {
+            service = hoop(service);
+        }
+
+        println!("🔄 在以下位置监听 {}", &config.listen_addr);
+        //Acme 支持，自动从 Let's Encrypt 获取 TLS 证书。例子请看 https://github.com/salvo-rs/salvo/blob/main/examples/acme-http01-quinn/src/main.rs
+        let served = match &config.tls {
+            Some(tls) => {
+                let rustls_config =
+                    RustlsConfig::new(Keycert::new().cert(tls.cert.clone()).key(tls.key.clone()));
+                let acceptor = TcpListener::new(config.listen_addr.clone())
+                    .rustls(rustls_config)
+                    .bind()
+                    .await;
+                serve(acceptor, service, &config, health.clone()).await
+            }
+            None => {
+                let acceptor = TcpListener::new(config.listen_addr.clone()).bind().await;
+                serve(acceptor, service, &config, health.clone()).await
+            }
+        };
+        stop_modules(shutdown).await;
+        if let Err(e) = served {
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
+        #[cfg(feature = "otel")]
+        crate::otel::shutdown();
+    }
+}
+
+/// Run the startup hooks of `modules` in order and mount their routes on `router`.
+///
+/// Returns the shutdown hooks to run once serving ends. If a module fails to start, those of the
+/// modules started before it are run before returning the error.
+async fn start_modules(
+    modules: Vec<Module>,
+    health: &Health,
+    mut router: Router,
+) -> anyhow::Result<(Router, Vec<Hook>)> {
+    let mut shutdown = Vec::new();
+    for mut module in modules {
+        for check in module.checks.drain(..) {
+            check(health);
+        }
+        for startup in module.startup.drain(..) {
+            if let Err(e) = startup().await {
+                stop_modules(shutdown).await;
+                return Err(e.context(format!("module `{}` failed to start", module.name)));
+            }
+        }
+        shutdown.append(&mut module.shutdown);
+        let prefix = module.prefix.as_deref().unwrap_or_default();
+        info!(module = module.name, prefix, "module started");
+        println!("📦 模块 {} 挂载于 /{prefix}", module.name);
+        router = router.push(module.router());
+    }
+    Ok((router, shutdown))
+}
+
+/// Run shutdown hooks in the reverse order of registration, so modules release their resources
+/// in the reverse order of starting.
+async fn stop_modules(shutdown: Vec<Hook>) {
+    for hook in shutdown.into_iter().rev() {
+        if let Err(e) = hook().await {
+            tracing::warn!(error = %e, "shutdown hook failed");
+        }
+    }
+}
+
+/// Serve until a shutdown signal arrives. Fails before serving if the Nacos registration is
+/// misconfigured; an unreachable Nacos only keeps the server unready until it registers.
+async fn serve<A: Acceptor + Send>(
+    acceptor: A,
+    service: Service,
+    config: &ServerConfig,
+    health: Arc<Health>,
+) -> anyhow::Result<()> {
+    let server = Server::new(acceptor);
+    let handle = server.handle();
+    #[cfg(feature = "nacos")]
+    let registration = naming::register(config)
+        .await
+        .context("invalid Nacos registration settings")?;
+    #[cfg(feature = "nacos")]
+    if let Some(registration) = &registration {
+        health.add_check(crate::health::NacosCheck(registration.status()));
+    }
+    let shutdown_delay = Duration::from_secs(config.shutdown_delay);
+    health.set_ready(true);
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        // Fail readiness and leave the registry first, so load balancers and callers stop
+        // picking this instance before it refuses connections.
+        health.set_ready(false);
+        #[cfg(feature = "nacos")]
+        deregister(registration).await;
+        tokio::time::sleep(shutdown_delay).await;
+        handle.stop_graceful(GRACEFUL_TIMEOUT);
+    });
+    server.serve(service).await;
+    Ok(())
+}
+
+#[cfg(feature = "nacos")]
+async fn deregister(registration: Option<Registration>) {
+    if let Some(registration) = registration
+        && let Err(e) = registration.deregister().await
+    {
+        tracing::warn!(error = %e, "failed to deregister from Nacos");
+    }
+}
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("ctrl_c signal received"),
+        _ = terminate => info!("terminate signal received"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use salvo::test::TestClient;
+
+    use super::*;
+
+    type Events = Arc<Mutex<Vec<String>>>;
+
+    #[handler]
+    async fn pong() -> &'static str {
+        "pong"
+    }
+
+    /// Module serving `ping` and recording when it starts and stops.
+    fn module(name: &'static str, events: &Events) -> Module {
+        let (started, stopped) = (events.clone(), events.clone());
+        Module::new(name, || Router::with_path("ping").get(pong))
+            .on_startup(move || async move {
+                started.lock().unwrap().push(format!("start {name}"));
+                Ok(())
+            })
+            .on_shutdown(move || async move {
+                stopped.lock().unwrap().push(format!("stop {name}"));
+                Ok(())
+            })
+    }
+
+    #[tokio::test]
+    async fn modules_mount_under_their_prefix() {
+        let events = Events::default();
+        let modules = vec![
+            module("system", &events).prefix("admin-api/system"),
+            module("infra", &events).prefix("admin-api/infra"),
+        ];
+        let (router, _) = start_modules(modules, &Health::default(), Router::new())
+            .await
+            .unwrap();
+        let service = Service::new(router);
+        for (path, status) in [
+            ("/admin-api/system/ping", StatusCode::OK),
+            ("/admin-api/infra/ping", StatusCode::OK),
+            ("/ping", StatusCode::NOT_FOUND),
+            ("/admin-api/member/ping", StatusCode::NOT_FOUND),
+        ] {
+            let res = TestClient::get(format!("http://127.0.0.1{path}"))
+                .send(&service)
+                .await;
+            assert_eq!(res.status_code, Some(status), "{path}");
+        }
+    }
+
+    #[tokio::test]
+    async fn shutdown_hooks_run_in_reverse_order() {
+        let events = Events::default();
+        let modules = vec![module("system", &events), module("infra", &events)];
+        let (_, shutdown) = start_modules(modules, &Health::default(), Router::new())
+            .await
+            .unwrap();
+        stop_modules(shutdown).await;
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["start system", "start infra", "stop infra", "stop system"]
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_startup_stops_the_modules_already_started() {
+        let events = Events::default();
+        let broken = Module::new("broken", Router::new)
+            .on_startup(|| async { anyhow::bail!("database unreachable") });
+        let modules = vec![module("system", &events), broken, module("infra", &events)];
+        let Err(e) = start_modules(modules, &Health::default(), Router::new()).await else {
+            panic!("a failed startup hook should fail the start");
+        };
+        assert_eq!(
+            format!("{e:#}"),
+            "module `broken` failed to start: database unreachable"
+        );
+        assert_eq!(*events.lock().unwrap(), ["start system", "stop system"]);
+    }
+}
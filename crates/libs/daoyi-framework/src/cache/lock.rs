@@ -0,0 +1,311 @@
+//! Mutual exclusion across instances on top of Redis.
+//!
+//! A lock is the key `<prefix>lock:{name}` holding a random owner id with a TTL. While a
+//! `LockGuard` is alive a background task extends the TTL, and dropping or releasing the guard
+//! deletes the key only if it still holds our owner id. Every successful acquisition also
+//! returns a fencing token from `<prefix>lock:{name}:fence`, strictly increasing per lock name:
+//! pass it to the protected resource and reject writes carrying an older token, which keeps
+//! a holder that stalled past its TTL from overwriting the work of the next one.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use redis::{RedisResult, Script};
+use tokio::task::JoinHandle;
+use tokio::time::{Instant, MissedTickBehavior, sleep};
+use ulid::Ulid;
+
+use super::Redis;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(30);
+/// Shortest TTL, leaving renewal every third of it time to reach Redis.
+const MIN_TTL: Duration = Duration::from_secs(1);
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Take the lock and bump the fencing counter, returning the new token or 0 if the lock is held.
+static ACQUIRE: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if redis.call('SET', KEYS[1], ARGV[1], 'NX', 'PX', ARGV[2]) then
+            return redis.call('INCR', KEYS[2])
+        end
+        return 0
+        ",
+    )
+});
+
+/// Extend the TTL if we still own the lock.
+static RENEW: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if redis.call('GET', KEYS[1]) == ARGV[1] then
+            return redis.call('PEXPIRE', KEYS[1], ARGV[2])
+        end
+        return 0
+        ",
+    )
+});
+
+/// Delete the lock if we still own it.
+static RELEASE: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if redis.call('GET', KEYS[1]) == ARGV[1] then
+            return redis.call('DEL', KEYS[1])
+        end
+        return 0
+        ",
+    )
+});
+
+/// A named lock. Create one with `Redis::lock`.
+pub struct DistributedLock {
+    redis: Redis,
+    name: String,
+    ttl: Duration,
+}
+
+impl Redis {
+    pub fn lock(&self, name: impl Into<String>) -> DistributedLock {
+        DistributedLock {
+            redis: self.clone(),
+            name: name.into(),
+            ttl: DEFAULT_TTL,
+        }
+    }
+}
+
+impl DistributedLock {
+    /// How long the lock survives a holder that stopped renewing it. Defaults to 30 seconds.
+    ///
+    /// # Panics
+    ///
+    /// If `ttl` is shorter than one second.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = checked_ttl(ttl);
+        self
+    }
+
+    fn keys(&self) -> (String, String) {
+        // The hash tag keeps both keys in the same Redis Cluster slot.
+        let key = self.redis.key(&format!("lock:{{{}}}", self.name));
+        let fence = format!("{key}:fence");
+        (key, fence)
+    }
+
+    /// Take the lock if it is free right now.
+    pub async fn try_lock(&self) -> RedisResult<Option<LockGuard>> {
+        let (key, fence) = self.keys();
+        let owner = Ulid::new().to_string();
+        let token: u64 = ACQUIRE
+            .key(&key)
+            .key(&fence)
+            .arg(&owner)
+            .arg(self.ttl.as_millis() as u64)
+            .invoke_async(&mut self.redis.conn())
+            .await?;
+        if token == 0 {
+            return Ok(None);
+        }
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let renewal = tokio::spawn(renew(
+            self.redis.clone(),
+            key.clone(),
+            owner.clone(),
+            self.ttl,
+            lost.clone(),
+        ));
+        Ok(Some(LockGuard {
+            redis: self.redis.clone(),
+            key,
+            owner,
+            token,
+            lost,
+            renewal,
+            released: false,
+        }))
+    }
+
+    /// Take the lock, retrying until `wait` has passed. `None` if it stayed held.
+    pub async fn lock(&self, wait: Duration) -> RedisResult<Option<LockGuard>> {
+        let deadline = Instant::now() + wait;
+        loop {
+            if let Some(guard) = self.try_lock().await? {
+                return Ok(Some(guard));
+            }
+            if Instant::now() >= deadline {
+                return Ok(None);
+            }
+            sleep(RETRY_INTERVAL.min(deadline - Instant::now())).await;
+        }
+    }
+}
+
+fn checked_ttl(ttl: Duration) -> Duration {
+    assert!(
+        ttl >= MIN_TTL,
+        "distributed lock TTL must be at least {MIN_TTL:?}, got {ttl:?}"
+    );
+    ttl
+}
+
+async fn renew(redis: Redis, key: String, owner: String, ttl: Duration, lost: Arc<AtomicBool>) {
+    let mut interval = tokio::time::interval(ttl / 3);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    interval.tick().await;
+    loop {
+        interval.tick().await;
+        let renewed: RedisResult<i64> = RENEW
+            .key(&key)
+            .arg(&owner)
+            .arg(ttl.as_millis() as u64)
+            .invoke_async(&mut redis.conn())
+            .await;
+        match renewed {
+            Ok(1) => {}
+            Ok(_) => {
+                tracing::warn!(key, "distributed lock expired while held");
+                lost.store(true, Ordering::Release);
+                return;
+            }
+            // Keep trying: the lock only expires if Redis stays unreachable for the whole TTL.
+            Err(e) => tracing::warn!(key, error = %e, "distributed lock renewal failed"),
+        }
+    }
+}
+
+/// Proof of holding a `DistributedLock`. The lock is released when the guard is dropped.
+pub struct LockGuard {
+    redis: Redis,
+    key: String,
+    owner: String,
+    token: u64,
+    lost: Arc<AtomicBool>,
+    renewal: JoinHandle<()>,
+    released: bool,
+}
+
+impl LockGuard {
+    /// Fencing token of this acquisition, greater than that of every earlier holder.
+    pub fn token(&self) -> u64 {
+        self.token
+    }
+
+    /// False once renewal found the lock expired and possibly taken by someone else.
+    pub fn is_held(&self) -> bool {
+        !self.lost.load(Ordering::Acquire)
+    }
+
+    /// Release now and report whether we still owned the lock.
+    pub async fn release(mut self) -> RedisResult<bool> {
+        self.released = true;
+        self.renewal.abort();
+        release(&self.redis, &self.key, &self.owner).await
+    }
+}
+
+async fn release(redis: &Redis, key: &str, owner: &str) -> RedisResult<bool> {
+    let deleted: i64 = RELEASE
+        .key(key)
+        .arg(owner)
+        .invoke_async(&mut redis.conn())
+        .await?;
+    Ok(deleted == 1)
+}
+
+impl Drop for LockGuard {
+    fn drop(&mut self) {
+        self.renewal.abort();
+        if self.released {
+            return;
+        }
+        // Without a runtime the key is left to expire after its TTL.
+        if let Ok(runtime) = tokio::runtime::Handle::try_current() {
+            let redis = self.redis.clone();
+            let key = std::mem::take(&mut self.key);
+            let owner = std::mem::take(&mut self.owner);
+            runtime.spawn(async move {
+                if let Err(e) = release(&redis, &key, &owner).await {
+                    tracing::warn!(key, error = %e, "distributed lock release failed");
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use redis::AsyncCommands;
+
+    use super::super::testing::RedisServer;
+    use super::*;
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn excludes_other_holders_and_increments_tokens() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let redis = server.connect().await;
+        let lock = redis.lock("stock:1");
+
+        let first = lock.try_lock().await.unwrap().expect("lock should be free");
+        assert!(lock.try_lock().await.unwrap().is_none());
+        assert!(redis.lock("stock:2").try_lock().await.unwrap().is_some());
+        let first_token = first.token();
+        assert!(first.release().await.unwrap());
+
+        let second = lock.try_lock().await.unwrap().expect("lock should be free");
+        assert!(second.token() > first_token);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn dropping_the_guard_releases_the_lock() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let redis = server.connect().await;
+        let lock = redis.lock("order:1");
+
+        drop(lock.try_lock().await.unwrap().unwrap());
+        let guard = lock.lock(Duration::from_secs(2)).await.unwrap();
+        assert!(guard.is_some());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn renewal_keeps_the_lock_past_its_ttl() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let redis = server.connect().await;
+        let lock = redis.lock("pay:1").ttl(Duration::from_secs(1));
+
+        let guard = lock.try_lock().await.unwrap().unwrap();
+        sleep(Duration::from_millis(2500)).await;
+        assert!(guard.is_held());
+        assert!(lock.try_lock().await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn release_leaves_a_lock_taken_over_by_someone_else() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let redis = server.connect().await;
+        let lock = redis.lock("erp:1").ttl(Duration::from_secs(1));
+
+        let stale = lock.try_lock().await.unwrap().unwrap();
+        // Simulate the key expiring while the holder was paused.
+        let _: usize = redis.conn().del("test:lock:{erp:1}").await.unwrap();
+        let current = lock.try_lock().await.unwrap().unwrap();
+
+        assert!(!stale.release().await.unwrap());
+        assert!(current.token() > 1);
+        assert!(lock.try_lock().await.unwrap().is_none());
+        sleep(Duration::from_secs(1)).await;
+        assert!(current.is_held());
+    }
+
+    #[test]
+    #[should_panic(expected = "at least")]
+    fn rejects_a_ttl_renewal_cannot_keep_up_with() {
+        checked_ttl(Duration::ZERO);
+    }
+}
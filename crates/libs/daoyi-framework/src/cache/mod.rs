@@ -0,0 +1,289 @@
+use std::future::Future;
+use std::marker::PhantomData;
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+
+use redis::aio::{ConnectionManager, ConnectionManagerConfig};
+use redis::{AsyncCommands, RedisResult};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::config::RedisConfig;
+
+pub mod lock;
+
+/// Shared Redis connection plus the configured key prefix. Cheap to clone.
+#[derive(Clone)]
+pub struct Redis {
+    conn: ConnectionManager,
+    prefix: Arc<str>,
+    default_ttl: Duration,
+}
+
+impl Redis {
+    pub async fn connect(config: &RedisConfig) -> RedisResult<Self> {
+        let client = redis::Client::open(config.url.as_str())?;
+        let timeout = Duration::from_secs(config.timeout);
+        let manager_config = ConnectionManagerConfig::new()
+            .set_connection_timeout(Some(timeout))
+            .set_response_timeout(Some(timeout));
+        let conn = client
+            .get_connection_manager_with_config(manager_config)
+            .await?;
+        Ok(Self {
+            conn,
+            prefix: config.key_prefix.as_str().into(),
+            default_ttl: Duration::from_secs(config.default_ttl),
+        })
+    }
+
+    /// Connection for running commands directly. Keys must go through `key`.
+    pub fn conn(&self) -> ConnectionManager {
+        self.conn.clone()
+    }
+
+    /// `key` with the configured prefix.
+    pub fn key(&self, key: &str) -> String {
+        format!("{}{key}", self.prefix)
+    }
+
+    pub fn default_ttl(&self) -> Duration {
+        self.default_ttl
+    }
+}
+
+static REDIS: OnceLock<Redis> = OnceLock::new();
+
+/// Connect the shared `redis()` connection. The caller decides whether an unreachable Redis
+/// stops startup.
+pub async fn init(config: &RedisConfig) -> RedisResult<()> {
+    let redis = Redis::connect(config).await?;
+    if REDIS.set(redis).is_err() {
+        panic!("redis should be set once");
+    }
+    Ok(())
+}
+
+pub fn redis() -> &'static Redis {
+    REDIS.get().expect("redis should be set")
+}
+
+/// Typed view over the keys `<prefix><namespace>:<id>`, stored as JSON.
+pub struct Cache<T> {
+    redis: Redis,
+    namespace: &'static str,
+    ttl: Duration,
+    _value: PhantomData<fn() -> T>,
+}
+
+impl<T> Cache<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub fn new(redis: Redis, namespace: &'static str, ttl: Duration) -> Self {
+        Self {
+            redis,
+            namespace,
+            ttl,
+            _value: PhantomData,
+        }
+    }
+
+    fn key(&self, id: &str) -> String {
+        self.redis.key(&format!("{}:{id}", self.namespace))
+    }
+
+    /// Cached value, or `None` when missing or no longer decodable.
+    pub async fn get(&self, id: &str) -> RedisResult<Option<T>> {
+        let key = self.key(id);
+        let raw: Option<String> = self.redis.conn().get(&key).await?;
+        Ok(raw.and_then(|raw| match serde_json::from_str(&raw) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                tracing::warn!(key, error = %e, "ignoring undecodable cache entry");
+                None
+            }
+        }))
+    }
+
+    pub async fn set(&self, id: &str, value: &T) -> RedisResult<()> {
+        let raw = serde_json::to_string(value).map_err(|e| {
+            redis::RedisError::from((
+                redis::ErrorKind::Client,
+                "cache value is not serializable",
+                e.to_string(),
+            ))
+        })?;
+        self.redis
+            .conn()
+            .set_ex(self.key(id), raw, self.ttl.as_secs().max(1))
+            .await
+    }
+
+    pub async fn delete(&self, id: &str) -> RedisResult<()> {
+        let _: usize = self.redis.conn().del(self.key(id)).await?;
+        Ok(())
+    }
+
+    /// Cache-aside read: return the cached value, or load it and cache what was found.
+    ///
+    /// Redis failures are logged and fall back to `load`, so the cache never fails a request.
+    pub async fn get_or_load<F, E>(&self, id: &str, load: F) -> Result<Option<T>, E>
+    where
+        F: Future<Output = Result<Option<T>, E>>,
+    {
+        match self.get(id).await {
+            Ok(Some(value)) => return Ok(Some(value)),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(namespace = self.namespace, error = %e, "cache read failed"),
+        }
+        let value = load.await?;
+        if let Some(value) = &value
+            && let Err(e) = self.set(id, value).await
+        {
+            tracing::warn!(namespace = self.namespace, error = %e, "cache write failed");
+        }
+        Ok(value)
+    }
+
+    /// Drop an entry after its source changed. Failures are only logged.
+    pub async fn evict(&self, id: &str) {
+        if let Err(e) = self.delete(id).await {
+            tracing::warn!(namespace = self.namespace, error = %e, "cache eviction failed");
+        }
+    }
+}
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing {
+    use std::net::{TcpListener, TcpStream};
+    use std::process::{Child, Command, Stdio};
+    use std::thread::sleep;
+    use std::time::Duration;
+
+    use super::Redis;
+    use crate::config::RedisConfig;
+
+    /// Throwaway `redis-server` on a free port, killed on drop.
+    ///
+    /// Tests using it are `#[ignore]`d; run them with `cargo test -- --ignored` where
+    /// `redis-server` is installed.
+    pub struct RedisServer {
+        child: Child,
+        url: String,
+    }
+
+    impl RedisServer {
+        /// `None` when `redis-server` is not installed.
+        pub fn spawn() -> Option<Self> {
+            let port = TcpListener::bind("127.0.0.1:0")
+                .ok()?
+                .local_addr()
+                .ok()?
+                .port();
+            let child = Command::new("redis-server")
+                .args([
+                    "--port",
+                    &port.to_string(),
+                    "--save",
+                    "",
+                    "--appendonly",
+                    "no",
+                ])
+                .stdout(Stdio::null())
+                .spawn()
+                .ok()?;
+            let server = Self {
+                child,
+                url: format!("redis://127.0.0.1:{port}"),
+            };
+            for _ in 0..50 {
+                if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                    return Some(server);
+                }
+                sleep(Duration::from_millis(100));
+            }
+            None
+        }
+
+        pub async fn connect(&self) -> Redis {
+            Redis::connect(&RedisConfig {
+                url: self.url.clone(),
+                key_prefix: "test:".into(),
+                ..Default::default()
+            })
+            .await
+            .expect("redis-server should accept connections")
+        }
+    }
+
+    impl Drop for RedisServer {
+        fn drop(&mut self) {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use redis::AsyncCommands;
+    use serde::{Deserialize, Serialize};
+
+    use super::testing::RedisServer;
+    use super::*;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Item {
+        id: u32,
+        name: String,
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn round_trips_with_prefix_and_ttl() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let redis = server.connect().await;
+        let cache = Cache::<Item>::new(redis.clone(), "item", Duration::from_secs(60));
+        let item = Item {
+            id: 1,
+            name: "one".into(),
+        };
+
+        assert_eq!(cache.get("1").await.unwrap(), None);
+        cache.set("1", &item).await.unwrap();
+        assert_eq!(cache.get("1").await.unwrap(), Some(item));
+        let ttl: i64 = redis.conn().ttl("test:item:1").await.unwrap();
+        assert!((1..=60).contains(&ttl));
+
+        cache.delete("1").await.unwrap();
+        assert_eq!(cache.get("1").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn get_or_load_only_loads_on_miss() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let cache = Cache::<Item>::new(server.connect().await, "item", Duration::from_secs(60));
+        let loads = AtomicUsize::new(0);
+        let load = || async {
+            loads.fetch_add(1, Ordering::SeqCst);
+            Ok::<_, ()>(Some(Item {
+                id: 2,
+                name: "two".into(),
+            }))
+        };
+
+        for _ in 0..3 {
+            let item = cache.get_or_load("2", load()).await.unwrap().unwrap();
+            assert_eq!(item.name, "two");
+        }
+        assert_eq!(loads.load(Ordering::SeqCst), 1);
+
+        cache.evict("2").await;
+        cache.get_or_load("2", load()).await.unwrap();
+        assert_eq!(loads.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::default_false;
+
+/// Cross-origin requests allowed by the app's `cors_hoop`. Nothing is allowed by default.
+#[derive(Deserialize, Clone, Debug)]
+pub struct CorsConfig {
+    /// Exact origins such as `https://admin.example.com`, or patterns with `*` standing for one
+    /// or more subdomain labels or a port, such as `https://*.example.com` or
+    /// `http://localhost:*`. A lone `*` allows any origin, but not together with
+    /// `allow_credentials`.
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// Response headers scripts of other origins may read.
+    #[serde(default = "default_exposed_headers")]
+    pub exposed_headers: Vec<String>,
+    /// Let browsers send cookies, such as `jwt_token`, with cross-origin requests.
+    #[serde(default = "default_false")]
+    pub allow_credentials: bool,
+    /// Seconds browsers may cache a preflight response.
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+    /// Overrides for the deployment named by the top-level `environment`.
+    #[serde(default)]
+    pub environments: HashMap<String, CorsEnvironmentConfig>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CorsEnvironmentConfig {
+    pub allowed_origins: Option<Vec<String>>,
+    pub allow_credentials: Option<bool>,
+}
+
+impl CorsConfig {
+    /// Settings for `environment`, with its overrides applied.
+    pub fn resolve(&self, environment: Option<&str>) -> CorsConfig {
+        let mut config = self.clone();
+        if let Some(overrides) = environment.and_then(|name| self.environments.get(name)) {
+            if let Some(origins) = &overrides.allowed_origins {
+                config.allowed_origins = origins.clone();
+            }
+            if let Some(allow_credentials) = overrides.allow_credentials {
+                config.allow_credentials = allow_credentials;
+            }
+        }
+        config.environments.clear();
+        config
+    }
+
+    /// Browsers refuse credentialed responses for any origin, so reject the combination rather
+    /// than fail every request.
+    pub(super) fn validate(&self, environment: Option<&str>) -> Result<(), String> {
+        let config = self.resolve(environment);
+        if config.allow_credentials && config.allowed_origins.iter().any(|origin| origin == "*") {
+            return Err("cors.allow_credentials cannot be combined with the `*` origin".into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: default_allowed_methods(),
+            allowed_headers: default_allowed_headers(),
+            exposed_headers: default_exposed_headers(),
+            allow_credentials: false,
+            max_age: default_max_age(),
+            environments: HashMap::new(),
+        }
+    }
+}
+
+fn default_allowed_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "PATCH", "DELETE"]
+        .map(String::from)
+        .into()
+}
+fn default_allowed_headers() -> Vec<String> {
+    [
+        "authorization",
+        "content-type",
+        "tenant-id",
+        "x-request-id",
+        "traceparent",
+        "x-csrf-token",
+    ]
+    .map(String::from)
+    .into()
+}
+fn default_exposed_headers() -> Vec<String> {
+    [
+        "x-request-id",
+        "traceparent",
+        "retry-after",
+        "x-ratelimit-limit",
+        "x-ratelimit-remaining",
+        "x-ratelimit-reset",
+    ]
+    .map(String::from)
+    .into()
+}
+fn default_max_age() -> u64 {
+    3600
+}
@@ -0,0 +1,89 @@
+use serde::Deserialize;
+
+use super::default_true;
+
+/// Limits and compression applied to every request, see `crate::http`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct HttpConfig {
+    /// Seconds a request may take before it is answered with 504. 0 for no limit.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Largest request body in bytes. Larger ones are answered with 413.
+    #[serde(default = "default_body_limit")]
+    pub body_limit: u64,
+    #[serde(default)]
+    pub compression: CompressionConfig,
+    /// Settings of subtrees by path prefix. The longest matching prefix wins, and what it
+    /// leaves unset keeps the values above.
+    #[serde(default)]
+    pub routes: Vec<HttpRouteConfig>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct CompressionConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Encodings offered, in order of preference when the client accepts several.
+    #[serde(default = "default_algorithms")]
+    pub algorithms: Vec<CompressionAlgorithm>,
+    /// Responses smaller than this many bytes are sent as is.
+    #[serde(default = "default_min_size")]
+    pub min_size: usize,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CompressionAlgorithm {
+    Gzip,
+    #[serde(rename = "br")]
+    Brotli,
+    Zstd,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct HttpRouteConfig {
+    /// Path prefix of the subtree, e.g. `/api/files`.
+    pub path: String,
+    pub timeout: Option<u64>,
+    pub body_limit: Option<u64>,
+    /// Turn compression on or off below `path`.
+    pub compression: Option<bool>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout: default_timeout(),
+            body_limit: default_body_limit(),
+            compression: CompressionConfig::default(),
+            routes: Vec::new(),
+        }
+    }
+}
+
+impl Default for CompressionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            algorithms: default_algorithms(),
+            min_size: default_min_size(),
+        }
+    }
+}
+
+fn default_timeout() -> u64 {
+    30
+}
+fn default_body_limit() -> u64 {
+    10 * 1024 * 1024
+}
+fn default_algorithms() -> Vec<CompressionAlgorithm> {
+    vec![
+        CompressionAlgorithm::Zstd,
+        CompressionAlgorithm::Brotli,
+        CompressionAlgorithm::Gzip,
+    ]
+}
+fn default_min_size() -> usize {
+    1024
+}
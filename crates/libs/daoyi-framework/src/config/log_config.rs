@@ -1,17 +1,60 @@
 // https://github.com/clia/tracing-config/blob/main/src/lib.rs
+use std::sync::OnceLock;
+
 use serde::Deserialize;
 use tracing_appender::non_blocking::WorkerGuard;
-use tracing_subscriber::fmt;
+use tracing_subscriber::fmt::{self, FormatEvent, FormatFields, MakeWriter, SubscriberBuilder};
+#[cfg(feature = "otel")]
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{EnvFilter, Registry};
 
 use tracing_appender::rolling;
 
-use super::default_true;
+use super::{OtelConfig, default_true};
 
 const FORMAT_PRETTY: &str = "pretty";
 const FORMAT_COMPACT: &str = "compact";
 const FORMAT_JSON: &str = "json";
 const FORMAT_FULL: &str = "full";
 
+type ReloadFilter = Box<dyn Fn(EnvFilter) -> Result<(), String> + Send + Sync>;
+
+/// Swaps the filter of the installed subscriber.
+static RELOAD_FILTER: OnceLock<ReloadFilter> = OnceLock::new();
+
+/// Replace the filter set from `filter_level` when the subscriber was installed.
+pub fn reload_filter_level(filter_level: &str) -> Result<(), String> {
+    let filter = EnvFilter::try_new(filter_level).map_err(|e| e.to_string())?;
+    match RELOAD_FILTER.get() {
+        Some(reload) => reload(filter),
+        None => Err("log subscriber is not installed".into()),
+    }
+}
+
+/// Install `builder` as the global subscriber, keeping a handle to reload its filter, with
+/// span export when `otel` is set.
+fn init<N, E, W>(builder: SubscriberBuilder<N, E, EnvFilter, W>, otel: Option<&OtelConfig>)
+where
+    N: for<'writer> FormatFields<'writer> + Send + Sync + 'static,
+    E: FormatEvent<Registry, N> + Send + Sync + 'static,
+    W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+    let builder = builder.with_filter_reloading();
+    let handle = builder.reload_handle();
+    let _ = RELOAD_FILTER.set(Box::new(move |filter| {
+        handle.reload(filter).map_err(|e| e.to_string())
+    }));
+    let subscriber = builder.finish();
+    #[cfg(feature = "otel")]
+    let subscriber = subscriber.with(otel.and_then(crate::otel::layer));
+    #[cfg(not(feature = "otel"))]
+    if otel.is_some() {
+        eprintln!("[log.otel] is ignored: built without the `otel` feature");
+    }
+    subscriber.init();
+}
+
 #[derive(Deserialize, Clone, Debug)]
 pub struct LogConfig {
     #[serde(default = "default_filter_level")]
@@ -38,6 +81,8 @@ pub struct LogConfig {
     pub with_thread_names: bool,
     #[serde(default = "default_true")]
     pub with_source_location: bool,
+    /// Export spans to an OpenTelemetry collector.
+    pub otel: Option<OtelConfig>,
 }
 fn default_filter_level() -> String {
     "info".into()
@@ -70,6 +115,7 @@ impl Default for LogConfig {
             with_thread_ids: true,
             with_thread_names: true,
             with_source_location: true,
+            otel: None,
         }
     }
 }
@@ -169,6 +215,12 @@ impl LogConfig {
         self
     }
 
+    /// Export spans to an OpenTelemetry collector.
+    pub fn otel(mut self, otel: OtelConfig) -> Self {
+        self.otel = Some(otel);
+        self
+    }
+
     /// Init tracing log.
     ///
     /// Caller should hold the guard.
@@ -186,8 +238,7 @@ impl LogConfig {
         // Tracing subscriber init.
         let subscriber = tracing_subscriber::fmt()
             .with_env_filter(
-                tracing_subscriber::EnvFilter::try_from_default_env()
-                    .unwrap_or(tracing_subscriber::EnvFilter::new(&self.filter_level)),
+                EnvFilter::try_from_default_env().unwrap_or(EnvFilter::new(&self.filter_level)),
             )
             .with_ansi(self.with_ansi);
 
@@ -202,9 +253,9 @@ impl LogConfig {
                     .with_source_location(self.with_source_location),
             );
             if self.stdout {
-                subscriber.with_writer(std::io::stdout).init();
+                init(subscriber.with_writer(std::io::stdout), self.otel.as_ref());
             } else {
-                subscriber.with_writer(file_writer).init();
+                init(subscriber.with_writer(file_writer), self.otel.as_ref());
             };
         } else if self.format == FORMAT_COMPACT {
             let subscriber = subscriber.event_format(
@@ -217,9 +268,9 @@ impl LogConfig {
                     .with_source_location(self.with_source_location),
             );
             if self.stdout {
-                subscriber.with_writer(std::io::stdout).init();
+                init(subscriber.with_writer(std::io::stdout), self.otel.as_ref());
             } else {
-                subscriber.with_writer(file_writer).init();
+                init(subscriber.with_writer(file_writer), self.otel.as_ref());
             };
         } else if self.format == FORMAT_JSON {
             let subscriber = subscriber.event_format(
@@ -232,9 +283,15 @@ impl LogConfig {
                     .with_source_location(self.with_source_location),
             );
             if self.stdout {
-                subscriber.json().with_writer(std::io::stdout).init();
+                init(
+                    subscriber.json().with_writer(std::io::stdout),
+                    self.otel.as_ref(),
+                );
             } else {
-                subscriber.json().with_writer(file_writer).init();
+                init(
+                    subscriber.json().with_writer(file_writer),
+                    self.otel.as_ref(),
+                );
             };
         } else if self.format == FORMAT_FULL {
             let subscriber = subscriber.event_format(
@@ -246,9 +303,9 @@ impl LogConfig {
                     .with_source_location(self.with_source_location),
             );
             if self.stdout {
-                subscriber.with_writer(std::io::stdout).init();
+                init(subscriber.with_writer(std::io::stdout), self.otel.as_ref());
             } else {
-                subscriber.with_writer(file_writer).init();
+                init(subscriber.with_writer(file_writer), self.otel.as_ref());
             };
         }
 
@@ -0,0 +1,322 @@
+//! Server configuration: the config file, then the Nacos document when the `nacos` feature is
+//! on, then `APP_` environment variables.
+
+use std::sync::{Arc, OnceLock};
+
+use arc_swap::ArcSwap;
+use figment::Figment;
+use figment::providers::{Env, Format, Toml};
+use serde::Deserialize;
+use serde::de::DeserializeOwned;
+
+#[cfg(feature = "nacos")]
+use crate::nacos::config::NacosProvider;
+use crate::utils::IpNet;
+
+mod log_config;
+pub use log_config::{LogConfig, reload_filter_level};
+mod db_config;
+pub use db_config::DbConfig;
+mod redis_config;
+pub use redis_config::RedisConfig;
+mod nacos_config;
+pub use nacos_config::NacosConfig;
+mod otel_config;
+pub use otel_config::{OtelConfig, OtelProtocol};
+mod cors_config;
+pub use cors_config::{CorsConfig, CorsEnvironmentConfig};
+mod http_config;
+pub use http_config::{CompressionAlgorithm, CompressionConfig, HttpConfig, HttpRouteConfig};
+
+/// Current configuration. Replaced as a whole when the Nacos document changes.
+static CONFIG: OnceLock<ArcSwap<ServerConfig>> = OnceLock::new();
+/// Sources `CONFIG` was extracted from, for the settings of dependent crates.
+static SOURCES: OnceLock<ArcSwap<Figment>> = OnceLock::new();
+
+/// The config file, then `remote` if any, then `APP_` environment variables.
+fn figment(remote: Option<Figment>) -> Figment {
+    let mut figment = Figment::new().merge(Toml::file(
+        Env::var("APP_CONFIG").as_deref().unwrap_or("config.toml"),
+    ));
+    if let Some(remote) = remote {
+        figment = figment.merge(remote);
+    }
+    figment.merge(Env::prefixed("APP_").global())
+}
+
+fn extract_server(figment: &Figment) -> Result<ServerConfig, String> {
+    let config = figment.extract::<ServerConfig>().map_err(|e| {
+        format!("It looks like your config is invalid. The following error occurred: {e}")
+    })?;
+    config.cors.validate(config.environment.as_deref())?;
+    #[cfg(feature = "db")]
+    let config = with_database_url(config)?;
+    Ok(config)
+}
+
+/// Fall back to `DATABASE_URL` when the config leaves the database URL empty.
+#[cfg(feature = "db")]
+fn with_database_url(mut config: ServerConfig) -> Result<ServerConfig, String> {
+    if config.db.url.is_empty() {
+        config.db.url = std::env::var("DATABASE_URL").unwrap_or_default();
+    }
+    if config.db.url.is_empty() {
+        return Err("DATABASE_URL is not set".into());
+    }
+    Ok(config)
+}
+
+fn exit_on_error<T>(result: Result<T, String>) -> T {
+    result.unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    })
+}
+
+/// Load the configuration. Exits the process if it is invalid.
+#[cfg(not(feature = "nacos"))]
+pub async fn init() {
+    let figment = figment(None);
+    set(exit_on_error(extract_server(&figment)), figment);
+}
+
+/// Load the configuration, layering and then watching the Nacos document named by
+/// `nacos.data_id`. Exits the process if it is invalid.
+#[cfg(feature = "nacos")]
+pub async fn init() {
+    use std::time::Duration;
+
+    use crate::nacos::{self, NacosClient};
+
+    let local = figment(None);
+    let nacos = if local.contains("nacos") {
+        let nacos = local
+            .extract_inner::<NacosConfig>("nacos")
+            .map_err(|e| format!("It looks like your Nacos config is invalid: {e}"));
+        Some(exit_on_error(nacos))
+    } else {
+        None
+    };
+    let mut remote = None;
+    if let Some(nacos) = nacos
+        && let Some(data_id) = &nacos.data_id
+    {
+        let client = NacosClient::new(&nacos);
+        let provider = NacosProvider::fetch(&client, data_id, &nacos.group)
+            .await
+            .map_err(|e| format!("Failed to load config `{data_id}` from Nacos: {e}"));
+        let timeout = Duration::from_secs(nacos.long_poll_timeout);
+        remote = Some((client, exit_on_error(provider), timeout));
+    }
+
+    let layer = remote
+        .as_ref()
+        .map(|(_, provider, _)| Figment::from(provider.clone()));
+    let figment = figment(layer);
+    set(exit_on_error(extract_server(&figment)), figment);
+    if let Some((client, provider, timeout)) = remote {
+        nacos::config::watch(Arc::new(client), provider, timeout, reload);
+    }
+}
+
+fn set(config: ServerConfig, sources: Figment) {
+    if SOURCES.set(ArcSwap::from_pointee(sources)).is_err()
+        || CONFIG.set(ArcSwap::from_pointee(config)).is_err()
+    {
+        panic!("config should be set once");
+    }
+}
+
+/// Rebuild the configuration around a new version of the Nacos document.
+///
+/// Settings read through `get()` on every use, such as JWT expiry, the tenant header or the CORS
+/// policy, change right away; the log filter is reloaded here. Listen address, TLS, database and Redis
+/// settings only take effect after a restart.
+#[cfg(feature = "nacos")]
+fn reload(remote: &NacosProvider) {
+    let sources = figment(Some(Figment::from(remote.clone())));
+    let config = match extract_server(&sources) {
+        Ok(config) => config,
+        Err(e) => {
+            tracing::error!(error = e, "ignoring invalid config from Nacos");
+            return;
+        }
+    };
+    let filter_level = config.log.filter_level.clone();
+    // Sources first, so whoever sees the new `get()` also extracts from them.
+    SOURCES
+        .get()
+        .expect("config should be set")
+        .store(Arc::new(sources));
+    let previous = CONFIG
+        .get()
+        .expect("config should be set")
+        .swap(Arc::new(config));
+    if previous.log.filter_level != filter_level
+        && let Err(e) = log_config::reload_filter_level(&filter_level)
+    {
+        tracing::error!(error = e, "failed to reload log filter");
+    }
+    tracing::info!("config reloaded from Nacos");
+}
+
+/// Snapshot of the current configuration. Call again to observe later reloads.
+pub fn get() -> Arc<ServerConfig> {
+    CONFIG.get().expect("config should be set").load_full()
+}
+
+/// Like `get`, but `None` before `init`, for code that also runs outside a server, e.g. in tests.
+pub fn try_get() -> Option<Arc<ServerConfig>> {
+    CONFIG.get().map(|config| config.load_full())
+}
+
+/// Extract `T` from the sources of `get()`, for settings that dependent crates keep in their own
+/// sections next to the framework's. Call again to observe later reloads.
+pub fn extract<T: DeserializeOwned>() -> Result<T, Box<figment::Error>> {
+    SOURCES
+        .get()
+        .expect("config should be set")
+        .load()
+        .extract()
+        .map_err(Box::new)
+}
+
+#[cfg(any(test, feature = "testing"))]
+pub mod testing {
+    use super::*;
+
+    /// Load `path` overridden by `APP_` variables, unless a test already did, so tests do not
+    /// depend on a `config.toml` in the working directory. Panics if the file is invalid.
+    pub fn init(path: &str) {
+        CONFIG.get_or_init(|| {
+            let figment = Figment::new()
+                .merge(Toml::file(path))
+                .merge(Env::prefixed("APP_").global());
+            let config = extract_server(&figment).unwrap_or_else(|e| panic!("{e}"));
+            SOURCES.get_or_init(|| ArcSwap::from_pointee(figment));
+            ArcSwap::from_pointee(config)
+        });
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ServerConfig {
+    #[serde(default = "default_listen_addr")]
+    pub listen_addr: String,
+    /// Seconds between turning not ready on shutdown and refusing new connections, so load
+    /// balancers see the failing readiness probe and stop sending traffic first.
+    #[serde(default = "default_shutdown_delay")]
+    pub shutdown_delay: u64,
+    /// Name of the deployment, such as `dev` or `prod`, selecting per-environment overrides.
+    pub environment: Option<String>,
+    /// Addresses or CIDR blocks of proxies, such as the gateway, whose `X-Forwarded-For` is
+    /// believed when telling client addresses apart.
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpNet>,
+
+    #[cfg(feature = "db")]
+    pub db: DbConfig,
+    #[cfg(feature = "redis")]
+    #[serde(default)]
+    pub redis: RedisConfig,
+    #[cfg(feature = "nacos")]
+    pub nacos: Option<NacosConfig>,
+    pub log: LogConfig,
+    #[cfg(feature = "jwt")]
+    pub jwt: JwtConfig,
+    pub tls: Option<TlsConfig>,
+    #[serde(default)]
+    pub http: HttpConfig,
+    #[serde(default)]
+    pub cors: CorsConfig,
+    #[serde(default)]
+    pub tenant: TenantConfig,
+}
+
+#[cfg(feature = "jwt")]
+#[derive(Deserialize, Clone, Debug)]
+pub struct JwtConfig {
+    /// HS256 secret. Used for signing when `signing_kid` is unset, and for verifying tokens
+    /// without a `kid` header.
+    #[serde(default)]
+    pub secret: String,
+    pub expiry: i64,
+    #[serde(default = "default_refresh_expiry")]
+    pub refresh_expiry: i64,
+    /// Kid of the entry in `keys` used to sign new tokens. Without it or `secret`, tokens are
+    /// only verified.
+    pub signing_kid: Option<String>,
+    /// Asymmetric keys accepted for verification and published at `/.well-known/jwks.json`.
+    #[serde(default)]
+    pub keys: Vec<JwtKeyConfig>,
+    /// Where token families and revocations are kept. `memory` only suits a single instance,
+    /// as other instances would accept tokens revoked here.
+    #[serde(default)]
+    pub store: TokenStoreKind,
+}
+
+#[cfg(feature = "jwt")]
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenStoreKind {
+    Memory,
+    #[default]
+    Redis,
+}
+
+#[cfg(feature = "jwt")]
+#[derive(Deserialize, Clone, Debug)]
+pub struct JwtKeyConfig {
+    pub kid: String,
+    /// RS256 | RS384 | RS512 | PS256 | PS384 | PS512 | ES256 | EdDSA
+    pub algorithm: jsonwebtoken::Algorithm,
+    /// Path to the PEM encoded public key.
+    pub public_key: String,
+    /// Path to the PEM encoded private key. Only needed for the signing key.
+    pub private_key: Option<String>,
+}
+#[derive(Deserialize, Clone, Debug)]
+pub struct TenantConfig {
+    /// Request header carrying the tenant id.
+    #[serde(default = "default_tenant_header")]
+    pub header: String,
+    /// Tenant used when neither the token, the header nor the host name identifies one.
+    pub default_id: Option<String>,
+}
+impl Default for TenantConfig {
+    fn default() -> Self {
+        Self {
+            header: default_tenant_header(),
+            default_id: None,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+}
+
+#[allow(dead_code)]
+pub fn default_false() -> bool {
+    false
+}
+#[allow(dead_code)]
+pub fn default_true() -> bool {
+    true
+}
+
+fn default_listen_addr() -> String {
+    "127.0.0.1:8008".into()
+}
+fn default_shutdown_delay() -> u64 {
+    5
+}
+fn default_tenant_header() -> String {
+    "tenant-id".into()
+}
+#[cfg(feature = "jwt")]
+fn default_refresh_expiry() -> i64 {
+    7 * 24 * 3600
+}
@@ -0,0 +1,71 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct NacosConfig {
+    /// Base URL of the Nacos open API, including the context path.
+    #[serde(default = "default_server_url")]
+    pub server_url: String,
+    /// Namespace id. Empty for the public namespace.
+    #[serde(default)]
+    pub namespace: String,
+    #[serde(default = "default_group")]
+    pub group: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Data id of a TOML document layered over the config file and watched for changes.
+    /// Remote configuration is disabled when unset.
+    pub data_id: Option<String>,
+    /// How long the server may hold a change listener open, in seconds.
+    #[serde(default = "default_long_poll_timeout")]
+    pub long_poll_timeout: u64,
+    /// Name this server registers under. Registration is disabled when unset.
+    pub service_name: Option<String>,
+    /// Address announced to other services. Defaults to the host of `listen_addr`, or to the
+    /// address of the interface that reaches Nacos when listening on all interfaces.
+    pub ip: Option<String>,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    /// Extra instance metadata. `version` and `secure` are always set.
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Seconds between heartbeats.
+    #[serde(default = "default_heartbeat_interval")]
+    pub heartbeat_interval: u64,
+}
+
+impl Default for NacosConfig {
+    fn default() -> Self {
+        Self {
+            server_url: default_server_url(),
+            namespace: String::new(),
+            group: default_group(),
+            username: None,
+            password: None,
+            data_id: None,
+            long_poll_timeout: default_long_poll_timeout(),
+            service_name: None,
+            ip: None,
+            weight: default_weight(),
+            metadata: HashMap::new(),
+            heartbeat_interval: default_heartbeat_interval(),
+        }
+    }
+}
+
+fn default_server_url() -> String {
+    "http://127.0.0.1:8848/nacos".into()
+}
+fn default_group() -> String {
+    "DEFAULT_GROUP".into()
+}
+fn default_long_poll_timeout() -> u64 {
+    30
+}
+fn default_weight() -> f64 {
+    1.0
+}
+fn default_heartbeat_interval() -> u64 {
+    5
+}
@@ -0,0 +1,55 @@
+use serde::Deserialize;
+
+/// Export of request spans to an OpenTelemetry collector. Needs the `otel` feature.
+#[derive(Deserialize, Clone, Debug)]
+pub struct OtelConfig {
+    /// Collector address, e.g. `http://localhost:4317` for gRPC or `http://localhost:4318` for
+    /// HTTP. Spans go to `/v1/traces` under it over HTTP.
+    #[serde(default = "default_endpoint")]
+    pub endpoint: String,
+    #[serde(default)]
+    pub protocol: OtelProtocol,
+    /// `service.name` of the exported spans.
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+    /// Share of traces started here that are exported, from 0.0 to 1.0. Traces started by a
+    /// caller follow the caller's decision.
+    #[serde(default = "default_sample_ratio")]
+    pub sample_ratio: f64,
+    /// Export timeout, in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum OtelProtocol {
+    #[default]
+    Grpc,
+    Http,
+}
+
+impl Default for OtelConfig {
+    fn default() -> Self {
+        Self {
+            endpoint: default_endpoint(),
+            protocol: OtelProtocol::default(),
+            service_name: default_service_name(),
+            sample_ratio: default_sample_ratio(),
+            timeout: default_timeout(),
+        }
+    }
+}
+
+fn default_endpoint() -> String {
+    "http://localhost:4317".into()
+}
+fn default_service_name() -> String {
+    "daoyi-server".into()
+}
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+fn default_timeout() -> u64 {
+    10
+}
@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct RedisConfig {
+    #[serde(default = "default_url")]
+    pub url: String,
+    /// Prepended to every key, so several applications can share one database.
+    #[serde(default = "default_key_prefix")]
+    pub key_prefix: String,
+    /// Default time to live of cached entries, in seconds.
+    #[serde(default = "default_ttl")]
+    pub default_ttl: u64,
+    /// Connect and response timeout, in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+}
+
+impl Default for RedisConfig {
+    fn default() -> Self {
+        Self {
+            url: default_url(),
+            key_prefix: default_key_prefix(),
+            default_ttl: default_ttl(),
+            timeout: default_timeout(),
+        }
+    }
+}
+
+fn default_url() -> String {
+    "redis://127.0.0.1:6379".into()
+}
+fn default_key_prefix() -> String {
+    "daoyi:".into()
+}
+fn default_ttl() -> u64 {
+    1800
+}
+fn default_timeout() -> u64 {
+    3
+}
@@ -0,0 +1,111 @@
+//! Error codes returned in the `code` field of `CommonResult`.
+//!
+//! Global codes reuse the HTTP status number. Modules register their business codes next to
+//! their handlers, following the layout of the Java services, `1_MMM_SSS_EEE`: module,
+//! sub-module and error number.
+
+use salvo::http::StatusCode;
+
+use crate::AppError;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorCode {
+    pub code: i32,
+    pub status: StatusCode,
+    pub msg: &'static str,
+}
+
+impl ErrorCode {
+    pub const fn new(code: i32, status: StatusCode, msg: &'static str) -> Self {
+        Self { code, status, msg }
+    }
+
+    /// This error with a message other than the registered one.
+    pub fn with_msg(self, msg: impl Into<String>) -> AppError {
+        AppError::Code(self, Some(msg.into()))
+    }
+
+    /// Global code for an HTTP status that has no registered code.
+    pub fn from_status(status: StatusCode) -> Self {
+        GLOBAL
+            .iter()
+            .find(|code| code.status == status)
+            .copied()
+            .unwrap_or(Self {
+                code: status.as_u16() as i32,
+                status,
+                msg: status.canonical_reason().unwrap_or_default(),
+            })
+    }
+}
+
+pub const SUCCESS: ErrorCode = ErrorCode::new(0, StatusCode::OK, "Success.");
+
+// Global errors.
+pub const BAD_REQUEST: ErrorCode =
+    ErrorCode::new(400, StatusCode::BAD_REQUEST, "Invalid request parameters.");
+pub const UNAUTHORIZED: ErrorCode =
+    ErrorCode::new(401, StatusCode::UNAUTHORIZED, "Authentication is required.");
+pub const FORBIDDEN: ErrorCode = ErrorCode::new(403, StatusCode::FORBIDDEN, "Permission denied.");
+pub const NOT_FOUND: ErrorCode =
+    ErrorCode::new(404, StatusCode::NOT_FOUND, "Resource does not exist.");
+pub const METHOD_NOT_ALLOWED: ErrorCode = ErrorCode::new(
+    405,
+    StatusCode::METHOD_NOT_ALLOWED,
+    "Request method is not supported.",
+);
+pub const CONFLICT: ErrorCode =
+    ErrorCode::new(409, StatusCode::CONFLICT, "Resource already exists.");
+pub const TOO_MANY_REQUESTS: ErrorCode = ErrorCode::new(
+    429,
+    StatusCode::TOO_MANY_REQUESTS,
+    "Too many requests, please try again later.",
+);
+pub const INTERNAL_SERVER_ERROR: ErrorCode = ErrorCode::new(
+    500,
+    StatusCode::INTERNAL_SERVER_ERROR,
+    "Internal server error.",
+);
+
+const GLOBAL: &[ErrorCode] = &[
+    BAD_REQUEST,
+    UNAUTHORIZED,
+    FORBIDDEN,
+    NOT_FOUND,
+    METHOD_NOT_ALLOWED,
+    CONFLICT,
+    TOO_MANY_REQUESTS,
+    INTERNAL_SERVER_ERROR,
+];
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn codes_are_unique() {
+        let codes = [
+            SUCCESS,
+            BAD_REQUEST,
+            UNAUTHORIZED,
+            FORBIDDEN,
+            NOT_FOUND,
+            METHOD_NOT_ALLOWED,
+            CONFLICT,
+            TOO_MANY_REQUESTS,
+            INTERNAL_SERVER_ERROR,
+        ];
+        let unique = codes.iter().map(|code| code.code).collect::<HashSet<_>>();
+        assert_eq!(unique.len(), codes.len());
+    }
+
+    #[test]
+    fn unregistered_status_keeps_its_number() {
+        assert_eq!(ErrorCode::from_status(StatusCode::FORBIDDEN), FORBIDDEN);
+        let code = ErrorCode::from_status(StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(code.code, 413);
+        assert_eq!(code.status, StatusCode::PAYLOAD_TOO_LARGE);
+    }
+}
@@ -0,0 +1,139 @@
+#[cfg(feature = "oapi")]
+use salvo::http::StatusCode;
+use salvo::http::{ParseError, StatusError};
+#[cfg(feature = "oapi")]
+use salvo::oapi::{self, EndpointOutRegister, ToSchema};
+use salvo::prelude::*;
+#[cfg(feature = "db")]
+use sea_orm::{DbErr, SqlErr};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::CommonResult;
+
+pub mod code;
+pub use code::ErrorCode;
+
+#[derive(Error, Debug)]
+pub enum AppError {
+    #[error("public: `{0}`")]
+    Public(String),
+    #[error("internal: `{0}`")]
+    Internal(String),
+    #[error("error code {}: `{}`", .0.code, .1.as_deref().unwrap_or(.0.msg))]
+    Code(ErrorCode, Option<String>),
+    #[error("salvo internal error: `{0}`")]
+    Salvo(#[from] ::salvo::Error),
+    #[error("http status error: `{0}`")]
+    HttpStatus(#[from] StatusError),
+    #[error("http parse error:`{0}`")]
+    HttpParse(#[from] ParseError),
+    #[error("anyhow error:`{0}`")]
+    Anyhow(#[from] anyhow::Error),
+    #[cfg(feature = "db")]
+    #[error("seaorm db error:`{0}`")]
+    Seaorm(#[from] sea_orm::DbErr),
+    #[error("validation error:`{0}`")]
+    Validation(#[from] validator::ValidationErrors),
+}
+impl AppError {
+    pub fn public<S: Into<String>>(msg: S) -> Self {
+        Self::Public(msg.into())
+    }
+
+    pub fn internal<S: Into<String>>(msg: S) -> Self {
+        Self::Internal(msg.into())
+    }
+
+    /// Error code, message and optional details sent to the client.
+    fn into_parts(self) -> (ErrorCode, String, Option<Value>) {
+        let simple = |code: ErrorCode| (code, code.msg.to_owned(), None);
+        match self {
+            Self::Code(code, msg) => (code, msg.unwrap_or_else(|| code.msg.to_owned()), None),
+            Self::Public(msg) => (code::BAD_REQUEST, msg, None),
+            Self::Internal(msg) => {
+                tracing::error!(msg = msg, "internal error");
+                simple(code::INTERNAL_SERVER_ERROR)
+            }
+            Self::Salvo(e) => {
+                tracing::error!(error = ?e, "salvo error");
+                simple(code::INTERNAL_SERVER_ERROR)
+            }
+            Self::HttpStatus(e) => (ErrorCode::from_status(e.code), e.brief, None),
+            Self::HttpParse(e) => (code::BAD_REQUEST, e.to_string(), None),
+            Self::Anyhow(e) => {
+                tracing::error!(error = ?e, "unhandled error");
+                simple(code::INTERNAL_SERVER_ERROR)
+            }
+            #[cfg(feature = "db")]
+            Self::Seaorm(DbErr::RecordNotFound(_)) => simple(code::NOT_FOUND),
+            #[cfg(feature = "db")]
+            Self::Seaorm(e) => match e.sql_err() {
+                Some(SqlErr::UniqueConstraintViolation(_)) => simple(code::CONFLICT),
+                Some(SqlErr::ForeignKeyConstraintViolation(_)) => simple(code::BAD_REQUEST),
+                _ => {
+                    tracing::error!(error = ?e, "database error");
+                    simple(code::INTERNAL_SERVER_ERROR)
+                }
+            },
+            Self::Validation(errors) => {
+                let fields = errors
+                    .field_errors()
+                    .into_iter()
+                    .map(|(field, errors)| {
+                        let messages = errors
+                            .iter()
+                            .map(|error| match &error.message {
+                                Some(message) => message.to_string(),
+                                None => error.code.to_string(),
+                            })
+                            .collect::<Vec<_>>();
+                        (field.to_string(), Value::from(messages))
+                    })
+                    .collect::<serde_json::Map<_, _>>();
+                (
+                    code::BAD_REQUEST,
+                    code::BAD_REQUEST.msg.to_owned(),
+                    Some(Value::Object(fields)),
+                )
+            }
+        }
+    }
+}
+impl From<ErrorCode> for AppError {
+    fn from(code: ErrorCode) -> Self {
+        Self::Code(code, None)
+    }
+}
+
+#[async_trait]
+impl Writer for AppError {
+    async fn write(self, _req: &mut Request, depot: &mut Depot, res: &mut Response) {
+        let (code, msg, data) = self.into_parts();
+        res.status_code(code.status);
+        res.render(Json(
+            CommonResult::<Value>::error(code, msg, data).correlated(depot),
+        ));
+    }
+}
+#[cfg(feature = "oapi")]
+impl EndpointOutRegister for AppError {
+    fn register(components: &mut salvo::oapi::Components, operation: &mut salvo::oapi::Operation) {
+        for (status, description) in [
+            (StatusCode::BAD_REQUEST, "Bad request"),
+            (StatusCode::UNAUTHORIZED, "Unauthorized"),
+            (StatusCode::FORBIDDEN, "Forbidden"),
+            (StatusCode::NOT_FOUND, "Not found"),
+            (StatusCode::CONFLICT, "Conflict"),
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error"),
+        ] {
+            operation.responses.insert(
+                status.as_str(),
+                oapi::Response::new(description).add_content(
+                    "application/json",
+                    CommonResult::<Value>::to_schema(components),
+                ),
+            );
+        }
+    }
+}
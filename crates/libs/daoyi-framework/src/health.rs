@@ -0,0 +1,249 @@
+//! Liveness and readiness probes.
+//!
+//! `/health/live` answers as long as the process serves requests. `/health/ready` and
+//! `/actuator/health` run every registered check and answer 503 when one fails, before startup
+//! has finished, and from the moment a shutdown begins.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::Duration;
+
+use salvo::prelude::*;
+use serde_json::{Map, Value, json};
+use tokio::task::JoinSet;
+
+/// Time allowed for one check before it counts as failed.
+const CHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// A dependency the server cannot serve requests without.
+#[async_trait]
+pub trait HealthCheck: Send + Sync {
+    /// Key of the check in the health report.
+    fn name(&self) -> &str;
+
+    async fn check(&self) -> Result<(), String>;
+}
+
+/// Readiness state and checks of the server.
+#[derive(Default)]
+pub struct Health {
+    ready: AtomicBool,
+    checks: RwLock<Vec<Arc<dyn HealthCheck>>>,
+}
+
+impl Health {
+    pub fn add_check(&self, check: impl HealthCheck + 'static) {
+        self.checks
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(check));
+    }
+
+    pub fn set_ready(&self, ready: bool) {
+        self.ready.store(ready, Ordering::Relaxed);
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Relaxed)
+    }
+
+    /// Run every check. Returns whether the server is ready, and the report.
+    pub async fn report(&self) -> (bool, Value) {
+        let checks = self
+            .checks
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        let mut running = JoinSet::new();
+        for check in checks {
+            running.spawn(async move {
+                let result = match tokio::time::timeout(CHECK_TIMEOUT, check.check()).await {
+                    Ok(result) => result,
+                    Err(_) => Err("timed out".to_owned()),
+                };
+                (check.name().to_owned(), result)
+            });
+        }
+
+        let mut healthy = true;
+        let mut components = Map::new();
+        while let Some(joined) = running.join_next().await {
+            let Ok((name, result)) = joined else {
+                healthy = false;
+                continue;
+            };
+            let component = match result {
+                Ok(()) => json!({ "status": "UP" }),
+                Err(error) => {
+                    healthy = false;
+                    json!({ "status": "DOWN", "error": error })
+                }
+            };
+            components.insert(name, component);
+        }
+        let ready = self.is_ready();
+        let status = match (healthy, ready) {
+            (false, _) => "DOWN",
+            (true, false) => "OUT_OF_SERVICE",
+            (true, true) => "UP",
+        };
+        let report = json!({ "status": status, "components": components });
+        (healthy && ready, report)
+    }
+
+    /// `/actuator/health`, `/health/live` and `/health/ready`.
+    pub fn router(self: &Arc<Self>) -> Router {
+        Router::new()
+            .push(Router::with_path("actuator/health").get(Ready(self.clone())))
+            .push(
+                Router::with_path("health")
+                    .push(Router::with_path("live").get(live))
+                    .push(Router::with_path("ready").get(Ready(self.clone()))),
+            )
+    }
+}
+
+#[handler]
+async fn live(res: &mut Response) {
+    res.render(Json(json!({ "status": "UP" })));
+}
+
+struct Ready(Arc<Health>);
+
+#[async_trait]
+impl Handler for Ready {
+    async fn handle(
+        &self,
+        _req: &mut Request,
+        _depot: &mut Depot,
+        res: &mut Response,
+        _ctrl: &mut FlowCtrl,
+    ) {
+        let (ready, report) = self.0.report().await;
+        if !ready {
+            res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+        }
+        res.render(Json(report));
+    }
+}
+
+/// Postgres answers a ping through the shared pool.
+#[cfg(feature = "db")]
+pub struct DbCheck;
+
+#[cfg(feature = "db")]
+#[async_trait]
+impl HealthCheck for DbCheck {
+    fn name(&self) -> &str {
+        "db"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        crate::db::pool().ping().await.map_err(|e| e.to_string())
+    }
+}
+
+/// Redis answers a `PING`.
+#[cfg(feature = "redis")]
+pub struct RedisCheck;
+
+#[cfg(feature = "redis")]
+#[async_trait]
+impl HealthCheck for RedisCheck {
+    fn name(&self) -> &str {
+        "redis"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        let mut conn = crate::cache::redis().conn();
+        redis::cmd("PING")
+            .query_async::<String>(&mut conn)
+            .await
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    }
+}
+
+/// This server's Nacos registration is alive, so other services can find it.
+#[cfg(feature = "nacos")]
+pub struct NacosCheck(pub crate::nacos::naming::RegistrationStatus);
+
+#[cfg(feature = "nacos")]
+#[async_trait]
+impl HealthCheck for NacosCheck {
+    fn name(&self) -> &str {
+        "nacos"
+    }
+
+    async fn check(&self) -> Result<(), String> {
+        if self.0.is_registered() {
+            Ok(())
+        } else {
+            Err("not registered".into())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    struct Fixed(&'static str, Result<(), String>);
+
+    #[async_trait]
+    impl HealthCheck for Fixed {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        async fn check(&self) -> Result<(), String> {
+            self.1.clone()
+        }
+    }
+
+    async fn get(service: &Service, path: &str) -> (StatusCode, Value) {
+        let mut res = TestClient::get(format!("http://127.0.0.1{path}"))
+            .send(service)
+            .await;
+        let status = res.status_code.unwrap_or(StatusCode::OK);
+        (status, res.take_json::<Value>().await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn ready_once_started_and_every_check_passes() {
+        let health = Arc::new(Health::default());
+        health.add_check(Fixed("db", Ok(())));
+        let service = Service::new(health.router());
+
+        let (status, body) = get(&service, "/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "OUT_OF_SERVICE");
+
+        health.set_ready(true);
+        for path in ["/health/ready", "/actuator/health"] {
+            let (status, body) = get(&service, path).await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["status"], "UP");
+            assert_eq!(body["components"]["db"]["status"], "UP");
+        }
+
+        health.add_check(Fixed("redis", Err("connection refused".into())));
+        let (status, body) = get(&service, "/health/ready").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], "DOWN");
+        assert_eq!(body["components"]["redis"]["error"], "connection refused");
+    }
+
+    #[tokio::test]
+    async fn live_while_not_ready() {
+        let health = Arc::new(Health::default());
+        health.add_check(Fixed("db", Err("down".into())));
+        let service = Service::new(health.router());
+
+        let (status, body) = get(&service, "/health/live").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], "UP");
+    }
+}
@@ -0,0 +1,234 @@
+//! Request timeout, body size limit and response compression from the `[http]` section.
+//!
+//! One hoop applies all three, with the settings of the longest `[[http.routes]]` prefix
+//! matching the request path. Nested hoops could only tighten an outer timeout, while a subtree
+//! such as file uploads may need a longer one.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use salvo::compression::{Compression, CompressionLevel};
+use salvo::http::header::CONTENT_LENGTH;
+use salvo::http::{ResBody, StatusError};
+use salvo::prelude::*;
+
+use crate::config::{CompressionAlgorithm, CompressionConfig, HttpConfig};
+
+#[derive(Clone)]
+struct Settings {
+    timeout: Option<Duration>,
+    body_limit: u64,
+    compression: Option<Arc<Compression>>,
+}
+
+fn timeout(seconds: u64) -> Option<Duration> {
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+fn compression(config: &CompressionConfig) -> Compression {
+    let mut compression = Compression::new()
+        .disable_all()
+        .min_length(config.min_size)
+        .force_priority(true);
+    for algorithm in &config.algorithms {
+        compression = match algorithm {
+            CompressionAlgorithm::Gzip => compression.enable_gzip(CompressionLevel::Default),
+            CompressionAlgorithm::Brotli => compression.enable_brotli(CompressionLevel::Default),
+            CompressionAlgorithm::Zstd => compression.enable_zstd(CompressionLevel::Default),
+        };
+    }
+    compression
+}
+
+/// Path without a trailing slash and with a leading one, as prefixes are compared.
+fn normalize(path: &str) -> String {
+    format!("/{}", path.trim_matches('/'))
+}
+
+fn under(path: &str, prefix: &str) -> bool {
+    prefix == "/"
+        || path
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+/// Hoop enforcing the `[http]` settings.
+pub struct Http {
+    defaults: Settings,
+    /// Longest prefix first.
+    routes: Vec<(String, Settings)>,
+}
+
+impl Http {
+    pub fn new(config: &HttpConfig) -> Self {
+        let compression = Arc::new(compression(&config.compression));
+        let defaults = Settings {
+            timeout: timeout(config.timeout),
+            body_limit: config.body_limit,
+            compression: config.compression.enabled.then(|| compression.clone()),
+        };
+        let mut routes = config
+            .routes
+            .iter()
+            .map(|route| {
+                let settings = Settings {
+                    timeout: route.timeout.map_or(defaults.timeout, timeout),
+                    body_limit: route.body_limit.unwrap_or(defaults.body_limit),
+                    compression: match route.compression {
+                        Some(enabled) => enabled.then(|| compression.clone()),
+                        None => defaults.compression.clone(),
+                    },
+                };
+                (normalize(&route.path), settings)
+            })
+            .collect::<Vec<_>>();
+        routes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+        Self { defaults, routes }
+    }
+
+    fn settings(&self, path: &str) -> &Settings {
+        self.routes
+            .iter()
+            .find(|(prefix, _)| under(path, prefix))
+            .map_or(&self.defaults, |(_, settings)| settings)
+    }
+}
+
+#[async_trait]
+impl Handler for Http {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let settings = self.settings(req.uri().path());
+        if req
+            .header::<u64>(CONTENT_LENGTH)
+            .is_some_and(|length| length > settings.body_limit)
+        {
+            res.render(StatusError::payload_too_large().brief(format!(
+                "Request body is larger than {} bytes.",
+                settings.body_limit
+            )));
+            ctrl.skip_rest();
+            return;
+        }
+        // Bodies sent without a length stop being read at the limit.
+        req.set_secure_max_size(settings.body_limit as usize);
+
+        let next = async {
+            match &settings.compression {
+                Some(compression) => compression.handle(req, depot, res, ctrl).await,
+                None => {
+                    ctrl.call_next(req, depot, res).await;
+                }
+            }
+        };
+        match settings.timeout {
+            Some(timeout) => {
+                if tokio::time::timeout(timeout, next).await.is_err() {
+                    tracing::warn!(timeout = ?timeout, "request timed out");
+                    res.body = ResBody::None;
+                    res.render(StatusError::gateway_timeout());
+                    ctrl.skip_rest();
+                }
+            }
+            None => next.await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
+    use salvo::test::TestClient;
+
+    use super::*;
+    use crate::config::HttpRouteConfig;
+
+    #[handler]
+    async fn slow(res: &mut Response) {
+        tokio::time::sleep(Duration::from_secs(5)).await;
+        res.render("done");
+    }
+
+    #[handler]
+    async fn text(req: &mut Request, res: &mut Response) {
+        let size = req.query::<usize>("size").unwrap_or_default();
+        res.render("a".repeat(size));
+    }
+
+    fn service(config: HttpConfig) -> Service {
+        Service::new(
+            Router::new()
+                .hoop(Http::new(&config))
+                .push(Router::with_path("slow").get(slow))
+                .push(Router::with_path("text").get(text).post(text))
+                .push(Router::with_path("files/text").get(text).post(text)),
+        )
+    }
+
+    fn config() -> HttpConfig {
+        HttpConfig {
+            timeout: 1,
+            body_limit: 16,
+            routes: vec![HttpRouteConfig {
+                path: "files/".into(),
+                timeout: Some(0),
+                body_limit: Some(1024),
+                compression: Some(false),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn times_out_with_504() {
+        let res = TestClient::get("http://127.0.0.1/slow")
+            .send(&service(config()))
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::GATEWAY_TIMEOUT));
+    }
+
+    #[tokio::test]
+    async fn rejects_large_bodies_unless_the_route_allows_them() {
+        let service = service(config());
+        let body = "b".repeat(64);
+        let res = TestClient::post("http://127.0.0.1/text")
+            .add_header(CONTENT_LENGTH, body.len(), true)
+            .text(body.clone())
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::PAYLOAD_TOO_LARGE));
+
+        let res = TestClient::post("http://127.0.0.1/files/text")
+            .add_header(CONTENT_LENGTH, body.len(), true)
+            .text(body)
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code.unwrap_or(StatusCode::OK), StatusCode::OK);
+    }
+
+    async fn encoding(service: &Service, path: &str) -> Option<String> {
+        TestClient::get(format!("http://127.0.0.1{path}"))
+            .add_header(ACCEPT_ENCODING, "gzip, zstd", true)
+            .send(service)
+            .await
+            .headers()
+            .get(CONTENT_ENCODING)
+            .map(|value| value.to_str().unwrap().to_owned())
+    }
+
+    #[tokio::test]
+    async fn compresses_large_responses_where_enabled() {
+        let service = service(config());
+        assert_eq!(
+            encoding(&service, "/text?size=4096").await.as_deref(),
+            Some("zstd")
+        );
+        assert_eq!(encoding(&service, "/text?size=16").await, None);
+        assert_eq!(encoding(&service, "/files/text?size=4096").await, None);
+    }
+}
@@ -0,0 +1,463 @@
+use std::collections::HashMap;
+
+use anyhow::{Context, Result, bail};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, EllipticCurveKeyParameters,
+    EllipticCurveKeyType, Jwk, JwkSet, KeyAlgorithm, OctetKeyPairParameters, OctetKeyPairType,
+    PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{
+    Algorithm, DecodingKey, EncodingKey, Header, TokenData, Validation, decode, decode_header,
+};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+use crate::config::{JwtConfig, JwtKeyConfig};
+
+struct SigningKey {
+    kid: Option<String>,
+    algorithm: Algorithm,
+    key: EncodingKey,
+}
+
+struct VerifyingKey {
+    key: DecodingKey,
+    validation: Validation,
+}
+
+/// Signing key plus every key currently accepted for verification.
+///
+/// Tokens signed with a configured key carry its `kid` header. Tokens without `kid` are only
+/// accepted when the legacy HS256 `secret` is set, which keeps tokens issued before the switch
+/// to asymmetric keys valid until they expire.
+///
+/// Services that only check tokens configure public keys without `signing_kid` or `secret`;
+/// their ring verifies but refuses to sign.
+pub struct KeyRing {
+    signing: Option<SigningKey>,
+    verifying: HashMap<String, VerifyingKey>,
+    legacy: Option<VerifyingKey>,
+    jwks: JwkSet,
+}
+
+impl KeyRing {
+    pub fn load(config: &JwtConfig) -> Result<Self> {
+        let legacy = (!config.secret.is_empty()).then(|| VerifyingKey {
+            key: DecodingKey::from_secret(config.secret.as_bytes()),
+            validation: Validation::new(Algorithm::HS256),
+        });
+
+        let mut verifying = HashMap::new();
+        let mut jwks = Vec::new();
+        let mut signing = None;
+        for key in &config.keys {
+            let public_pem = std::fs::read_to_string(&key.public_key)
+                .with_context(|| format!("failed to read jwt public key `{}`", key.public_key))?;
+            verifying.insert(
+                key.kid.clone(),
+                VerifyingKey {
+                    key: decoding_key(key.algorithm, public_pem.as_bytes())?,
+                    validation: Validation::new(key.algorithm),
+                },
+            );
+            jwks.push(public_jwk(key, &public_pem)?);
+
+            if config.signing_kid.as_deref() == Some(key.kid.as_str()) {
+                signing = Some(signing_key(key)?);
+            }
+        }
+
+        let signing = match (signing, &config.signing_kid) {
+            (Some(signing), _) => Some(signing),
+            (None, Some(kid)) => bail!("jwt signing key `{kid}` is not configured"),
+            (None, None) if legacy.is_some() => Some(SigningKey {
+                kid: None,
+                algorithm: Algorithm::HS256,
+                key: EncodingKey::from_secret(config.secret.as_bytes()),
+            }),
+            (None, None) if !verifying.is_empty() => None,
+            (None, None) => bail!("either jwt.secret or jwt.keys must be set"),
+        };
+
+        Ok(Self {
+            signing,
+            verifying,
+            legacy,
+            jwks: JwkSet { keys: jwks },
+        })
+    }
+
+    /// Fails when the ring was configured for verification only.
+    pub fn encode<T: Serialize>(&self, claims: &T) -> Result<String> {
+        let Some(signing) = &self.signing else {
+            bail!("no jwt signing key is configured, tokens can only be verified");
+        };
+        let mut header = Header::new(signing.algorithm);
+        header.kid = signing.kid.clone();
+        Ok(jsonwebtoken::encode(&header, claims, &signing.key)?)
+    }
+
+    pub fn decode<C: DeserializeOwned>(&self, token: &str) -> Result<TokenData<C>, JwtError> {
+        let header = decode_header(token)?;
+        let key = match header.kid.as_deref() {
+            Some(kid) => self.verifying.get(kid),
+            None => self.legacy.as_ref(),
+        }
+        .ok_or(ErrorKind::InvalidToken)?;
+        decode::<C>(token, &key.key, &key.validation)
+    }
+
+    /// Public halves of all verification keys. The legacy HS256 secret is never published.
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+fn signing_key(config: &JwtKeyConfig) -> Result<SigningKey> {
+    let Some(path) = &config.private_key else {
+        bail!("jwt signing key `{}` has no private_key", config.kid);
+    };
+    let pem =
+        std::fs::read(path).with_context(|| format!("failed to read jwt private key `{path}`"))?;
+    let key = match config.algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => EncodingKey::from_rsa_pem(&pem)?,
+        Algorithm::ES256 => EncodingKey::from_ec_pem(&pem)?,
+        Algorithm::EdDSA => EncodingKey::from_ed_pem(&pem)?,
+        other => bail!("unsupported jwt key algorithm {other:?}"),
+    };
+    Ok(SigningKey {
+        kid: Some(config.kid.clone()),
+        algorithm: config.algorithm,
+        key,
+    })
+}
+
+fn decoding_key(algorithm: Algorithm, pem: &[u8]) -> Result<DecodingKey> {
+    Ok(match algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => DecodingKey::from_rsa_pem(pem)?,
+        Algorithm::ES256 => DecodingKey::from_ec_pem(pem)?,
+        Algorithm::EdDSA => DecodingKey::from_ed_pem(pem)?,
+        other => bail!("unsupported jwt key algorithm {other:?}"),
+    })
+}
+
+fn key_algorithm(algorithm: Algorithm) -> Result<KeyAlgorithm> {
+    Ok(match algorithm {
+        Algorithm::RS256 => KeyAlgorithm::RS256,
+        Algorithm::RS384 => KeyAlgorithm::RS384,
+        Algorithm::RS512 => KeyAlgorithm::RS512,
+        Algorithm::PS256 => KeyAlgorithm::PS256,
+        Algorithm::PS384 => KeyAlgorithm::PS384,
+        Algorithm::PS512 => KeyAlgorithm::PS512,
+        Algorithm::ES256 => KeyAlgorithm::ES256,
+        Algorithm::EdDSA => KeyAlgorithm::EdDSA,
+        other => bail!("jwt key algorithm {other:?} cannot be published"),
+    })
+}
+
+fn public_jwk(config: &JwtKeyConfig, pem: &str) -> Result<Jwk> {
+    let algorithm = match config.algorithm {
+        Algorithm::RS256
+        | Algorithm::RS384
+        | Algorithm::RS512
+        | Algorithm::PS256
+        | Algorithm::PS384
+        | Algorithm::PS512 => {
+            use rsa::pkcs1::DecodeRsaPublicKey;
+            use rsa::pkcs8::DecodePublicKey;
+            use rsa::traits::PublicKeyParts;
+
+            let key = rsa::RsaPublicKey::from_public_key_pem(pem)
+                .or_else(|_| rsa::RsaPublicKey::from_pkcs1_pem(pem))
+                .with_context(|| format!("invalid rsa public key for `{}`", config.kid))?;
+            AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(key.n().to_bytes_be()),
+                e: URL_SAFE_NO_PAD.encode(key.e().to_bytes_be()),
+            })
+        }
+        Algorithm::ES256 => {
+            use p256::elliptic_curve::sec1::ToEncodedPoint;
+            use p256::pkcs8::DecodePublicKey;
+
+            let key = p256::PublicKey::from_public_key_pem(pem)
+                .with_context(|| format!("invalid P-256 public key for `{}`", config.kid))?;
+            let point = key.to_encoded_point(false);
+            let (Some(x), Some(y)) = (point.x(), point.y()) else {
+                bail!("invalid P-256 public key for `{}`", config.kid);
+            };
+            AlgorithmParameters::EllipticCurve(EllipticCurveKeyParameters {
+                key_type: EllipticCurveKeyType::EC,
+                curve: EllipticCurve::P256,
+                x: URL_SAFE_NO_PAD.encode(x),
+                y: URL_SAFE_NO_PAD.encode(y),
+            })
+        }
+        Algorithm::EdDSA => {
+            use ed25519_dalek::pkcs8::DecodePublicKey;
+
+            let key = ed25519_dalek::VerifyingKey::from_public_key_pem(pem)
+                .with_context(|| format!("invalid Ed25519 public key for `{}`", config.kid))?;
+            AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(key.as_bytes()),
+            })
+        }
+        other => bail!("jwt key algorithm {other:?} cannot be published"),
+    };
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            key_algorithm: Some(key_algorithm(config.algorithm)?),
+            key_id: Some(config.kid.clone()),
+            ..Default::default()
+        },
+        algorithm,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde::Deserialize;
+    use ulid::Ulid;
+
+    use super::*;
+    use crate::config::TokenStoreKind;
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Claims {
+        sub: String,
+        exp: i64,
+    }
+
+    fn claims(sub: &str) -> Claims {
+        Claims {
+            sub: sub.to_owned(),
+            // 2100-01-01
+            exp: 4_102_444_800,
+        }
+    }
+
+    /// PEM files of test keys, removed when dropped.
+    struct KeyDir(PathBuf);
+
+    impl KeyDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("daoyi-jwt-{}", Ulid::new()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+
+        fn write(&self, name: &str, pem: &str) -> String {
+            let path = self.0.join(name);
+            std::fs::write(&path, pem).unwrap();
+            path.to_string_lossy().into_owned()
+        }
+
+        fn ed25519(&self, kid: &str, seed: u8) -> JwtKeyConfig {
+            use ed25519_dalek::pkcs8::spki::der::pem::LineEnding;
+            use ed25519_dalek::pkcs8::{EncodePrivateKey, EncodePublicKey};
+
+            let key = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+            JwtKeyConfig {
+                kid: kid.to_owned(),
+                algorithm: Algorithm::EdDSA,
+                public_key: self.write(
+                    &format!("{kid}.pub.pem"),
+                    &key.verifying_key()
+                        .to_public_key_pem(LineEnding::LF)
+                        .unwrap(),
+                ),
+                private_key: Some(self.write(
+                    &format!("{kid}.pem"),
+                    &key.to_pkcs8_pem(LineEnding::LF).unwrap(),
+                )),
+            }
+        }
+
+        fn es256(&self, kid: &str, seed: u8) -> JwtKeyConfig {
+            use p256::pkcs8::{EncodePrivateKey, EncodePublicKey, LineEnding};
+
+            let key = p256::SecretKey::from_slice(&[seed; 32]).unwrap();
+            JwtKeyConfig {
+                kid: kid.to_owned(),
+                algorithm: Algorithm::ES256,
+                public_key: self.write(
+                    &format!("{kid}.pub.pem"),
+                    &key.public_key().to_public_key_pem(LineEnding::LF).unwrap(),
+                ),
+                private_key: Some(self.write(
+                    &format!("{kid}.pem"),
+                    &key.to_pkcs8_pem(LineEnding::LF).unwrap(),
+                )),
+            }
+        }
+    }
+
+    impl Drop for KeyDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn config(keys: Vec<JwtKeyConfig>, signing_kid: Option<&str>) -> JwtConfig {
+        JwtConfig {
+            secret: String::new(),
+            expiry: 900,
+            refresh_expiry: 3600,
+            signing_kid: signing_kid.map(str::to_owned),
+            keys,
+            store: TokenStoreKind::Memory,
+        }
+    }
+
+    fn kid(token: &str) -> Option<String> {
+        decode_header(token).unwrap().kid
+    }
+
+    #[test]
+    fn load_rejects_missing_signing_keys() {
+        let dir = KeyDir::new();
+        let key = dir.ed25519("2024-07", 1);
+        assert!(KeyRing::load(&config(vec![key.clone()], Some("2024-08"))).is_err());
+        let public_only = JwtKeyConfig {
+            private_key: None,
+            ..key
+        };
+        assert!(KeyRing::load(&config(vec![public_only], Some("2024-07"))).is_err());
+        assert!(KeyRing::load(&config(Vec::new(), None)).is_err());
+    }
+
+    #[test]
+    fn public_keys_alone_verify_but_do_not_sign() {
+        let dir = KeyDir::new();
+        let key = dir.ed25519("2024-07", 1);
+        let signer = KeyRing::load(&config(vec![key.clone()], Some("2024-07"))).unwrap();
+        let verifier = KeyRing::load(&config(
+            vec![JwtKeyConfig {
+                private_key: None,
+                ..key
+            }],
+            None,
+        ))
+        .unwrap();
+
+        let token = signer.encode(&claims("alice")).unwrap();
+        assert_eq!(
+            verifier.decode::<Claims>(&token).unwrap().claims,
+            claims("alice")
+        );
+        assert!(verifier.encode(&claims("alice")).is_err());
+    }
+
+    #[test]
+    fn rotation_keeps_tokens_of_the_previous_key_valid() {
+        let dir = KeyDir::new();
+        let old = dir.ed25519("2024-07", 1);
+        let new = dir.es256("2024-08", 2);
+        let before = KeyRing::load(&config(vec![old.clone()], Some("2024-07"))).unwrap();
+        let during = KeyRing::load(&config(vec![old, new.clone()], Some("2024-08"))).unwrap();
+        let after = KeyRing::load(&config(vec![new], Some("2024-08"))).unwrap();
+
+        let old_token = before.encode(&claims("alice")).unwrap();
+        let new_token = during.encode(&claims("bob")).unwrap();
+        assert_eq!(kid(&old_token).as_deref(), Some("2024-07"));
+        assert_eq!(kid(&new_token).as_deref(), Some("2024-08"));
+
+        assert_eq!(
+            during.decode::<Claims>(&old_token).unwrap().claims,
+            claims("alice")
+        );
+        assert_eq!(
+            after.decode::<Claims>(&new_token).unwrap().claims,
+            claims("bob")
+        );
+        assert!(after.decode::<Claims>(&old_token).is_err());
+        assert!(before.decode::<Claims>(&new_token).is_err());
+    }
+
+    #[test]
+    fn tokens_without_kid_need_the_legacy_secret() {
+        let dir = KeyDir::new();
+        let legacy = KeyRing::load(&JwtConfig {
+            secret: "secret".into(),
+            ..config(Vec::new(), None)
+        })
+        .unwrap();
+        let token = legacy.encode(&claims("alice")).unwrap();
+        assert_eq!(kid(&token), None);
+
+        let migrated = KeyRing::load(&JwtConfig {
+            secret: "secret".into(),
+            ..config(vec![dir.ed25519("2024-07", 1)], Some("2024-07"))
+        })
+        .unwrap();
+        assert!(migrated.decode::<Claims>(&token).is_ok());
+        let asymmetric_only =
+            KeyRing::load(&config(vec![dir.ed25519("2024-07", 1)], Some("2024-07"))).unwrap();
+        assert!(asymmetric_only.decode::<Claims>(&token).is_err());
+    }
+
+    #[test]
+    fn jwks_publishes_the_public_keys_only() {
+        let dir = KeyDir::new();
+        let ring = KeyRing::load(&JwtConfig {
+            secret: "secret".into(),
+            ..config(
+                vec![dir.ed25519("2024-07", 1), dir.es256("2024-08", 2)],
+                Some("2024-08"),
+            )
+        })
+        .unwrap();
+        let jwks = serde_json::to_value(ring.jwks()).unwrap();
+        let keys = jwks["keys"].as_array().unwrap();
+        assert_eq!(keys.len(), 2);
+
+        let ed25519 = &keys[0];
+        assert_eq!(ed25519["kid"], "2024-07");
+        assert_eq!(ed25519["alg"], "EdDSA");
+        assert_eq!(ed25519["use"], "sig");
+        assert_eq!(ed25519["kty"], "OKP");
+        assert_eq!(ed25519["crv"], "Ed25519");
+        assert_eq!(
+            ed25519["x"]
+                .as_str()
+                .map(|x| URL_SAFE_NO_PAD.decode(x).unwrap()),
+            Some(
+                ed25519_dalek::SigningKey::from_bytes(&[1; 32])
+                    .verifying_key()
+                    .to_bytes()
+                    .to_vec()
+            )
+        );
+
+        let es256 = &keys[1];
+        assert_eq!(es256["kid"], "2024-08");
+        assert_eq!(es256["alg"], "ES256");
+        assert_eq!(es256["kty"], "EC");
+        assert_eq!(es256["crv"], "P-256");
+        assert!(es256["x"].is_string() && es256["y"].is_string());
+
+        // Neither private halves nor the HS256 secret.
+        assert!(
+            keys.iter()
+                .all(|key| key.get("d").is_none() && key["kty"] != "oct")
+        );
+    }
+}
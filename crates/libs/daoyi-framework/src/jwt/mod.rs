@@ -0,0 +1,220 @@
+use std::sync::{Arc, LazyLock, OnceLock};
+
+use anyhow::Result;
+use jsonwebtoken::TokenData;
+use jsonwebtoken::errors::{Error as JwtError, ErrorKind};
+use salvo::jwt_auth::{CookieFinder, HeaderFinder, JwtAuthDecoder, QueryFinder};
+use salvo::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+use ulid::Ulid;
+
+use crate::config::{self, JwtConfig, TokenStoreKind};
+use crate::tenant::CurrentTenant;
+
+mod keys;
+pub use keys::KeyRing;
+mod store;
+pub use store::{MemoryTokenStore, RotateError, TokenStore};
+#[cfg(feature = "redis")]
+mod redis_store;
+#[cfg(feature = "redis")]
+pub use redis_store::RedisTokenStore;
+
+static KEY_RING: OnceLock<KeyRing> = OnceLock::new();
+static TOKEN_STORE: LazyLock<Arc<dyn TokenStore>> =
+    LazyLock::new(|| token_store(&config::get().jwt));
+
+fn init_key_ring(config: &JwtConfig) -> &'static KeyRing {
+    KEY_RING.get_or_init(|| KeyRing::load(config).expect("jwt keys should load"))
+}
+
+pub fn key_ring() -> &'static KeyRing {
+    init_key_ring(&config::get().jwt)
+}
+
+/// Store chosen by `[jwt] store`: Redis, or a `MemoryTokenStore` for a single instance.
+fn token_store(config: &JwtConfig) -> Arc<dyn TokenStore> {
+    match config.store {
+        TokenStoreKind::Memory => Arc::new(MemoryTokenStore::default()),
+        #[cfg(feature = "redis")]
+        TokenStoreKind::Redis => Arc::new(RedisTokenStore::new(crate::cache::redis().clone())),
+        #[cfg(not(feature = "redis"))]
+        TokenStoreKind::Redis => {
+            tracing::warn!("token state is kept in memory: built without the `redis` feature");
+            Arc::new(MemoryTokenStore::default())
+        }
+    }
+}
+
+pub fn store() -> &'static dyn TokenStore {
+    TOKEN_STORE.as_ref()
+}
+
+/// Whether the token was revoked. If the store cannot tell, the token is refused.
+async fn is_revoked(claims: &JwtClaims) -> bool {
+    store()
+        .is_revoked(&claims.fid, &claims.jti)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!(error = %e, "failed to check token revocation, refusing the token");
+            true
+        })
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenKind {
+    #[default]
+    Access,
+    Refresh,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct JwtClaims {
+    pub uid: String,
+    /// Tenant the user belongs to.
+    pub tid: String,
+    /// Unique id of this token.
+    pub jti: String,
+    /// Family id shared by all tokens descending from the same login.
+    pub fid: String,
+    #[serde(default)]
+    pub typ: TokenKind,
+    pub exp: i64,
+}
+
+/// Decoder which only accepts access tokens that have not been revoked.
+///
+/// The token's tenant becomes the request tenant. A tenant already resolved from the request
+/// header or host name must match it.
+pub struct AccessDecoder {
+    keys: &'static KeyRing,
+}
+
+impl JwtAuthDecoder for AccessDecoder {
+    type Error = JwtError;
+
+    async fn decode<C>(&self, token: &str, depot: &mut Depot) -> Result<TokenData<C>, JwtError>
+    where
+        C: DeserializeOwned,
+    {
+        let data = self.keys.decode::<JwtClaims>(token)?;
+        if data.claims.typ != TokenKind::Access || is_revoked(&data.claims).await {
+            return Err(ErrorKind::InvalidToken.into());
+        }
+        if let Ok(CurrentTenant(tenant_id)) = depot.obtain::<CurrentTenant>()
+            && *tenant_id != data.claims.tid
+        {
+            return Err(ErrorKind::InvalidToken.into());
+        }
+        depot.inject(CurrentTenant(data.claims.tid));
+        self.keys.decode::<C>(token)
+    }
+}
+
+pub fn auth_hoop(config: &JwtConfig) -> JwtAuth<JwtClaims, AccessDecoder> {
+    JwtAuth::new(AccessDecoder {
+        keys: init_key_ring(config),
+    })
+    .finders(vec![
+        Box::new(HeaderFinder::new()),
+        Box::new(QueryFinder::new("token")),
+        Box::new(CookieFinder::new("jwt_token")),
+    ])
+    .force_passed(false)
+}
+
+#[derive(Debug)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub access_exp: i64,
+    pub refresh_token: String,
+    pub refresh_exp: i64,
+}
+
+fn encode_claims(claims: &JwtClaims) -> Result<String> {
+    key_ring().encode(claims)
+}
+
+fn sign_pair(uid: &str, tid: &str, fid: &str, refresh_jti: &str) -> Result<TokenPair> {
+    let now = OffsetDateTime::now_utc();
+    let access = JwtClaims {
+        uid: uid.to_owned(),
+        tid: tid.to_owned(),
+        jti: Ulid::new().to_string(),
+        fid: fid.to_owned(),
+        typ: TokenKind::Access,
+        exp: (now + Duration::seconds(config::get().jwt.expiry)).unix_timestamp(),
+    };
+    let refresh = JwtClaims {
+        uid: uid.to_owned(),
+        tid: tid.to_owned(),
+        jti: refresh_jti.to_owned(),
+        fid: fid.to_owned(),
+        typ: TokenKind::Refresh,
+        exp: (now + Duration::seconds(config::get().jwt.refresh_expiry)).unix_timestamp(),
+    };
+    Ok(TokenPair {
+        access_token: encode_claims(&access)?,
+        access_exp: access.exp,
+        refresh_token: encode_claims(&refresh)?,
+        refresh_exp: refresh.exp,
+    })
+}
+
+/// Issue an access/refresh pair starting a new token family.
+pub async fn issue_tokens(uid: &str, tid: &str) -> Result<TokenPair> {
+    let fid = Ulid::new().to_string();
+    let refresh_jti = Ulid::new().to_string();
+    let pair = sign_pair(uid, tid, &fid, &refresh_jti)?;
+    store()
+        .start_family(&fid, uid, &refresh_jti, pair.refresh_exp)
+        .await?;
+    Ok(pair)
+}
+
+/// Exchange a refresh token for a new pair of the same family.
+///
+/// Returns `Ok(None)` when the refresh token is invalid, expired, revoked or reused.
+pub async fn refresh_tokens(refresh_token: &str) -> Result<Option<TokenPair>> {
+    let Ok(data) = key_ring().decode::<JwtClaims>(refresh_token) else {
+        return Ok(None);
+    };
+    let claims = data.claims;
+    if claims.typ != TokenKind::Refresh {
+        return Ok(None);
+    }
+    let next_jti = Ulid::new().to_string();
+    let pair = sign_pair(&claims.uid, &claims.tid, &claims.fid, &next_jti)?;
+    match store()
+        .rotate(
+            &claims.fid,
+            &claims.uid,
+            &claims.jti,
+            &next_jti,
+            pair.refresh_exp,
+        )
+        .await
+    {
+        Ok(()) => Ok(Some(pair)),
+        Err(e) => {
+            tracing::warn!(uid = %claims.uid, fid = %claims.fid, error = ?e, "refresh token rejected");
+            Ok(None)
+        }
+    }
+}
+
+/// Revoke the given access token together with its whole family.
+pub async fn revoke(claims: &JwtClaims) -> Result<()> {
+    store().revoke_token(&claims.jti, claims.exp).await?;
+    store().revoke_family(&claims.fid).await
+}
+
+pub async fn decode_token(token: &str) -> bool {
+    match key_ring().decode::<JwtClaims>(token) {
+        Ok(data) => data.claims.typ == TokenKind::Access && !is_revoked(&data.claims).await,
+        Err(_) => false,
+    }
+}
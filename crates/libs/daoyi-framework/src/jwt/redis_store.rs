@@ -0,0 +1,207 @@
+use std::sync::LazyLock;
+
+use redis::Script;
+use salvo::async_trait;
+
+use super::{RotateError, TokenStore};
+use crate::cache::Redis;
+
+/// Move the family at `KEYS[1]` from refresh token `ARGV[2]` of user `ARGV[1]` on to `ARGV[3]`,
+/// expiring at `ARGV[4]`. A stale or foreign token revokes the family instead.
+static ROTATE: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local family = redis.call('HMGET', KEYS[1], 'uid', 'refresh_jti', 'revoked')
+        if not family[1] then
+            return 'unknown'
+        end
+        if family[3] == '1' then
+            return 'revoked'
+        end
+        if family[1] ~= ARGV[1] or family[2] ~= ARGV[2] then
+            redis.call('HSET', KEYS[1], 'revoked', '1')
+            return 'reused'
+        end
+        redis.call('HSET', KEYS[1], 'refresh_jti', ARGV[3])
+        redis.call('EXPIREAT', KEYS[1], ARGV[4])
+        return 'rotated'
+        ",
+    )
+});
+
+/// Mark the family at `KEYS[1]` revoked, without resurrecting one that already expired.
+static REVOKE_FAMILY: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        if redis.call('EXISTS', KEYS[1]) == 1 then
+            redis.call('HSET', KEYS[1], 'revoked', '1')
+        end
+        return 0
+        ",
+    )
+});
+
+/// Token state shared by every instance, in the hashes `<prefix>token-family:{fid}` and the
+/// keys `<prefix>revoked-token:{jti}`. Both expire with the tokens they concern.
+pub struct RedisTokenStore {
+    redis: Redis,
+}
+
+impl RedisTokenStore {
+    pub fn new(redis: Redis) -> Self {
+        Self { redis }
+    }
+
+    fn family_key(&self, fid: &str) -> String {
+        self.redis.key(&format!("token-family:{fid}"))
+    }
+
+    fn revoked_key(&self, jti: &str) -> String {
+        self.redis.key(&format!("revoked-token:{jti}"))
+    }
+}
+
+#[async_trait]
+impl TokenStore for RedisTokenStore {
+    async fn start_family(
+        &self,
+        fid: &str,
+        uid: &str,
+        refresh_jti: &str,
+        exp: i64,
+    ) -> anyhow::Result<()> {
+        let key = self.family_key(fid);
+        let _: () = redis::pipe()
+            .atomic()
+            .hset_multiple(&key, &[("uid", uid), ("refresh_jti", refresh_jti)])
+            .expire_at(&key, exp)
+            .query_async(&mut self.redis.conn())
+            .await?;
+        Ok(())
+    }
+
+    async fn rotate(
+        &self,
+        fid: &str,
+        uid: &str,
+        presented_jti: &str,
+        next_jti: &str,
+        exp: i64,
+    ) -> Result<(), RotateError> {
+        let outcome: String = ROTATE
+            .key(self.family_key(fid))
+            .arg(uid)
+            .arg(presented_jti)
+            .arg(next_jti)
+            .arg(exp)
+            .invoke_async(&mut self.redis.conn())
+            .await
+            .map_err(|e| RotateError::Store(e.to_string()))?;
+        match outcome.as_str() {
+            "rotated" => Ok(()),
+            "unknown" => Err(RotateError::UnknownFamily),
+            "revoked" => Err(RotateError::Revoked),
+            "reused" => Err(RotateError::Reused),
+            other => Err(RotateError::Store(format!(
+                "unexpected rotation outcome `{other}`"
+            ))),
+        }
+    }
+
+    async fn revoke_family(&self, fid: &str) -> anyhow::Result<()> {
+        let _: i64 = REVOKE_FAMILY
+            .key(self.family_key(fid))
+            .invoke_async(&mut self.redis.conn())
+            .await?;
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &str, exp: i64) -> anyhow::Result<()> {
+        let key = self.revoked_key(jti);
+        let _: () = redis::pipe()
+            .atomic()
+            .set(&key, 1)
+            .expire_at(&key, exp)
+            .query_async(&mut self.redis.conn())
+            .await?;
+        Ok(())
+    }
+
+    async fn is_revoked(&self, fid: &str, jti: &str) -> anyhow::Result<bool> {
+        let (token_revoked, family_revoked): (bool, Option<String>) = redis::pipe()
+            .exists(self.revoked_key(jti))
+            .hget(self.family_key(fid), "revoked")
+            .query_async(&mut self.redis.conn())
+            .await?;
+        Ok(token_revoked || family_revoked.as_deref() == Some("1"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::cache::testing::RedisServer;
+    use crate::jwt::store::tests;
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn rotation_reuse_revokes_family() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        tests::rotation_reuse_revokes_family(&RedisTokenStore::new(server.connect().await)).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn revoked_token_is_rejected() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        tests::revoked_token_is_rejected(&RedisTokenStore::new(server.connect().await)).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn entries_expire_with_their_tokens() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let redis = server.connect().await;
+        let store = RedisTokenStore::new(redis.clone());
+        let exp = OffsetDateTime::now_utc().unix_timestamp() + 60;
+        store.start_family("f1", "u1", "r1", exp).await.unwrap();
+        store.revoke_token("a1", exp).await.unwrap();
+
+        let mut conn = redis.conn();
+        for key in [store.family_key("f1"), store.revoked_key("a1")] {
+            let ttl: i64 = redis::cmd("TTL")
+                .arg(&key)
+                .query_async(&mut conn)
+                .await
+                .unwrap();
+            assert!((55..=60).contains(&ttl), "{key} expires in {ttl}s");
+        }
+
+        // Revoking an unknown or expired family must not create a key that never expires.
+        store.revoke_family("f2").await.unwrap();
+        let exists: bool = redis::cmd("EXISTS")
+            .arg(store.family_key("f2"))
+            .query_async(&mut conn)
+            .await
+            .unwrap();
+        assert!(!exists);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn instances_share_the_families() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let first = RedisTokenStore::new(server.connect().await);
+        let second = RedisTokenStore::new(server.connect().await);
+        let exp = OffsetDateTime::now_utc().unix_timestamp() + 60;
+        first.start_family("f1", "u1", "r1", exp).await.unwrap();
+        assert_eq!(second.rotate("f1", "u1", "r1", "r2", exp).await, Ok(()));
+        assert_eq!(
+            first.rotate("f1", "u1", "r1", "r3", exp).await,
+            Err(RotateError::Reused)
+        );
+        assert!(second.is_revoked("f1", "a1").await.unwrap());
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+
+use salvo::async_trait;
+use time::OffsetDateTime;
+
+/// Why a refresh token could not be rotated.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RotateError {
+    /// The family was never issued by this server or has already expired.
+    UnknownFamily,
+    /// The family was revoked by a logout or an earlier reuse.
+    Revoked,
+    /// An already rotated refresh token was presented again. The whole family is revoked.
+    Reused,
+    /// The store could not be reached.
+    Store(String),
+}
+
+/// Server-side state for refresh-token rotation and revocation.
+///
+/// Every login starts a token family. Refresh tokens of a family are single use: rotating one
+/// replaces it with the next, and presenting a replaced token again revokes the whole family.
+/// Families and revoked tokens are forgotten once the tokens they concern have expired.
+#[async_trait]
+pub trait TokenStore: Send + Sync {
+    /// Start a new family whose first refresh token is `refresh_jti`.
+    async fn start_family(
+        &self,
+        fid: &str,
+        uid: &str,
+        refresh_jti: &str,
+        exp: i64,
+    ) -> anyhow::Result<()>;
+
+    /// Replace `presented_jti` by `next_jti` as the live refresh token of the family.
+    async fn rotate(
+        &self,
+        fid: &str,
+        uid: &str,
+        presented_jti: &str,
+        next_jti: &str,
+        exp: i64,
+    ) -> Result<(), RotateError>;
+
+    /// Revoke every token descending from the login that started `fid`.
+    async fn revoke_family(&self, fid: &str) -> anyhow::Result<()>;
+
+    /// Revoke a single token until it would have expired anyway.
+    async fn revoke_token(&self, jti: &str, exp: i64) -> anyhow::Result<()>;
+
+    async fn is_revoked(&self, fid: &str, jti: &str) -> anyhow::Result<bool>;
+}
+
+struct Family {
+    uid: String,
+    /// Jti of the only refresh token of this family that may still be exchanged.
+    refresh_jti: String,
+    exp: i64,
+    revoked: bool,
+}
+
+#[derive(Default)]
+struct Inner {
+    families: HashMap<String, Family>,
+    /// Revoked token ids mapped to their expiry, so they can be pruned once they are dead anyway.
+    revoked: HashMap<String, i64>,
+}
+
+/// Token state of this process, for tests and single instances.
+#[derive(Default)]
+pub struct MemoryTokenStore {
+    inner: Mutex<Inner>,
+}
+
+impl MemoryTokenStore {
+    fn lock(&self) -> MutexGuard<'_, Inner> {
+        let mut inner = self.inner.lock().unwrap_or_else(PoisonError::into_inner);
+        let now = OffsetDateTime::now_utc().unix_timestamp();
+        inner.families.retain(|_, family| family.exp > now);
+        inner.revoked.retain(|_, exp| *exp > now);
+        inner
+    }
+}
+
+#[async_trait]
+impl TokenStore for MemoryTokenStore {
+    async fn start_family(
+        &self,
+        fid: &str,
+        uid: &str,
+        refresh_jti: &str,
+        exp: i64,
+    ) -> anyhow::Result<()> {
+        self.lock().families.insert(
+            fid.to_owned(),
+            Family {
+                uid: uid.to_owned(),
+                refresh_jti: refresh_jti.to_owned(),
+                exp,
+                revoked: false,
+            },
+        );
+        Ok(())
+    }
+
+    async fn rotate(
+        &self,
+        fid: &str,
+        uid: &str,
+        presented_jti: &str,
+        next_jti: &str,
+        exp: i64,
+    ) -> Result<(), RotateError> {
+        let mut inner = self.lock();
+        let Some(family) = inner.families.get_mut(fid) else {
+            return Err(RotateError::UnknownFamily);
+        };
+        if family.revoked {
+            return Err(RotateError::Revoked);
+        }
+        if family.uid != uid || family.refresh_jti != presented_jti {
+            family.revoked = true;
+            return Err(RotateError::Reused);
+        }
+        family.refresh_jti = next_jti.to_owned();
+        family.exp = exp;
+        Ok(())
+    }
+
+    async fn revoke_family(&self, fid: &str) -> anyhow::Result<()> {
+        if let Some(family) = self.lock().families.get_mut(fid) {
+            family.revoked = true;
+        }
+        Ok(())
+    }
+
+    async fn revoke_token(&self, jti: &str, exp: i64) -> anyhow::Result<()> {
+        self.lock().revoked.insert(jti.to_owned(), exp);
+        Ok(())
+    }
+
+    async fn is_revoked(&self, fid: &str, jti: &str) -> anyhow::Result<bool> {
+        let inner = self.lock();
+        Ok(inner.revoked.contains_key(jti)
+            || inner.families.get(fid).is_some_and(|family| family.revoked))
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    fn exp() -> i64 {
+        OffsetDateTime::now_utc().unix_timestamp() + 60
+    }
+
+    pub(in crate::jwt) async fn rotation_reuse_revokes_family(store: &dyn TokenStore) {
+        store.start_family("f1", "u1", "r1", exp()).await.unwrap();
+        assert_eq!(store.rotate("f1", "u1", "r1", "r2", exp()).await, Ok(()));
+        assert_eq!(
+            store.rotate("f1", "u1", "r1", "r3", exp()).await,
+            Err(RotateError::Reused)
+        );
+        assert!(store.is_revoked("f1", "a1").await.unwrap());
+        assert_eq!(
+            store.rotate("f1", "u1", "r2", "r4", exp()).await,
+            Err(RotateError::Revoked)
+        );
+        assert_eq!(
+            store.rotate("f2", "u1", "r1", "r2", exp()).await,
+            Err(RotateError::UnknownFamily)
+        );
+    }
+
+    pub(in crate::jwt) async fn revoked_token_is_rejected(store: &dyn TokenStore) {
+        store.start_family("f1", "u1", "r1", exp()).await.unwrap();
+        store.revoke_token("a1", exp()).await.unwrap();
+        assert!(store.is_revoked("f1", "a1").await.unwrap());
+        assert!(!store.is_revoked("f1", "a2").await.unwrap());
+        store.revoke_family("f1").await.unwrap();
+        assert!(store.is_revoked("f1", "a2").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn memory_rotation_reuse_revokes_family() {
+        rotation_reuse_revokes_family(&MemoryTokenStore::default()).await;
+    }
+
+    #[tokio::test]
+    async fn memory_revoked_token_is_rejected() {
+        revoked_token_is_rejected(&MemoryTokenStore::default()).await;
+    }
+}
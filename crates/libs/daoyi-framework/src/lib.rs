@@ -1,14 +1,99 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
+//! Shared core of the daoyi module servers: configuration, errors and the response envelope,
+//! plus database, Redis, Nacos, JWT, Prometheus metrics and OpenTelemetry support behind the
+//! cargo features of the same names.
+
+use salvo::prelude::*;
+use serde::Serialize;
+
+pub mod app;
+pub use app::{App, AppBuilder, Module};
+pub mod config;
+pub mod error;
+pub use error::{AppError, ErrorCode};
+pub mod health;
+pub use health::{Health, HealthCheck};
+pub mod http;
+pub mod rate_limit;
+pub mod request_id;
+pub mod tenant;
+pub mod utils;
+
+#[cfg(feature = "redis")]
+pub mod cache;
+#[cfg(feature = "db")]
+pub mod db;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "nacos")]
+pub mod nacos;
+#[cfg(feature = "otel")]
+pub mod otel;
+
+pub type AppResult<T> = Result<T, AppError>;
+pub type JsonResult<T> = Result<Json<CommonResult<T>>, AppError>;
+pub type EmptyResult = Result<Json<CommonResult<Empty>>, AppError>;
+
+/// Response envelope shared with the Java services. `code` is 0 on success, see `error::code`.
+#[derive(Serialize, Debug)]
+#[cfg_attr(feature = "oapi", derive(ToSchema))]
+pub struct CommonResult<T> {
+    pub code: i32,
+    pub msg: String,
+    pub data: Option<T>,
+    /// `X-Request-Id` of the failed request, to look up its logs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    /// Trace of the failed request, to look up its spans.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
 }
+impl<T> CommonResult<T> {
+    pub fn success(data: T) -> Self {
+        Self {
+            code: error::code::SUCCESS.code,
+            msg: String::new(),
+            data: Some(data),
+            request_id: None,
+            trace_id: None,
+        }
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub fn error(code: ErrorCode, msg: String, data: Option<T>) -> Self {
+        Self {
+            code: code.code,
+            msg,
+            data,
+            request_id: None,
+            trace_id: None,
+        }
+    }
 
-    #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    /// Attach the request id and, with the `otel` hoop, the trace id of this request.
+    pub fn correlated(self, depot: &Depot) -> Self {
+        let request_id = request_id::request_id(depot).map(str::to_owned);
+        #[cfg(feature = "otel")]
+        let trace_id = otel::trace_id(depot).map(str::to_owned);
+        #[cfg(not(feature = "otel"))]
+        let trace_id = {
+            let _ = depot;
+            None
+        };
+        Self {
+            request_id,
+            trace_id,
+            ..self
+        }
     }
 }
+
+pub fn json_ok<T>(data: T) -> JsonResult<T> {
+    Ok(Json(CommonResult::success(data)))
+}
+#[derive(Serialize, Clone, Copy, Debug)]
+#[cfg_attr(feature = "oapi", derive(ToSchema))]
+pub struct Empty {}
+pub fn empty_ok() -> JsonResult<Empty> {
+    json_ok(Empty {})
+}
@@ -0,0 +1,215 @@
+//! Prometheus metrics of HTTP requests and the database pool, served at `/metrics`.
+
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use salvo::http::header::CONTENT_TYPE;
+use salvo::prelude::*;
+
+use crate::utils;
+
+/// Request latency buckets in seconds, from 5ms to 10s.
+const HTTP_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+/// Query latency buckets in seconds, from 1ms to 5s.
+const QUERY_BUCKETS: &[f64] = &[
+    0.001, 0.0025, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0,
+];
+
+struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    duration: HistogramVec,
+    in_flight: IntGaugeVec,
+    queries: HistogramVec,
+    pool: IntGaugeVec,
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(|| {
+    let registry = Registry::new();
+    let requests = IntCounterVec::new(
+        Opts::new("http_requests_total", "HTTP requests answered."),
+        &["method", "route", "status"],
+    )
+    .expect("http_requests_total should be valid");
+    let duration = HistogramVec::new(
+        HistogramOpts::new(
+            "http_request_duration_seconds",
+            "Time taken to answer HTTP requests.",
+        )
+        .buckets(HTTP_BUCKETS.to_vec()),
+        &["method", "route"],
+    )
+    .expect("http_request_duration_seconds should be valid");
+    let in_flight = IntGaugeVec::new(
+        Opts::new("http_requests_in_flight", "HTTP requests being answered."),
+        &["method", "route"],
+    )
+    .expect("http_requests_in_flight should be valid");
+    let queries = HistogramVec::new(
+        HistogramOpts::new(
+            "db_query_duration_seconds",
+            "Time taken by database queries.",
+        )
+        .buckets(QUERY_BUCKETS.to_vec()),
+        &["operation", "outcome"],
+    )
+    .expect("db_query_duration_seconds should be valid");
+    let pool = IntGaugeVec::new(
+        Opts::new(
+            "db_pool_connections",
+            "Connections of the database pool by state: active, idle and max.",
+        ),
+        &["state"],
+    )
+    .expect("db_pool_connections should be valid");
+    registry
+        .register(Box::new(requests.clone()))
+        .expect("http_requests_total should register");
+    registry
+        .register(Box::new(duration.clone()))
+        .expect("http_request_duration_seconds should register");
+    registry
+        .register(Box::new(in_flight.clone()))
+        .expect("http_requests_in_flight should register");
+    registry
+        .register(Box::new(queries.clone()))
+        .expect("db_query_duration_seconds should register");
+    registry
+        .register(Box::new(pool.clone()))
+        .expect("db_pool_connections should register");
+    Metrics {
+        registry,
+        requests,
+        duration,
+        in_flight,
+        queries,
+        pool,
+    }
+});
+
+/// Count and time every request below the router it is installed on.
+#[handler]
+pub async fn hoop(req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+    let method = req.method().to_string();
+    let route = utils::route_path(req);
+    let in_flight = METRICS
+        .in_flight
+        .with_label_values(&[method.as_str(), route.as_str()]);
+    in_flight.inc();
+    let started_at = Instant::now();
+    ctrl.call_next(req, depot, res).await;
+    in_flight.dec();
+
+    let status = res.status_code.unwrap_or(StatusCode::OK);
+    METRICS
+        .duration
+        .with_label_values(&[method.as_str(), route.as_str()])
+        .observe(started_at.elapsed().as_secs_f64());
+    METRICS
+        .requests
+        .with_label_values(&[method.as_str(), route.as_str(), status.as_str()])
+        .inc();
+}
+
+/// Record a finished database query. Installed as the SeaORM metric callback.
+pub fn observe_query(sql: &str, elapsed: Duration, failed: bool) {
+    let operation = sql
+        .split_whitespace()
+        .next()
+        .unwrap_or_default()
+        .to_ascii_uppercase();
+    let outcome = if failed { "error" } else { "ok" };
+    METRICS
+        .queries
+        .with_label_values(&[operation.as_str(), outcome])
+        .observe(elapsed.as_secs_f64());
+}
+
+#[cfg(feature = "db")]
+fn update_pool() {
+    let Some(db) = crate::db::SEAORM_POOL.get() else {
+        return;
+    };
+    let pool = db.get_postgres_connection_pool();
+    let idle = pool.num_idle() as i64;
+    let size = pool.size() as i64;
+    METRICS.pool.with_label_values(&["active"]).set(size - idle);
+    METRICS.pool.with_label_values(&["idle"]).set(idle);
+    METRICS
+        .pool
+        .with_label_values(&["max"])
+        .set(pool.options().get_max_connections() as i64);
+}
+
+#[handler]
+async fn scrape(res: &mut Response) {
+    #[cfg(feature = "db")]
+    update_pool();
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&METRICS.registry.gather(), &mut body) {
+        tracing::error!(error = %e, "failed to encode metrics");
+        res.status_code(StatusCode::INTERNAL_SERVER_ERROR);
+        return;
+    }
+    let _ = res.add_header(CONTENT_TYPE, encoder.format_type(), true);
+    res.write_body(body).ok();
+}
+
+/// `/metrics` in the Prometheus text format.
+pub fn router() -> Router {
+    Router::with_path("metrics").get(scrape)
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::test::{ResponseExt, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn show(res: &mut Response) {
+        res.render("user");
+    }
+
+    #[tokio::test]
+    async fn counts_requests_per_route() {
+        let service = Service::new(
+            Router::new()
+                .hoop(hoop)
+                .push(router())
+                .push(Router::with_path("api/users/{user_id}").get(show)),
+        );
+        for id in ["1", "2"] {
+            TestClient::get(format!("http://127.0.0.1/api/users/{id}"))
+                .send(&service)
+                .await;
+        }
+        observe_query(
+            "select * from system_users",
+            Duration::from_millis(3),
+            false,
+        );
+
+        let body = TestClient::get("http://127.0.0.1/metrics")
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert!(body.contains(
+            r#"http_requests_total{method="GET",route="/api/users/{user_id}",status="200"} 2"#
+        ));
+        assert!(body.contains(
+            r#"http_request_duration_seconds_count{method="GET",route="/api/users/{user_id}"} 2"#
+        ));
+        assert!(
+            body.contains(r#"db_query_duration_seconds_count{operation="SELECT",outcome="ok"}"#)
+        );
+    }
+}
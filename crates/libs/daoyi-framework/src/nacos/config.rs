@@ -0,0 +1,173 @@
+//! Configuration kept in Nacos, layered into `config::init` as a Figment provider.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use figment::providers::{Format, Toml};
+use figment::value::{Dict, Map};
+use figment::{Metadata, Profile, Provider, Source};
+use md5::{Digest, Md5};
+use tokio::task::JoinHandle;
+
+use super::NacosClient;
+
+const RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// A TOML document fetched from Nacos.
+#[derive(Clone, Debug)]
+pub struct NacosProvider {
+    data_id: String,
+    group: String,
+    content: String,
+}
+
+impl NacosProvider {
+    /// Fetch the current version. A data id that was never published is an empty document.
+    pub async fn fetch(client: &NacosClient, data_id: &str, group: &str) -> anyhow::Result<Self> {
+        let content = client.get_config(data_id, group).await?;
+        if content.is_none() {
+            tracing::warn!(
+                data_id,
+                group,
+                "Nacos config not found, using local config only"
+            );
+        }
+        Ok(Self {
+            data_id: data_id.to_owned(),
+            group: group.to_owned(),
+            content: content.unwrap_or_default(),
+        })
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
+impl Provider for NacosProvider {
+    fn metadata(&self) -> Metadata {
+        Metadata::named("Nacos").source(Source::Custom(format!(
+            "nacos:{}/{}",
+            self.group, self.data_id
+        )))
+    }
+
+    fn data(&self) -> figment::Result<Map<Profile, Dict>> {
+        Toml::string(&self.content).data()
+    }
+}
+
+/// Hash Nacos uses to tell whether a listener's copy is current.
+pub(crate) fn md5(content: &str) -> String {
+    format!("{:x}", Md5::digest(content.as_bytes()))
+}
+
+/// Long-poll Nacos and call `on_change` with every new version of `provider`'s document.
+pub fn watch<F>(
+    client: Arc<NacosClient>,
+    mut provider: NacosProvider,
+    timeout: Duration,
+    on_change: F,
+) -> JoinHandle<()>
+where
+    F: Fn(&NacosProvider) + Send + 'static,
+{
+    tokio::spawn(async move {
+        loop {
+            // Nacos reports an unpublished config with an empty hash.
+            let md5 = if provider.content.is_empty() {
+                String::new()
+            } else {
+                md5(&provider.content)
+            };
+            let content = match client
+                .listen_config(&provider.data_id, &provider.group, &md5, timeout)
+                .await
+            {
+                Ok(false) => continue,
+                Ok(true) => client.get_config(&provider.data_id, &provider.group).await,
+                Err(e) => Err(e),
+            };
+            match content {
+                Ok(content) => {
+                    provider.content = content.unwrap_or_default();
+                    on_change(&provider);
+                }
+                Err(e) => {
+                    tracing::warn!(data_id = provider.data_id, error = %e, "watching Nacos config failed");
+                    tokio::time::sleep(RETRY_DELAY).await;
+                }
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use figment::Figment;
+    use tokio::sync::mpsc;
+    use tokio::time::timeout;
+
+    use super::super::testing::MockNacos;
+    use super::*;
+
+    const LOCAL: &str = "[jwt]\nsecret = \"local\"\nexpiry = 3600\n";
+
+    #[tokio::test]
+    async fn remote_values_override_the_file() {
+        let nacos = MockNacos::default();
+        let client = NacosClient::new(&nacos.start().await);
+        nacos.publish("app.toml", "DEFAULT_GROUP", "[jwt]\nexpiry = 60\n");
+
+        let remote = NacosProvider::fetch(&client, "app.toml", "DEFAULT_GROUP")
+            .await
+            .unwrap();
+        let figment = Figment::new().merge(Toml::string(LOCAL)).merge(remote);
+        assert_eq!(figment.extract_inner::<i64>("jwt.expiry").unwrap(), 60);
+        assert_eq!(
+            figment.extract_inner::<String>("jwt.secret").unwrap(),
+            "local"
+        );
+    }
+
+    #[tokio::test]
+    async fn unpublished_config_is_an_empty_layer() {
+        let nacos = MockNacos::default();
+        let client = NacosClient::new(&nacos.start().await);
+
+        let remote = NacosProvider::fetch(&client, "app.toml", "DEFAULT_GROUP")
+            .await
+            .unwrap();
+        assert_eq!(remote.content(), "");
+        let figment = Figment::new().merge(Toml::string(LOCAL)).merge(remote);
+        assert_eq!(figment.extract_inner::<i64>("jwt.expiry").unwrap(), 3600);
+    }
+
+    #[tokio::test]
+    async fn watch_delivers_published_changes() {
+        let nacos = MockNacos::default();
+        let client = NacosClient::new(&nacos.start().await);
+        nacos.publish("app.toml", "DEFAULT_GROUP", "[jwt]\nexpiry = 60\n");
+        let remote = NacosProvider::fetch(&client, "app.toml", "DEFAULT_GROUP")
+            .await
+            .unwrap();
+
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let watcher = watch(
+            Arc::new(client),
+            remote,
+            Duration::from_secs(5),
+            move |provider| {
+                let _ = tx.send(provider.content().to_owned());
+            },
+        );
+        nacos.publish("app.toml", "DEFAULT_GROUP", "[jwt]\nexpiry = 120\n");
+
+        let content = timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .expect("change should be delivered")
+            .unwrap();
+        assert_eq!(content, "[jwt]\nexpiry = 120\n");
+        watcher.abort();
+    }
+}
@@ -0,0 +1,336 @@
+//! Client for the Nacos open API.
+
+use std::sync::{Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use reqwest::StatusCode;
+use serde::Deserialize;
+
+use crate::config::NacosConfig;
+
+pub mod config;
+pub mod naming;
+
+pub struct NacosClient {
+    http: reqwest::Client,
+    server_url: String,
+    namespace: String,
+    credentials: Option<(String, String)>,
+    token: Mutex<Option<(String, Instant)>>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct LoginOutData {
+    access_token: String,
+    /// Seconds.
+    token_ttl: u64,
+}
+
+impl NacosClient {
+    pub fn new(config: &NacosConfig) -> Self {
+        Self {
+            http: reqwest::Client::new(),
+            server_url: config.server_url.trim_end_matches('/').to_owned(),
+            namespace: config.namespace.clone(),
+            credentials: config.username.clone().zip(config.password.clone()),
+            token: Mutex::new(None),
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.server_url)
+    }
+
+    /// `accessToken` query parameter when the server requires authentication.
+    async fn auth_query(&self) -> Result<Vec<(&'static str, String)>> {
+        let Some((username, password)) = &self.credentials else {
+            return Ok(Vec::new());
+        };
+        if let Some((token, expires_at)) =
+            &*self.token.lock().unwrap_or_else(PoisonError::into_inner)
+            && Instant::now() < *expires_at
+        {
+            return Ok(vec![("accessToken", token.clone())]);
+        }
+
+        let login: LoginOutData = self
+            .http
+            .post(self.url("/v1/auth/login"))
+            .form(&[("username", username), ("password", password)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        // Log in again a little before the token expires.
+        let expires_at = Instant::now() + Duration::from_secs(login.token_ttl * 9 / 10);
+        *self.token.lock().unwrap_or_else(PoisonError::into_inner) =
+            Some((login.access_token.clone(), expires_at));
+        Ok(vec![("accessToken", login.access_token)])
+    }
+
+    /// Content of a config, or `None` if it was never published.
+    pub async fn get_config(&self, data_id: &str, group: &str) -> Result<Option<String>> {
+        let res = self
+            .http
+            .get(self.url("/v1/cs/configs"))
+            .query(&[
+                ("dataId", data_id),
+                ("group", group),
+                ("tenant", &self.namespace),
+            ])
+            .query(&self.auth_query().await?)
+            .send()
+            .await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        Ok(Some(res.error_for_status()?.text().await?))
+    }
+
+    /// Wait up to `timeout` for a config to stop matching `md5`, the hash of the content we have.
+    ///
+    /// Returns whether it changed. An empty `md5` stands for a config we have not seen.
+    pub async fn listen_config(
+        &self,
+        data_id: &str,
+        group: &str,
+        md5: &str,
+        timeout: Duration,
+    ) -> Result<bool> {
+        let mut listening = format!("{data_id}\u{2}{group}\u{2}{md5}");
+        if !self.namespace.is_empty() {
+            listening.push('\u{2}');
+            listening.push_str(&self.namespace);
+        }
+        listening.push('\u{1}');
+
+        let changed = self
+            .http
+            .post(self.url("/v1/cs/configs/listener"))
+            .header("Long-Pulling-Timeout", timeout.as_millis().to_string())
+            .timeout(timeout + Duration::from_secs(10))
+            .query(&self.auth_query().await?)
+            .form(&[("Listening-Configs", listening)])
+            .send()
+            .await?
+            .error_for_status()?
+            .text()
+            .await?;
+        Ok(!changed.trim().is_empty())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    use salvo::http::Method;
+    use salvo::prelude::*;
+    use serde_json::json;
+    use tokio::sync::Notify;
+
+    use super::naming::Instance;
+    use crate::config::NacosConfig;
+
+    /// In-process stand-in for the Nacos open API, serving configs and instances from memory.
+    #[derive(Clone, Default)]
+    pub struct MockNacos {
+        configs: Arc<Mutex<HashMap<(String, String), String>>>,
+        changed: Arc<Notify>,
+        instances: Arc<Mutex<HashMap<String, Vec<Instance>>>>,
+        beats: Arc<Mutex<HashMap<String, usize>>>,
+        down: Arc<AtomicBool>,
+    }
+
+    impl MockNacos {
+        /// Serve on a free local port and return the settings pointing at it.
+        pub async fn start(&self) -> NacosConfig {
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            self.start_on(port).await
+        }
+
+        /// Serve on `port`, e.g. one clients were already pointed at while nothing listened.
+        pub async fn start_on(&self, port: u16) -> NacosConfig {
+            let acceptor = TcpListener::new(("127.0.0.1", port)).bind().await;
+            let router = Router::with_path("{**rest}").goal(self.clone());
+            tokio::spawn(Server::new(acceptor).serve(router));
+            NacosConfig {
+                server_url: format!("http://127.0.0.1:{port}/nacos"),
+                ..Default::default()
+            }
+        }
+
+        pub fn publish(&self, data_id: &str, group: &str, content: &str) {
+            self.configs
+                .lock()
+                .unwrap()
+                .insert((data_id.to_owned(), group.to_owned()), content.to_owned());
+            self.changed.notify_waiters();
+        }
+
+        pub fn put_instance(&self, service: &str, instance: Instance) {
+            let mut instances = self.instances.lock().unwrap();
+            let instances = instances.entry(service.to_owned()).or_default();
+            instances.retain(|known| (&known.ip, known.port) != (&instance.ip, instance.port));
+            instances.push(instance);
+        }
+
+        pub fn instances(&self, service: &str) -> Vec<Instance> {
+            self.instances
+                .lock()
+                .unwrap()
+                .get(service)
+                .cloned()
+                .unwrap_or_default()
+        }
+
+        /// Forget every instance of `service`, as the server does when heartbeats stop.
+        pub fn expire(&self, service: &str) {
+            self.instances.lock().unwrap().remove(service);
+        }
+
+        /// Answer every request with a 503 while `down`, as an overloaded or restarting server.
+        pub fn set_down(&self, down: bool) {
+            self.down.store(down, Ordering::Relaxed);
+        }
+
+        pub fn beats(&self, service: &str) -> usize {
+            self.beats
+                .lock()
+                .unwrap()
+                .get(service)
+                .copied()
+                .unwrap_or_default()
+        }
+
+        fn config(&self, data_id: &str, group: &str) -> Option<String> {
+            self.configs
+                .lock()
+                .unwrap()
+                .get(&(data_id.to_owned(), group.to_owned()))
+                .cloned()
+        }
+    }
+
+    #[async_trait]
+    impl Handler for MockNacos {
+        async fn handle(
+            &self,
+            req: &mut Request,
+            _depot: &mut Depot,
+            res: &mut Response,
+            _ctrl: &mut FlowCtrl,
+        ) {
+            if self.down.load(Ordering::Relaxed) {
+                res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+                return;
+            }
+            match req.uri().path() {
+                "/nacos/v1/cs/configs" => {
+                    let data_id = req.query::<String>("dataId").unwrap_or_default();
+                    let group = req.query::<String>("group").unwrap_or_default();
+                    match self.config(&data_id, &group) {
+                        Some(content) => res.render(content),
+                        None => {
+                            res.status_code(StatusCode::NOT_FOUND);
+                        }
+                    }
+                }
+                "/nacos/v1/cs/configs/listener" => {
+                    let listening = req
+                        .form::<String>("Listening-Configs")
+                        .await
+                        .unwrap_or_default();
+                    let timeout = req.header::<u64>("Long-Pulling-Timeout").unwrap_or(30_000);
+                    let mut fields = listening.trim_end_matches('\u{1}').split('\u{2}');
+                    let data_id = fields.next().unwrap_or_default().to_owned();
+                    let group = fields.next().unwrap_or_default().to_owned();
+                    let md5 = fields.next().unwrap_or_default().to_owned();
+
+                    let deadline = tokio::time::sleep(Duration::from_millis(timeout));
+                    tokio::pin!(deadline);
+                    loop {
+                        let notified = self.changed.notified();
+                        let current = self
+                            .config(&data_id, &group)
+                            .map(|content| super::config::md5(&content))
+                            .unwrap_or_default();
+                        if current != md5 {
+                            res.render(format!("{data_id}%02{group}%01"));
+                            return;
+                        }
+                        tokio::select! {
+                            _ = notified => {}
+                            _ = &mut deadline => return,
+                        }
+                    }
+                }
+                "/nacos/v1/ns/instance" => {
+                    let service = req.query::<String>("serviceName").unwrap_or_default();
+                    let mut instance = Instance::new(
+                        req.query::<String>("ip").unwrap_or_default(),
+                        req.query::<u16>("port").unwrap_or_default(),
+                    );
+                    if *req.method() == Method::DELETE {
+                        let mut instances = self.instances.lock().unwrap();
+                        if let Some(instances) = instances.get_mut(&service) {
+                            instances.retain(|known| {
+                                (&known.ip, known.port) != (&instance.ip, instance.port)
+                            });
+                        }
+                    } else {
+                        instance.weight = req.query("weight").unwrap_or(1.0);
+                        instance.healthy = req.query("healthy").unwrap_or(true);
+                        instance.enabled = req.query("enabled").unwrap_or(true);
+                        instance.metadata = req
+                            .query::<String>("metadata")
+                            .and_then(|metadata| serde_json::from_str(&metadata).ok())
+                            .unwrap_or_default();
+                        self.put_instance(&service, instance);
+                    }
+                    res.render("ok");
+                }
+                "/nacos/v1/ns/instance/beat" => {
+                    let service = req.query::<String>("serviceName").unwrap_or_default();
+                    let ip = req.query::<String>("ip").unwrap_or_default();
+                    let port = req.query::<u16>("port").unwrap_or_default();
+                    *self
+                        .beats
+                        .lock()
+                        .unwrap()
+                        .entry(service.clone())
+                        .or_default() += 1;
+                    let known = self
+                        .instances(&service)
+                        .iter()
+                        .any(|instance| instance.ip == ip && instance.port == port);
+                    let code = if known { 10200 } else { 20404 };
+                    res.render(Json(json!({ "clientBeatInterval": 5000, "code": code })));
+                }
+                "/nacos/v1/ns/instance/list" => {
+                    let service = req.query::<String>("serviceName").unwrap_or_default();
+                    let healthy_only = req.query::<bool>("healthyOnly").unwrap_or(false);
+                    let hosts = self
+                        .instances(&service)
+                        .into_iter()
+                        .filter(|instance| instance.healthy || !healthy_only)
+                        .collect::<Vec<_>>();
+                    res.render(Json(json!({ "name": service, "hosts": hosts })));
+                }
+                _ => {
+                    res.status_code(StatusCode::NOT_FOUND);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,578 @@
+//! Service registration and discovery.
+
+use std::collections::HashMap;
+use std::net::{IpAddr, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use super::NacosClient;
+use crate::config::{NacosConfig, ServerConfig, default_true};
+
+/// Beat answer of a server that no longer knows the instance, e.g. after it expired.
+const RESOURCE_NOT_FOUND: i32 = 20404;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Instance {
+    pub ip: String,
+    pub port: u16,
+    #[serde(default = "default_weight")]
+    pub weight: f64,
+    #[serde(default = "default_true")]
+    pub healthy: bool,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+}
+
+fn default_weight() -> f64 {
+    1.0
+}
+
+impl Instance {
+    pub fn new(ip: impl Into<String>, port: u16) -> Self {
+        Self {
+            ip: ip.into(),
+            port,
+            weight: default_weight(),
+            healthy: true,
+            enabled: true,
+            metadata: HashMap::new(),
+        }
+    }
+
+    /// Whether the instance serves HTTPS, as announced in its `secure` metadata.
+    pub fn secure(&self) -> bool {
+        self.metadata
+            .get("secure")
+            .is_some_and(|secure| secure == "true")
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct BeatOutData {
+    code: i32,
+}
+
+#[derive(Deserialize)]
+struct InstanceListOutData {
+    #[serde(default)]
+    hosts: Vec<Instance>,
+}
+
+impl NacosClient {
+    fn instance_query(
+        &self,
+        service: &str,
+        group: &str,
+        instance: &Instance,
+    ) -> Vec<(&'static str, String)> {
+        vec![
+            ("serviceName", service.to_owned()),
+            ("groupName", group.to_owned()),
+            ("namespaceId", self.namespace.clone()),
+            ("ip", instance.ip.clone()),
+            ("port", instance.port.to_string()),
+            ("ephemeral", "true".to_owned()),
+        ]
+    }
+
+    /// Register an ephemeral instance, which the server drops when its heartbeats stop.
+    pub async fn register_instance(
+        &self,
+        service: &str,
+        group: &str,
+        instance: &Instance,
+    ) -> Result<()> {
+        self.http
+            .post(self.url("/v1/ns/instance"))
+            .query(&self.instance_query(service, group, instance))
+            .query(&[
+                ("weight", instance.weight.to_string()),
+                ("healthy", instance.healthy.to_string()),
+                ("enabled", instance.enabled.to_string()),
+                ("metadata", serde_json::to_string(&instance.metadata)?),
+            ])
+            .query(&self.auth_query().await?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn deregister_instance(
+        &self,
+        service: &str,
+        group: &str,
+        instance: &Instance,
+    ) -> Result<()> {
+        self.http
+            .delete(self.url("/v1/ns/instance"))
+            .query(&self.instance_query(service, group, instance))
+            .query(&self.auth_query().await?)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(())
+    }
+
+    /// Renew an instance's lease. Returns false if the server no longer knows the instance.
+    pub async fn send_beat(&self, service: &str, group: &str, instance: &Instance) -> Result<bool> {
+        let beat = serde_json::json!({
+            "serviceName": service,
+            "ip": instance.ip,
+            "port": instance.port,
+            "weight": instance.weight,
+            "metadata": instance.metadata,
+            "scheduled": true,
+        });
+        let beat: BeatOutData = self
+            .http
+            .put(self.url("/v1/ns/instance/beat"))
+            .query(&self.instance_query(service, group, instance))
+            .query(&[("beat", beat.to_string())])
+            .query(&self.auth_query().await?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(beat.code != RESOURCE_NOT_FOUND)
+    }
+
+    pub async fn list_instances(
+        &self,
+        service: &str,
+        group: &str,
+        healthy_only: bool,
+    ) -> Result<Vec<Instance>> {
+        let list: InstanceListOutData = self
+            .http
+            .get(self.url("/v1/ns/instance/list"))
+            .query(&[
+                ("serviceName", service),
+                ("groupName", group),
+                ("namespaceId", &self.namespace),
+                ("healthyOnly", if healthy_only { "true" } else { "false" }),
+            ])
+            .query(&self.auth_query().await?)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(list.hosts)
+    }
+}
+
+/// Whether the last heartbeat renewed the registration.
+#[derive(Clone, Default)]
+pub struct RegistrationStatus(Arc<AtomicBool>);
+
+impl RegistrationStatus {
+    pub fn is_registered(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    fn set(&self, registered: bool) {
+        self.0.store(registered, Ordering::Relaxed);
+    }
+}
+
+/// This server's instance, kept alive by heartbeats until `deregister`.
+pub struct Registration {
+    client: Arc<NacosClient>,
+    service: String,
+    group: String,
+    instance: Instance,
+    status: RegistrationStatus,
+    heartbeat: JoinHandle<()>,
+}
+
+impl Registration {
+    /// Register `instance`, failing if Nacos does not accept it.
+    pub async fn register(
+        client: Arc<NacosClient>,
+        service: &str,
+        group: &str,
+        instance: Instance,
+        heartbeat_interval: Duration,
+    ) -> Result<Self> {
+        client.register_instance(service, group, &instance).await?;
+        let status = RegistrationStatus::default();
+        status.set(true);
+        Ok(Self::keep_alive(
+            client,
+            service,
+            group,
+            instance,
+            heartbeat_interval,
+            status,
+        ))
+    }
+
+    /// Register `instance` without failing: if Nacos is unreachable, the heartbeats register it
+    /// once it answers, and `status` reports it unregistered meanwhile.
+    pub async fn start(
+        client: Arc<NacosClient>,
+        service: &str,
+        group: &str,
+        instance: Instance,
+        heartbeat_interval: Duration,
+    ) -> Self {
+        let status = RegistrationStatus::default();
+        match client.register_instance(service, group, &instance).await {
+            Ok(()) => status.set(true),
+            Err(e) => tracing::warn!(service, error = %e, "Nacos registration failed, retrying"),
+        }
+        Self::keep_alive(client, service, group, instance, heartbeat_interval, status)
+    }
+
+    fn keep_alive(
+        client: Arc<NacosClient>,
+        service: &str,
+        group: &str,
+        instance: Instance,
+        heartbeat_interval: Duration,
+        status: RegistrationStatus,
+    ) -> Self {
+        let heartbeat = tokio::spawn(heartbeat(
+            client.clone(),
+            service.to_owned(),
+            group.to_owned(),
+            instance.clone(),
+            heartbeat_interval,
+            status.clone(),
+        ));
+        Self {
+            client,
+            service: service.to_owned(),
+            group: group.to_owned(),
+            instance,
+            status,
+            heartbeat,
+        }
+    }
+
+    pub fn instance(&self) -> &Instance {
+        &self.instance
+    }
+
+    /// Handle following the registration, for health checks.
+    pub fn status(&self) -> RegistrationStatus {
+        self.status.clone()
+    }
+
+    /// Stop the heartbeats and remove the instance, so no new traffic is routed here.
+    pub async fn deregister(self) -> Result<()> {
+        self.heartbeat.abort();
+        self.status.set(false);
+        self.client
+            .deregister_instance(&self.service, &self.group, &self.instance)
+            .await
+    }
+}
+
+async fn heartbeat(
+    client: Arc<NacosClient>,
+    service: String,
+    group: String,
+    instance: Instance,
+    interval: Duration,
+    status: RegistrationStatus,
+) {
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    loop {
+        ticker.tick().await;
+        let renewed = if status.is_registered() {
+            match client.send_beat(&service, &group, &instance).await {
+                Ok(true) => Ok(()),
+                // The lease expired, e.g. while Nacos was unreachable.
+                Ok(false) => client.register_instance(&service, &group, &instance).await,
+                Err(e) => Err(e),
+            }
+        } else {
+            client.register_instance(&service, &group, &instance).await
+        };
+        status.set(renewed.is_ok());
+        if let Err(e) = renewed {
+            tracing::warn!(service, error = %e, "Nacos heartbeat failed");
+        }
+    }
+}
+
+/// Register this server under `nacos.service_name`, retrying in the background while Nacos is
+/// unreachable. `None` when registration is not configured; an error when it is misconfigured.
+pub async fn register(config: &ServerConfig) -> Result<Option<Registration>> {
+    let Some(nacos) = &config.nacos else {
+        return Ok(None);
+    };
+    let Some(service) = &nacos.service_name else {
+        return Ok(None);
+    };
+    let instance = local_instance(config, nacos)?;
+    let registration = Registration::start(
+        Arc::new(NacosClient::new(nacos)),
+        service,
+        &nacos.group,
+        instance,
+        Duration::from_secs(nacos.heartbeat_interval),
+    )
+    .await;
+    if registration.status().is_registered() {
+        tracing::info!(
+            service,
+            ip = registration.instance().ip,
+            port = registration.instance().port,
+            "registered with Nacos"
+        );
+    }
+    Ok(Some(registration))
+}
+
+fn local_instance(config: &ServerConfig, nacos: &NacosConfig) -> Result<Instance> {
+    let (host, port) = config
+        .listen_addr
+        .rsplit_once(':')
+        .context("listen_addr should be host:port")?;
+    let port = port.parse().context("listen_addr should end with a port")?;
+    let ip = match &nacos.ip {
+        Some(ip) => ip.clone(),
+        None => match host.parse::<IpAddr>() {
+            Ok(ip) if !ip.is_unspecified() => ip.to_string(),
+            _ => outbound_ip(&nacos.server_url)?.to_string(),
+        },
+    };
+
+    let mut instance = Instance::new(ip, port);
+    instance.weight = nacos.weight;
+    instance.metadata = nacos.metadata.clone();
+    instance
+        .metadata
+        .insert("version".into(), env!("CARGO_PKG_VERSION").into());
+    instance
+        .metadata
+        .insert("secure".into(), config.tls.is_some().to_string());
+    Ok(instance)
+}
+
+/// Address of the interface used to reach the Nacos server.
+fn outbound_ip(server_url: &str) -> Result<IpAddr> {
+    let url = reqwest::Url::parse(server_url)?;
+    let host = url
+        .host_str()
+        .context("Nacos server_url should have a host")?;
+    let port = url.port_or_known_default().unwrap_or(8848);
+    // Connecting a UDP socket sends nothing; it only selects the route.
+    let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+    socket.connect((host, port))?;
+    Ok(socket.local_addr()?.ip())
+}
+
+/// Instances last found for each service, and when.
+type Found = HashMap<String, (Instant, Vec<Instance>)>;
+
+/// Looks up instances of other services in the configured namespace and group.
+///
+/// The instances last found are reused while Nacos is unreachable.
+#[derive(Clone)]
+pub struct Discovery {
+    client: Arc<NacosClient>,
+    group: String,
+    cache_for: Duration,
+    found: Arc<Mutex<Found>>,
+}
+
+impl Discovery {
+    pub fn new(config: &NacosConfig) -> Self {
+        Self {
+            client: Arc::new(NacosClient::new(config)),
+            group: config.group.clone(),
+            cache_for: Duration::ZERO,
+            found: Arc::default(),
+        }
+    }
+
+    /// Reuse each service's instances for `ttl` instead of asking Nacos on every lookup.
+    pub fn cache_for(mut self, ttl: Duration) -> Self {
+        self.cache_for = ttl;
+        self
+    }
+
+    /// Instances of `service` that are healthy and enabled.
+    pub async fn healthy_instances(&self, service: &str) -> Result<Vec<Instance>> {
+        let found = self
+            .found
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(service)
+            .cloned();
+        if let Some((found_at, instances)) = &found
+            && found_at.elapsed() < self.cache_for
+        {
+            return Ok(instances.clone());
+        }
+
+        let mut instances = match self.client.list_instances(service, &self.group, true).await {
+            Ok(instances) => instances,
+            Err(e) => match found {
+                Some((_, instances)) => {
+                    tracing::warn!(service, error = %e, "using stale instances");
+                    return Ok(instances);
+                }
+                None => return Err(e),
+            },
+        };
+        instances.retain(|instance| instance.healthy && instance.enabled);
+        self.found
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(service.to_owned(), (Instant::now(), instances.clone()));
+        Ok(instances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::testing::MockNacos;
+    use super::*;
+
+    #[tokio::test]
+    async fn registers_and_deregisters_with_metadata() {
+        let nacos = MockNacos::default();
+        let config = nacos.start().await;
+        let mut instance = Instance::new("10.0.0.1", 8008);
+        instance.metadata.insert("version".into(), "1.0.0".into());
+
+        let registration = Registration::register(
+            Arc::new(NacosClient::new(&config)),
+            "system-server",
+            "DEFAULT_GROUP",
+            instance.clone(),
+            Duration::from_secs(5),
+        )
+        .await
+        .unwrap();
+        let discovery = Discovery::new(&config);
+        assert_eq!(
+            discovery.healthy_instances("system-server").await.unwrap(),
+            vec![instance]
+        );
+        let status = registration.status();
+        assert!(status.is_registered());
+
+        registration.deregister().await.unwrap();
+        assert!(!status.is_registered());
+        assert!(
+            discovery
+                .healthy_instances("system-server")
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn heartbeat_registers_again_after_expiry() {
+        let nacos = MockNacos::default();
+        let config = nacos.start().await;
+        let _registration = Registration::register(
+            Arc::new(NacosClient::new(&config)),
+            "infra-server",
+            "DEFAULT_GROUP",
+            Instance::new("10.0.0.2", 8009),
+            Duration::from_millis(100),
+        )
+        .await
+        .unwrap();
+
+        nacos.expire("infra-server");
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert_eq!(nacos.instances("infra-server").len(), 1);
+        assert!(nacos.beats("infra-server") > 0);
+    }
+
+    #[tokio::test]
+    async fn start_registers_once_nacos_answers() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let config = NacosConfig {
+            server_url: format!("http://127.0.0.1:{port}/nacos"),
+            ..Default::default()
+        };
+        let registration = Registration::start(
+            Arc::new(NacosClient::new(&config)),
+            "pay-server",
+            "DEFAULT_GROUP",
+            Instance::new("10.0.0.6", 8010),
+            Duration::from_millis(100),
+        )
+        .await;
+        let status = registration.status();
+        assert!(!status.is_registered());
+
+        let nacos = MockNacos::default();
+        nacos.start_on(port).await;
+        tokio::time::sleep(Duration::from_millis(500)).await;
+        assert!(status.is_registered());
+        assert_eq!(nacos.instances("pay-server").len(), 1);
+    }
+
+    #[tokio::test]
+    async fn discovery_skips_unhealthy_and_disabled_instances() {
+        let nacos = MockNacos::default();
+        let config = nacos.start().await;
+        let healthy = Instance::new("10.0.0.3", 8008);
+        let mut unhealthy = Instance::new("10.0.0.4", 8008);
+        unhealthy.healthy = false;
+        let mut disabled = Instance::new("10.0.0.5", 8008);
+        disabled.enabled = false;
+        for instance in [&healthy, &unhealthy, &disabled] {
+            nacos.put_instance("member-server", instance.clone());
+        }
+
+        let instances = Discovery::new(&config)
+            .healthy_instances("member-server")
+            .await
+            .unwrap();
+        assert_eq!(instances, vec![healthy]);
+    }
+
+    #[tokio::test]
+    async fn discovery_caches_and_outlives_nacos_outages() {
+        let nacos = MockNacos::default();
+        let config = nacos.start().await;
+        let first = Instance::new("10.0.0.7", 8008);
+        nacos.put_instance("trade-server", first.clone());
+
+        let fresh = Discovery::new(&config);
+        let cached = Discovery::new(&config).cache_for(Duration::from_secs(60));
+        for discovery in [&fresh, &cached] {
+            let instances = discovery.healthy_instances("trade-server").await.unwrap();
+            assert_eq!(instances, vec![first.clone()]);
+        }
+
+        let second = Instance::new("10.0.0.8", 8008);
+        nacos.put_instance("trade-server", second.clone());
+        let instances = fresh.healthy_instances("trade-server").await.unwrap();
+        assert_eq!(instances, vec![first.clone(), second]);
+        let instances = cached.healthy_instances("trade-server").await.unwrap();
+        assert_eq!(instances, vec![first]);
+
+        nacos.set_down(true);
+        let instances = fresh.healthy_instances("trade-server").await.unwrap();
+        assert_eq!(instances.len(), 2);
+        assert!(fresh.healthy_instances("other-server").await.is_err());
+    }
+}
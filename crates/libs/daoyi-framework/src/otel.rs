@@ -0,0 +1,262 @@
+//! OpenTelemetry span export and W3C trace context propagation.
+//!
+//! With `[log.otel]` set, `LogConfig::guard` layers an OTLP exporter onto the subscriber. The
+//! `hoop` continues the trace of the caller's `traceparent` header, or starts one, and answers
+//! with the `traceparent` of the request span. The trace id is recorded on the span, so JSON
+//! logs carry it, and kept in the depot for error responses.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::{TraceContextExt, TracerProvider as _};
+use opentelemetry::{Context, global};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider, Tracer};
+use salvo::http::HeaderMap;
+use salvo::http::header::{HeaderName, HeaderValue};
+use salvo::prelude::*;
+use tracing::Instrument;
+use tracing::field::Empty;
+use tracing::subscriber::Subscriber;
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::config::{OtelConfig, OtelProtocol};
+
+/// Installed provider, flushed by `shutdown`.
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// Trace id of the current request, as 32 hex digits.
+#[derive(Clone, Debug)]
+pub struct TraceId(pub String);
+
+/// Trace id the `hoop` stored for this request.
+pub fn trace_id(depot: &Depot) -> Option<&str> {
+    depot.obtain::<TraceId>().ok().map(|id| id.0.as_str())
+}
+
+fn provider(config: &OtelConfig) -> anyhow::Result<SdkTracerProvider> {
+    let timeout = Duration::from_secs(config.timeout);
+    let exporter = match config.protocol {
+        OtelProtocol::Grpc => SpanExporter::builder()
+            .with_tonic()
+            .with_endpoint(&config.endpoint)
+            .with_timeout(timeout)
+            .build()?,
+        OtelProtocol::Http => SpanExporter::builder()
+            .with_http()
+            .with_endpoint(format!(
+                "{}/v1/traces",
+                config.endpoint.trim_end_matches('/')
+            ))
+            .with_timeout(timeout)
+            .build()?,
+    };
+    Ok(SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build())
+}
+
+/// Layer exporting spans as configured. `None`, after printing why, if the exporter cannot be
+/// built.
+pub(crate) fn layer<S>(config: &OtelConfig) -> Option<OpenTelemetryLayer<S, Tracer>>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let provider = match provider(config) {
+        Ok(provider) => provider,
+        Err(e) => {
+            eprintln!("OpenTelemetry export is disabled: {e:#}");
+            return None;
+        }
+    };
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    global::set_tracer_provider(provider.clone());
+    let tracer = provider.tracer(env!("CARGO_PKG_NAME"));
+    let _ = PROVIDER.set(provider);
+    Some(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Export the spans still buffered. Call once the server has stopped.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get()
+        && let Err(e) = provider.shutdown()
+    {
+        eprintln!("failed to flush OpenTelemetry spans: {e}");
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let Ok(name) = HeaderName::from_bytes(key.as_bytes())
+            && let Ok(value) = HeaderValue::from_str(&value)
+        {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+fn valid_trace_id(cx: &Context) -> Option<String> {
+    let span = cx.span();
+    let span_context = span.span_context();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/// Run the request in a span continuing the caller's trace, and answer with its `traceparent`.
+#[handler]
+pub async fn hoop(req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    let span = tracing::info_span!(
+        "request",
+        otel.name = %format!("{} {}", req.method(), req.uri().path()),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %req.method(),
+        url.path = req.uri().path(),
+        http.response.status_code = Empty,
+        trace_id = Empty,
+    );
+    span.set_parent(parent.clone());
+    let cx = span.context();
+    // Without an exporter the span has no OpenTelemetry context, but the caller's trace id
+    // still ties the logs together.
+    if let Some(trace_id) = valid_trace_id(&cx).or_else(|| valid_trace_id(&parent)) {
+        span.record("trace_id", trace_id.as_str());
+        depot.inject(TraceId(trace_id));
+    }
+
+    ctrl.call_next(req, depot, res)
+        .instrument(span.clone())
+        .await;
+
+    let status = res.status_code.unwrap_or(StatusCode::OK);
+    span.record("http.response.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+    if valid_trace_id(&cx).is_some() {
+        global::get_text_map_propagator(|propagator| {
+            propagator.inject_context(&cx, &mut HeaderInjector(res.headers_mut()))
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use salvo::test::{ResponseExt, TestClient};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    use super::*;
+
+    /// In-process stand-in for an OTLP/HTTP collector, remembering the paths posted to.
+    #[derive(Clone, Default)]
+    struct Collector {
+        paths: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl Collector {
+        async fn start(&self) -> String {
+            let port = std::net::TcpListener::bind("127.0.0.1:0")
+                .unwrap()
+                .local_addr()
+                .unwrap()
+                .port();
+            let acceptor = TcpListener::new(("127.0.0.1", port)).bind().await;
+            let router = Router::with_path("{**rest}").goal(self.clone());
+            tokio::spawn(Server::new(acceptor).serve(router));
+            format!("http://127.0.0.1:{port}")
+        }
+    }
+
+    #[async_trait]
+    impl Handler for Collector {
+        async fn handle(
+            &self,
+            req: &mut Request,
+            _depot: &mut Depot,
+            res: &mut Response,
+            _ctrl: &mut FlowCtrl,
+        ) {
+            self.paths.lock().unwrap().push(req.uri().path().to_owned());
+            res.status_code(StatusCode::OK);
+        }
+    }
+
+    #[handler]
+    async fn show_trace_id(depot: &mut Depot, res: &mut Response) {
+        res.render(trace_id(depot).unwrap_or_default().to_owned());
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn continues_the_callers_trace_and_exports_the_span() {
+        let collector = Collector::default();
+        let config = OtelConfig {
+            endpoint: collector.start().await,
+            protocol: OtelProtocol::Http,
+            ..Default::default()
+        };
+        let subscriber = tracing_subscriber::registry().with(layer(&config));
+        let _default = tracing::subscriber::set_default(subscriber);
+        let service = Service::new(Router::new().hoop(hoop).get(show_trace_id));
+
+        let mut res = TestClient::get("http://127.0.0.1/")
+            .add_header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+                true,
+            )
+            .send(&service)
+            .await;
+        let traceparent = res.headers().get("traceparent").unwrap().to_str().unwrap();
+        assert!(traceparent.starts_with("00-4bf92f3577b34da6a3ce929d0e0e4736-"));
+        assert!(!traceparent.contains("00f067aa0ba902b7"));
+        assert_eq!(
+            res.take_string().await.unwrap(),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+
+        let provider = PROVIDER.get().unwrap().clone();
+        tokio::task::spawn_blocking(move || provider.force_flush())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(
+            collector
+                .paths
+                .lock()
+                .unwrap()
+                .contains(&"/v1/traces".to_owned())
+        );
+    }
+}
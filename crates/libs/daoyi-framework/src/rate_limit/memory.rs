@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use salvo::async_trait;
+
+use super::{Algorithm, Decision, RateLimitStore, now_millis};
+
+/// Entries kept before expired ones are swept.
+const SWEEP_AT: usize = 10_000;
+
+enum State {
+    TokenBucket {
+        tokens: f64,
+        updated: u64,
+    },
+    SlidingWindow {
+        index: u64,
+        previous: u64,
+        current: u64,
+    },
+}
+
+struct Entry {
+    state: State,
+    /// When the entry says nothing anymore that a fresh one would not, in Unix milliseconds.
+    expires: u64,
+}
+
+/// Counters of this process, for a single instance.
+#[derive(Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryStore {
+    fn hit_at(&self, key: &str, algorithm: &Algorithm, now: u64) -> Decision {
+        let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+        if entries.len() >= SWEEP_AT {
+            entries.retain(|_, entry| entry.expires > now);
+        }
+        match *algorithm {
+            Algorithm::TokenBucket {
+                capacity,
+                per_second,
+            } => {
+                let entry = entries.entry(key.to_owned()).or_insert(Entry {
+                    state: State::TokenBucket {
+                        tokens: capacity as f64,
+                        updated: now,
+                    },
+                    expires: now,
+                });
+                let (mut tokens, updated) = match entry.state {
+                    State::TokenBucket { tokens, updated } => (tokens, updated),
+                    State::SlidingWindow { .. } => (capacity as f64, now),
+                };
+                let refill = now.saturating_sub(updated) as f64 / 1000.0 * per_second;
+                tokens = (tokens + refill).min(capacity as f64);
+                let allowed = tokens >= 1.0;
+                if allowed {
+                    tokens -= 1.0;
+                }
+                let decision = Decision::token_bucket(capacity, per_second, tokens, allowed);
+                *entry = Entry {
+                    state: State::TokenBucket {
+                        tokens,
+                        updated: now,
+                    },
+                    expires: now + decision.reset.as_millis() as u64,
+                };
+                decision
+            }
+            Algorithm::SlidingWindow { limit, window } => {
+                let window_ms = (window.as_millis() as u64).max(1);
+                let index = now / window_ms;
+                let elapsed = now % window_ms;
+                let entry = entries.entry(key.to_owned()).or_insert(Entry {
+                    state: State::SlidingWindow {
+                        index,
+                        previous: 0,
+                        current: 0,
+                    },
+                    expires: now,
+                });
+                let (previous, mut current) = match entry.state {
+                    State::SlidingWindow {
+                        index: last,
+                        previous,
+                        current,
+                    } if last == index => (previous, current),
+                    State::SlidingWindow {
+                        index: last,
+                        current,
+                        ..
+                    } if last + 1 == index => (current, 0),
+                    _ => (0, 0),
+                };
+                let used = previous as f64 * (window_ms - elapsed) as f64 / window_ms as f64
+                    + current as f64;
+                let allowed = used + 1.0 <= limit as f64;
+                if allowed {
+                    current += 1;
+                }
+                *entry = Entry {
+                    state: State::SlidingWindow {
+                        index,
+                        previous,
+                        current,
+                    },
+                    expires: (index + 2) * window_ms,
+                };
+                Decision::sliding_window(
+                    limit,
+                    window,
+                    Duration::from_millis(elapsed),
+                    previous,
+                    current,
+                    allowed,
+                )
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for MemoryStore {
+    async fn hit(&self, key: &str, algorithm: &Algorithm) -> anyhow::Result<Decision> {
+        Ok(self.hit_at(key, algorithm, now_millis()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_bucket_refills_over_time() {
+        let store = MemoryStore::default();
+        let bucket = Algorithm::TokenBucket {
+            capacity: 2,
+            per_second: 1.0,
+        };
+        assert!(store.hit_at("k", &bucket, 0).allowed);
+        assert!(store.hit_at("k", &bucket, 0).allowed);
+        let denied = store.hit_at("k", &bucket, 0);
+        assert!(!denied.allowed);
+        assert_eq!(denied.retry_after, Some(Duration::from_secs(1)));
+        assert!(store.hit_at("k", &bucket, 1000).allowed);
+        assert!(!store.hit_at("k", &bucket, 1000).allowed);
+    }
+
+    #[test]
+    fn sliding_window_carries_part_of_the_previous_window() {
+        let store = MemoryStore::default();
+        let window = Algorithm::SlidingWindow {
+            limit: 4,
+            window: Duration::from_secs(10),
+        };
+        for _ in 0..4 {
+            assert!(store.hit_at("k", &window, 1_000).allowed);
+        }
+        assert!(!store.hit_at("k", &window, 9_000).allowed);
+        // Halfway into the next window half of the previous 4 still count.
+        let decision = store.hit_at("k", &window, 15_000);
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 1);
+        assert!(store.hit_at("k", &window, 15_000).allowed);
+        assert!(!store.hit_at("k", &window, 15_000).allowed);
+        // Two windows later nothing is left.
+        assert_eq!(store.hit_at("k", &window, 30_000).remaining, 3);
+    }
+}
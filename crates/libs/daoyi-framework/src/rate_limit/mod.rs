@@ -0,0 +1,346 @@
+//! Request throttling per client address, user, tenant and route.
+//!
+//! ```ignore
+//! Router::with_path("login")
+//!     .hoop(RateLimit::sliding_window("login", 10, Duration::from_secs(60)).by(Key::Ip))
+//!     .post(post_login)
+//! ```
+//!
+//! Counters live in the `MemoryStore` of this process unless the limiter is given another
+//! store, such as the `RedisStore` shared by every instance. A request over the limit gets 429
+//! with `Retry-After`, and every response below the limiter carries `X-RateLimit-Limit`,
+//! `X-RateLimit-Remaining` and `X-RateLimit-Reset`. If the store fails the request passes.
+
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[cfg(feature = "jwt")]
+use salvo::jwt_auth::JwtAuthDepotExt;
+use salvo::prelude::*;
+use serde::Deserialize;
+
+use crate::error::code;
+use crate::tenant::CurrentTenant;
+use crate::{AppError, utils};
+
+mod memory;
+pub use memory::MemoryStore;
+#[cfg(feature = "redis")]
+mod redis_store;
+#[cfg(feature = "redis")]
+pub use redis_store::RedisStore;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+    /// Bursts of up to `capacity` requests, refilled at `per_second`.
+    TokenBucket { capacity: u64, per_second: f64 },
+    /// At most `limit` requests in any `window`, estimated from the counts of the current and
+    /// the previous fixed window.
+    SlidingWindow { limit: u64, window: Duration },
+}
+
+/// Part of the request that identifies whose requests are counted together.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Key {
+    /// Client address, read from `X-Forwarded-For` behind the `trusted_proxies` so clients
+    /// behind the gateway are not all counted as the gateway.
+    Ip,
+    /// `uid` of the access token, or the client address without one.
+    User,
+    /// Resolved tenant, or the client address without one.
+    Tenant,
+    /// Matched route, e.g. `/api/users/{user_id}`.
+    Route,
+}
+
+impl Key {
+    fn of(self, req: &Request, depot: &Depot) -> String {
+        match self {
+            Self::Ip => format!("ip:{}", utils::client_ip(req)),
+            Self::User => match user(depot) {
+                Some(uid) => format!("uid:{uid}"),
+                None => Self::Ip.of(req, depot),
+            },
+            Self::Tenant => match depot.obtain::<CurrentTenant>() {
+                Ok(CurrentTenant(tenant_id)) => format!("tid:{tenant_id}"),
+                Err(_) => Self::Ip.of(req, depot),
+            },
+            Self::Route => format!("{} {}", req.method(), utils::route_path(req)),
+        }
+    }
+}
+
+#[cfg(feature = "jwt")]
+fn user(depot: &Depot) -> Option<String> {
+    depot
+        .jwt_auth_data::<crate::jwt::JwtClaims>()
+        .map(|data| data.claims.uid.clone())
+}
+
+#[cfg(not(feature = "jwt"))]
+fn user(_depot: &Depot) -> Option<String> {
+    None
+}
+
+/// Outcome of counting one request.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u64,
+    pub remaining: u64,
+    /// Until the limit is fully available again.
+    pub reset: Duration,
+    /// Until a request would be allowed. Only set when this one was not.
+    pub retry_after: Option<Duration>,
+}
+
+impl Decision {
+    /// Token bucket left with `tokens` after taking one if `allowed`.
+    pub(crate) fn token_bucket(capacity: u64, per_second: f64, tokens: f64, allowed: bool) -> Self {
+        Self {
+            allowed,
+            limit: capacity,
+            remaining: tokens.floor().max(0.0) as u64,
+            reset: Duration::from_secs_f64((capacity as f64 - tokens).max(0.0) / per_second),
+            retry_after: (!allowed)
+                .then(|| Duration::from_secs_f64((1.0 - tokens).max(0.0) / per_second)),
+        }
+    }
+
+    /// Sliding window counter `elapsed` into the current fixed window, after counting the
+    /// request in `current` if `allowed`.
+    pub(crate) fn sliding_window(
+        limit: u64,
+        window: Duration,
+        elapsed: Duration,
+        previous: u64,
+        current: u64,
+        allowed: bool,
+    ) -> Self {
+        let window_s = window.as_secs_f64();
+        let left = (window - elapsed.min(window)).as_secs_f64();
+        let used = previous as f64 * left / window_s + current as f64;
+        let retry_after = (!allowed).then(|| {
+            let seconds = if current < limit {
+                // The previous window's share shrinks until one more request fits.
+                left - (limit - 1 - current) as f64 * window_s / previous.max(1) as f64
+            } else {
+                // This window is full: wait for the next one, then for its share to shrink.
+                left + window_s * (1.0 - (limit.max(1) - 1) as f64 / current as f64)
+            };
+            Duration::from_secs_f64(seconds.max(0.0))
+        });
+        Self {
+            allowed,
+            limit,
+            remaining: limit.saturating_sub(used.ceil() as u64),
+            reset: Duration::from_secs_f64(if current > 0 { left + window_s } else { left }),
+            retry_after,
+        }
+    }
+}
+
+/// Counters of the limiters.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    /// Count a request under `key` and decide whether it may pass.
+    async fn hit(&self, key: &str, algorithm: &Algorithm) -> anyhow::Result<Decision>;
+}
+
+/// Milliseconds since the Unix epoch.
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+static MEMORY: LazyLock<Arc<MemoryStore>> = LazyLock::new(Default::default);
+
+/// Where the counters of a server's `RateLimit` hoops are kept, e.g. a module's `[rate_limit]`
+/// section.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct RateLimitConfig {
+    /// Use `redis` when several instances serve the same clients.
+    #[serde(default)]
+    pub store: RateLimitStoreKind,
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum RateLimitStoreKind {
+    #[default]
+    Memory,
+    Redis,
+}
+
+/// Store chosen by `config.store`: the process-wide `MemoryStore`, or Redis.
+pub fn store(config: &RateLimitConfig) -> Arc<dyn RateLimitStore> {
+    match config.store {
+        RateLimitStoreKind::Memory => MEMORY.clone(),
+        #[cfg(feature = "redis")]
+        RateLimitStoreKind::Redis => Arc::new(RedisStore::new(crate::cache::redis().clone())),
+        #[cfg(not(feature = "redis"))]
+        RateLimitStoreKind::Redis => {
+            tracing::warn!("rate limits are kept in memory: built without the `redis` feature");
+            MEMORY.clone()
+        }
+    }
+}
+
+/// Hoop counting requests under a name and the request `Key`s.
+pub struct RateLimit {
+    name: String,
+    algorithm: Algorithm,
+    keys: Vec<Key>,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimit {
+    pub fn new(name: impl Into<String>, algorithm: Algorithm) -> Self {
+        Self {
+            name: name.into(),
+            algorithm,
+            keys: Vec::new(),
+            store: MEMORY.clone(),
+        }
+    }
+
+    pub fn token_bucket(name: impl Into<String>, capacity: u64, per_second: f64) -> Self {
+        Self::new(
+            name,
+            Algorithm::TokenBucket {
+                capacity,
+                per_second,
+            },
+        )
+    }
+
+    pub fn sliding_window(name: impl Into<String>, limit: u64, window: Duration) -> Self {
+        Self::new(name, Algorithm::SlidingWindow { limit, window })
+    }
+
+    /// Count requests separately per value of `key`, on top of the keys added before. Without
+    /// keys every request shares one counter.
+    pub fn by(mut self, key: Key) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    pub fn store(mut self, store: Arc<dyn RateLimitStore>) -> Self {
+        self.store = store;
+        self
+    }
+
+    fn key(&self, req: &Request, depot: &Depot) -> String {
+        let mut key = format!("rate-limit:{}", self.name);
+        for part in &self.keys {
+            key.push(':');
+            key.push_str(&part.of(req, depot));
+        }
+        key
+    }
+}
+
+fn set_header(res: &mut Response, name: &'static str, value: u64) {
+    let _ = res.add_header(name, value, true);
+}
+
+/// Whole seconds, rounded up so clients do not retry early.
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_millis().div_ceil(1000) as u64
+}
+
+#[async_trait]
+impl Handler for RateLimit {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let key = self.key(req, depot);
+        let decision = match self.store.hit(&key, &self.algorithm).await {
+            Ok(decision) => decision,
+            Err(e) => {
+                tracing::warn!(key, error = %e, "rate limit store failed, letting the request pass");
+                return;
+            }
+        };
+        set_header(res, "x-ratelimit-limit", decision.limit);
+        set_header(res, "x-ratelimit-remaining", decision.remaining);
+        set_header(res, "x-ratelimit-reset", ceil_secs(decision.reset));
+        if !decision.allowed {
+            let retry_after = decision.retry_after.map_or(1, ceil_secs).max(1);
+            set_header(res, "retry-after", retry_after);
+            AppError::from(code::TOO_MANY_REQUESTS)
+                .write(req, depot, res)
+                .await;
+            ctrl.skip_rest();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::test::TestClient;
+
+    use super::*;
+
+    #[handler]
+    async fn ok(res: &mut Response) {
+        res.render("ok");
+    }
+
+    fn header(res: &Response, name: &str) -> Option<u64> {
+        res.headers().get(name)?.to_str().ok()?.parse().ok()
+    }
+
+    #[tokio::test]
+    async fn answers_429_with_retry_after_once_the_window_is_full() {
+        let limiter = RateLimit::sliding_window("window-test", 2, Duration::from_secs(60))
+            .by(Key::Ip)
+            .by(Key::Route)
+            .store(Arc::new(MemoryStore::default()));
+        let service = Service::new(
+            Router::new()
+                .hoop(limiter)
+                .push(Router::with_path("a").get(ok))
+                .push(Router::with_path("b").get(ok)),
+        );
+
+        for remaining in [1, 0] {
+            let res = TestClient::get("http://127.0.0.1/a").send(&service).await;
+            assert_eq!(res.status_code.unwrap_or(StatusCode::OK), StatusCode::OK);
+            assert_eq!(header(&res, "x-ratelimit-limit"), Some(2));
+            assert_eq!(header(&res, "x-ratelimit-remaining"), Some(remaining));
+        }
+        let res = TestClient::get("http://127.0.0.1/a").send(&service).await;
+        assert_eq!(res.status_code, Some(StatusCode::TOO_MANY_REQUESTS));
+        assert!(header(&res, "retry-after").is_some_and(|seconds| (1..=120).contains(&seconds)));
+
+        let res = TestClient::get("http://127.0.0.1/b").send(&service).await;
+        assert_eq!(res.status_code.unwrap_or(StatusCode::OK), StatusCode::OK);
+    }
+
+    #[test]
+    fn sliding_window_weighs_the_previous_window() {
+        let window = Duration::from_secs(60);
+        // 45s into the window, a quarter of the previous one still counts: 8 * 0.25 + 7 = 9.
+        let decision = Decision::sliding_window(10, window, Duration::from_secs(45), 8, 7, true);
+        assert_eq!(decision.remaining, 1);
+        // With 9 in the current window, one more fits once the previous one no longer counts.
+        let decision = Decision::sliding_window(10, window, Duration::from_secs(45), 8, 9, false);
+        assert!(!decision.allowed);
+        assert_eq!(decision.retry_after, Some(Duration::from_secs(15)));
+    }
+
+    #[test]
+    fn token_bucket_retries_after_one_token_refills() {
+        let decision = Decision::token_bucket(5, 2.0, 0.5, false);
+        assert_eq!(decision.remaining, 0);
+        assert_eq!(decision.retry_after, Some(Duration::from_millis(250)));
+        assert_eq!(decision.reset, Duration::from_millis(2250));
+    }
+}
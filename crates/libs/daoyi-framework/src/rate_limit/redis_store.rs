@@ -0,0 +1,143 @@
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use redis::Script;
+use salvo::async_trait;
+
+use super::{Algorithm, Decision, RateLimitStore};
+use crate::cache::Redis;
+
+/// Refill the bucket stored at `KEYS[1]` by the Redis clock and take a token if there is one.
+/// Returns whether a token was taken and the tokens left.
+static TOKEN_BUCKET: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local capacity = tonumber(ARGV[1])
+        local per_ms = tonumber(ARGV[2])
+        local time = redis.call('TIME')
+        local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+        local state = redis.call('HMGET', KEYS[1], 'tokens', 'updated')
+        local tokens = tonumber(state[1]) or capacity
+        local updated = tonumber(state[2]) or now
+        tokens = math.min(capacity, tokens + math.max(0, now - updated) * per_ms)
+        local allowed = 0
+        if tokens >= 1 then
+            tokens = tokens - 1
+            allowed = 1
+        end
+        redis.call('HSET', KEYS[1], 'tokens', tostring(tokens), 'updated', now)
+        redis.call('PEXPIRE', KEYS[1], math.ceil((capacity - tokens) / per_ms) + 1000)
+        return {allowed, tostring(tokens)}
+        ",
+    )
+});
+
+/// Count a request in the window of `KEYS[1]` that the Redis clock is in, if the weighted
+/// count of this and the previous window leaves room. Returns whether it was counted, both
+/// counts and the milliseconds elapsed in the current window.
+static SLIDING_WINDOW: LazyLock<Script> = LazyLock::new(|| {
+    Script::new(
+        r"
+        local limit = tonumber(ARGV[1])
+        local window = tonumber(ARGV[2])
+        local time = redis.call('TIME')
+        local now = tonumber(time[1]) * 1000 + math.floor(tonumber(time[2]) / 1000)
+        local index = math.floor(now / window)
+        local elapsed = now - index * window
+        local current_key = KEYS[1] .. ':' .. index
+        local previous = tonumber(redis.call('GET', KEYS[1] .. ':' .. (index - 1)) or '0')
+        local current = tonumber(redis.call('GET', current_key) or '0')
+        local allowed = 0
+        if previous * (window - elapsed) / window + current + 1 <= limit then
+            current = redis.call('INCR', current_key)
+            redis.call('PEXPIRE', current_key, window * 2)
+            allowed = 1
+        end
+        return {allowed, previous, current, elapsed}
+        ",
+    )
+});
+
+/// Counters shared by every instance through Redis, timed by the Redis clock.
+pub struct RedisStore {
+    redis: Redis,
+}
+
+impl RedisStore {
+    pub fn new(redis: Redis) -> Self {
+        Self { redis }
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for RedisStore {
+    async fn hit(&self, key: &str, algorithm: &Algorithm) -> anyhow::Result<Decision> {
+        let key = self.redis.key(key);
+        let mut conn = self.redis.conn();
+        Ok(match *algorithm {
+            Algorithm::TokenBucket {
+                capacity,
+                per_second,
+            } => {
+                let (allowed, tokens): (u8, String) = TOKEN_BUCKET
+                    .key(&key)
+                    .arg(capacity)
+                    .arg(per_second / 1000.0)
+                    .invoke_async(&mut conn)
+                    .await?;
+                Decision::token_bucket(capacity, per_second, tokens.parse()?, allowed == 1)
+            }
+            Algorithm::SlidingWindow { limit, window } => {
+                let (allowed, previous, current, elapsed): (u8, u64, u64, u64) = SLIDING_WINDOW
+                    .key(&key)
+                    .arg(limit)
+                    .arg((window.as_millis() as u64).max(1))
+                    .invoke_async(&mut conn)
+                    .await?;
+                Decision::sliding_window(
+                    limit,
+                    window,
+                    Duration::from_millis(elapsed),
+                    previous,
+                    current,
+                    allowed == 1,
+                )
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::testing::RedisServer;
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn instances_share_the_counters() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let first = RedisStore::new(server.connect().await);
+        let second = RedisStore::new(server.connect().await);
+
+        let window = Algorithm::SlidingWindow {
+            limit: 3,
+            window: Duration::from_secs(60),
+        };
+        assert!(first.hit("login:ip", &window).await.unwrap().allowed);
+        assert!(second.hit("login:ip", &window).await.unwrap().allowed);
+        assert!(first.hit("login:ip", &window).await.unwrap().allowed);
+        let denied = second.hit("login:ip", &window).await.unwrap();
+        assert!(!denied.allowed);
+        assert!(denied.retry_after.is_some());
+
+        let bucket = Algorithm::TokenBucket {
+            capacity: 2,
+            per_second: 0.01,
+        };
+        assert!(first.hit("api:uid", &bucket).await.unwrap().allowed);
+        let decision = second.hit("api:uid", &bucket).await.unwrap();
+        assert!(decision.allowed);
+        assert_eq!(decision.remaining, 0);
+        assert!(!first.hit("api:uid", &bucket).await.unwrap().allowed);
+    }
+}
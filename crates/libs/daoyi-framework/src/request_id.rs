@@ -0,0 +1,95 @@
+//! `X-Request-Id` correlation of a request with its log lines and error response.
+
+use salvo::http::header::HeaderValue;
+use salvo::prelude::*;
+use tracing::Instrument;
+use ulid::Ulid;
+
+pub const HEADER: &str = "x-request-id";
+
+/// Longest id accepted from a caller. Longer ones are replaced rather than logged.
+const MAX_LEN: usize = 128;
+
+/// Id of the current request.
+#[derive(Clone, Debug)]
+pub struct RequestId(pub String);
+
+/// Id the `hoop` stored for this request.
+pub fn request_id(depot: &Depot) -> Option<&str> {
+    depot.obtain::<RequestId>().ok().map(|id| id.0.as_str())
+}
+
+/// Whether a caller's id is safe to log and echo.
+fn acceptable(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_LEN
+        && id
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'-' | b'_' | b'.' | b':'))
+}
+
+/// Keep the caller's `X-Request-Id` or generate a ULID, and run the request in a span carrying
+/// it. The id is echoed in the response header and in error bodies.
+#[handler]
+pub async fn hoop(req: &mut Request, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+    let id = req
+        .header::<String>(HEADER)
+        .filter(|id| acceptable(id))
+        .unwrap_or_else(|| Ulid::new().to_string());
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        // Later hoops and proxied calls see the id that is logged.
+        req.headers_mut().insert(HEADER, value.clone());
+        res.headers_mut().insert(HEADER, value);
+    }
+    let span = tracing::info_span!("correlation", request_id = %id);
+    depot.inject(RequestId(id));
+    ctrl.call_next(req, depot, res).instrument(span).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::test::{ResponseExt, TestClient};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::{AppError, AppResult};
+
+    #[handler]
+    async fn fail() -> AppResult<()> {
+        Err(AppError::public("bad input"))
+    }
+
+    fn service() -> Service {
+        Service::new(Router::new().hoop(hoop).get(fail))
+    }
+
+    #[tokio::test]
+    async fn generates_an_id_and_puts_it_in_the_error_body() {
+        let mut res = TestClient::get("http://127.0.0.1/").send(&service()).await;
+        let id = res
+            .headers()
+            .get(HEADER)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_owned();
+        assert!(id.parse::<Ulid>().is_ok());
+        let body = res.take_json::<Value>().await.unwrap();
+        assert_eq!(body["request_id"], id.as_str());
+    }
+
+    #[tokio::test]
+    async fn keeps_the_callers_id_unless_unsafe() {
+        let res = TestClient::get("http://127.0.0.1/")
+            .add_header(HEADER, "gateway-7f3a", true)
+            .send(&service())
+            .await;
+        assert_eq!(res.headers().get(HEADER).unwrap(), "gateway-7f3a");
+
+        let res = TestClient::get("http://127.0.0.1/")
+            .add_header(HEADER, "a b", true)
+            .send(&service())
+            .await;
+        assert_ne!(res.headers().get(HEADER).unwrap(), "a b");
+    }
+}
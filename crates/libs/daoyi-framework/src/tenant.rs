@@ -0,0 +1,6 @@
+/// Tenant of the current request, stored in the `Depot`.
+///
+/// Set by the tenant hoop from the tenant header or host name, and replaced by the token's
+/// tenant once `jwt::auth_hoop` has accepted a token.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CurrentTenant(pub String);
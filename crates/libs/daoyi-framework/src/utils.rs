@@ -0,0 +1,225 @@
+use argon2::{
+    Argon2, PasswordHash,
+    password_hash::{SaltString, rand_core::OsRng},
+};
+use rand::RngExt;
+use salvo::Request;
+use salvo::http::HeaderName;
+use serde::Deserialize;
+use std::iter;
+use std::net::{IpAddr, SocketAddr};
+use std::str::FromStr;
+
+use crate::config;
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+
+#[inline]
+pub fn random_string(limit: usize) -> String {
+    iter::repeat(())
+        .map(|_| rand::rng().sample(rand::distr::Alphanumeric))
+        .map(char::from)
+        .take(limit)
+        .collect()
+}
+
+pub fn verify_password(password: &str, password_hash: &str) -> anyhow::Result<()> {
+    let hash = PasswordHash::new(password_hash)
+        .map_err(|e| anyhow::anyhow!("invalid password hash: {}", e))?;
+    let result = hash.verify_password(&[&Argon2::default()], password);
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(anyhow::anyhow!("invalid password")),
+    }
+}
+
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    Ok(PasswordHash::generate(Argon2::default(), password, &salt)
+        .map_err(|e| anyhow::anyhow!("failed to generate password hash: {}", e))?
+        .to_string())
+}
+
+/// Address or CIDR block in `trusted_proxies`, e.g. `10.0.0.0/8` or `192.168.1.10`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct IpNet {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpNet {
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr
+            .parse::<IpAddr>()
+            .map_err(|e| format!("invalid address `{s}`: {e}"))?
+            .to_canonical();
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix| *prefix <= max)
+                .ok_or_else(|| format!("invalid prefix length in `{s}`"))?,
+            None => max,
+        };
+        Ok(Self { addr, prefix })
+    }
+}
+
+impl TryFrom<String> for IpNet {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+/// Address of the client that sent the request, without the port.
+///
+/// Behind proxies listed in `trusted_proxies`, such as the gateway, this is the address they
+/// put in `X-Forwarded-For`. The header is read from the right and only past trusted proxies,
+/// so clients cannot choose their address by sending it themselves.
+pub fn client_ip(req: &Request) -> String {
+    let Some(peer) = req.remote_addr().clone().into_std().map(|addr| addr.ip()) else {
+        return req.remote_addr().to_string();
+    };
+    let Some(config) = config::try_get() else {
+        return peer.to_string();
+    };
+    let hops = req
+        .headers()
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .collect::<Vec<_>>();
+    forwarded_client(peer, &hops, &config.trusted_proxies).to_string()
+}
+
+/// First address, going back from `peer` through `hops`, that is not a trusted proxy.
+fn forwarded_client(peer: IpAddr, hops: &[&str], trusted: &[IpNet]) -> IpAddr {
+    let is_trusted = |ip: IpAddr| trusted.iter().any(|net| net.contains(ip));
+    let mut client = peer.to_canonical();
+    for hop in hops.iter().rev() {
+        if !is_trusted(client) {
+            break;
+        }
+        let hop = hop.trim();
+        match hop
+            .parse::<IpAddr>()
+            .or_else(|_| hop.parse::<SocketAddr>().map(|addr| addr.ip()))
+        {
+            Ok(ip) => client = ip.to_canonical(),
+            // A proxy we trust wrote something else: its own address is all we know.
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+/// Path of the matched route, with parameter values put back as `{name}`, e.g.
+/// `/api/users/{user_id}` for every user.
+pub fn route_path(req: &Request) -> String {
+    let mut route = req.uri().path().to_owned();
+    for (name, value) in req.params().iter() {
+        if let Some(start) = find_segments(&route, value) {
+            route.replace_range(start..start + value.len(), &format!("{{{name}}}"));
+        }
+    }
+    route
+}
+
+/// Start of the last occurrence of `value` spanning whole path segments.
+fn find_segments(path: &str, value: &str) -> Option<usize> {
+    if value.is_empty() {
+        return None;
+    }
+    path.rmatch_indices(value)
+        .map(|(start, _)| start)
+        .find(|&start| {
+            let end = start + value.len();
+            path[..start].ends_with('/') && (end == path.len() || path[end..].starts_with('/'))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn networks_contain_their_addresses() {
+        assert!(net("10.0.0.0/8").contains(ip("10.1.2.3")));
+        assert!(net("10.0.0.0/8").contains(ip("::ffff:10.1.2.3")));
+        assert!(!net("10.0.0.0/8").contains(ip("11.0.0.1")));
+        assert!(net("0.0.0.0/0").contains(ip("8.8.8.8")));
+        assert!(net("fd00::/8").contains(ip("fd12::1")));
+        assert!(net("192.168.1.10").contains(ip("192.168.1.10")));
+        assert!("10.0.0.0/33".parse::<IpNet>().is_err());
+        assert!("gateway".parse::<IpNet>().is_err());
+    }
+
+    #[test]
+    fn forwarded_for_is_only_followed_through_trusted_proxies() {
+        let trusted = [net("10.0.0.0/8")];
+        // Direct clients cannot claim another address.
+        assert_eq!(
+            forwarded_client(ip("203.0.113.7"), &["1.1.1.1"], &trusted),
+            ip("203.0.113.7")
+        );
+        // Behind the gateway the address it appended counts, not what the client sent.
+        assert_eq!(
+            forwarded_client(ip("10.0.0.2"), &["1.1.1.1", " 203.0.113.7"], &trusted),
+            ip("203.0.113.7")
+        );
+        // Chains of trusted proxies are followed.
+        assert_eq!(
+            forwarded_client(ip("10.0.0.2"), &["203.0.113.7", "10.0.0.3"], &trusted),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            forwarded_client(ip("10.0.0.2"), &["garbage"], &trusted),
+            ip("10.0.0.2")
+        );
+        let direct = forwarded_client(ip("10.0.0.2"), &[], &trusted);
+        assert_eq!(direct, ip("10.0.0.2"));
+    }
+
+    #[test]
+    fn parameters_become_placeholders() {
+        assert_eq!(find_segments("/api/users/42/roles", "42"), Some(11));
+        assert_eq!(find_segments("/api/users/142", "42"), None);
+        assert_eq!(find_segments("/assets/css/app.css", "css/app.css"), Some(8));
+        assert_eq!(find_segments("/api/users/users", "users"), Some(11));
+    }
+}
@@ -0,0 +1,398 @@
+//! Gray release: which requests a route sends to its canary upstreams, and shadow comparison of
+//! the canary's answers with the primary's.
+
+use std::collections::HashSet;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::sync::{Mutex, PoisonError};
+
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use bytes::Bytes;
+use salvo::http::header::AUTHORIZATION;
+use salvo::http::{Method, StatusCode};
+use salvo::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::ShadowConfig;
+
+/// At most this many differences are recorded per request.
+const MAX_DIFFERENCES: usize = 20;
+
+/// A rule sending matching requests to the canary. A route's rules are alternatives.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RuleConfig {
+    /// Share of callers in percent, stable per user or tenant so a caller stays on one side.
+    Weight {
+        percent: u8,
+    },
+    Header {
+        name: String,
+        values: Vec<String>,
+    },
+    Cookie {
+        name: String,
+        values: Vec<String>,
+    },
+    /// Tenant ids from the tenant header or the `tid` claim of a bearer JWT.
+    Tenant {
+        values: Vec<String>,
+    },
+    /// User ids from the `uid` claim of a bearer JWT.
+    User {
+        values: Vec<String>,
+    },
+}
+
+/// Who is calling, as far as the gateway can tell without verifying anything.
+///
+/// Only used to pick a backend; the backend still authenticates the request.
+struct Caller {
+    tenant: Option<String>,
+    user: Option<String>,
+}
+
+impl Caller {
+    fn of(req: &Request, tenant_header: &str) -> Self {
+        let claims = req
+            .header::<String>(AUTHORIZATION)
+            .and_then(|auth| unverified_claims(&auth));
+        let claim = |name: &str| {
+            claims
+                .as_ref()
+                .and_then(|claims| claims.get(name)?.as_str().map(str::to_owned))
+        };
+        Self {
+            tenant: req.header::<String>(tenant_header).or_else(|| claim("tid")),
+            user: claim("uid"),
+        }
+    }
+}
+
+fn unverified_claims(authorization: &str) -> Option<Value> {
+    let token = authorization.strip_prefix("Bearer ")?;
+    let payload = token.split('.').nth(1)?;
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok()
+}
+
+/// FNV-1a, so every gateway instance puts a caller in the same bucket.
+fn bucket(key: &str) -> u64 {
+    let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    });
+    hash % 100
+}
+
+pub struct Rules {
+    rules: Vec<RuleConfig>,
+    tenant_header: String,
+}
+
+impl Rules {
+    pub fn new(rules: Vec<RuleConfig>, tenant_header: &str) -> Self {
+        Self {
+            rules,
+            tenant_header: tenant_header.to_owned(),
+        }
+    }
+
+    pub fn matches(&self, req: &Request) -> bool {
+        let caller = Caller::of(req, &self.tenant_header);
+        self.rules.iter().any(|rule| match rule {
+            RuleConfig::Weight { percent } => {
+                let bucket = match caller.user.as_ref().or(caller.tenant.as_ref()) {
+                    Some(key) => bucket(key),
+                    None => rand::random_range(0..100),
+                };
+                bucket < *percent as u64
+            }
+            RuleConfig::Header { name, values } => req
+                .headers()
+                .get_all(name.as_str())
+                .iter()
+                .filter_map(|value| value.to_str().ok())
+                .any(|value| values.iter().any(|expected| expected == value)),
+            RuleConfig::Cookie { name, values } => req
+                .cookie(name)
+                .is_some_and(|cookie| values.iter().any(|expected| expected == cookie.value())),
+            RuleConfig::Tenant { values } => caller
+                .tenant
+                .as_ref()
+                .is_some_and(|tenant| values.contains(tenant)),
+            RuleConfig::User { values } => caller
+                .user
+                .as_ref()
+                .is_some_and(|user| values.contains(user)),
+        })
+    }
+}
+
+/// Mirrors primary traffic to the canary and compares the answers.
+pub struct Shadow {
+    percent: u8,
+    methods: Vec<Method>,
+    ignore_fields: HashSet<String>,
+}
+
+impl Shadow {
+    /// Only safe methods may be mirrored: the canary usually shares the database, and nothing
+    /// stops it from acting on a mirrored write.
+    pub fn new(config: &ShadowConfig) -> anyhow::Result<Self> {
+        let methods = config
+            .methods
+            .iter()
+            .map(|method| {
+                let method = method.to_uppercase().parse::<Method>()?;
+                if !method.is_safe() {
+                    anyhow::bail!("shadow.methods may only list safe methods, not {method}");
+                }
+                Ok(method)
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self {
+            percent: config.percent,
+            methods,
+            ignore_fields: config.ignore_fields.iter().cloned().collect(),
+        })
+    }
+
+    /// Whether to mirror this request.
+    pub fn samples(&self, method: &Method) -> bool {
+        self.methods.contains(method) && rand::random_range(0..100) < self.percent
+    }
+
+    /// Differences between two answers. JSON bodies are compared field by field.
+    pub fn compare(&self, primary: &Answer, shadow: &Answer) -> Vec<String> {
+        let mut differences = Vec::new();
+        if primary.status != shadow.status {
+            differences.push(format!("status: {} != {}", primary.status, shadow.status));
+        }
+        match (
+            serde_json::from_slice::<Value>(&primary.body),
+            serde_json::from_slice::<Value>(&shadow.body),
+        ) {
+            (Ok(primary), Ok(shadow)) => {
+                self.compare_json("$", &primary, &shadow, &mut differences)
+            }
+            _ if primary.body != shadow.body => differences.push("body differs".into()),
+            _ => {}
+        }
+        differences.truncate(MAX_DIFFERENCES);
+        differences
+    }
+
+    fn compare_json(&self, path: &str, primary: &Value, shadow: &Value, out: &mut Vec<String>) {
+        if out.len() >= MAX_DIFFERENCES {
+            return;
+        }
+        match (primary, shadow) {
+            (Value::Object(primary), Value::Object(shadow)) => {
+                let keys = primary
+                    .keys()
+                    .chain(shadow.keys().filter(|key| !primary.contains_key(*key)));
+                for key in keys {
+                    if self.ignore_fields.contains(key) {
+                        continue;
+                    }
+                    let path = format!("{path}.{key}");
+                    match (primary.get(key), shadow.get(key)) {
+                        (Some(primary), Some(shadow)) => {
+                            self.compare_json(&path, primary, shadow, out)
+                        }
+                        (primary, shadow) => out.push(format!(
+                            "{path}: {} != {}",
+                            primary.unwrap_or(&Value::Null),
+                            shadow.unwrap_or(&Value::Null)
+                        )),
+                    }
+                }
+            }
+            (Value::Array(primary), Value::Array(shadow)) if primary.len() == shadow.len() => {
+                for (i, (primary, shadow)) in primary.iter().zip(shadow).enumerate() {
+                    self.compare_json(&format!("{path}[{i}]"), primary, shadow, out);
+                }
+            }
+            _ if primary == shadow => {}
+            _ => out.push(format!("{path}: {primary} != {shadow}")),
+        }
+    }
+}
+
+/// A buffered upstream answer.
+pub struct Answer {
+    pub status: StatusCode,
+    pub body: Bytes,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ShadowDiff {
+    pub module: String,
+    pub method: String,
+    pub path: String,
+    pub differences: Vec<String>,
+}
+
+/// Logs shadow differences and appends them as JSON lines to `shadow_log`, if configured.
+pub struct ShadowRecorder {
+    file: Option<Mutex<File>>,
+}
+
+impl ShadowRecorder {
+    pub fn open(path: Option<&str>) -> io::Result<Self> {
+        let file = match path {
+            Some(path) => Some(Mutex::new(
+                OpenOptions::new().create(true).append(true).open(path)?,
+            )),
+            None => None,
+        };
+        Ok(Self { file })
+    }
+
+    pub fn record(&self, diff: &ShadowDiff) {
+        tracing::warn!(
+            module = diff.module,
+            method = diff.method,
+            path = diff.path,
+            differences = ?diff.differences,
+            "shadow response differs"
+        );
+        let Some(file) = &self.file else {
+            return;
+        };
+        let mut line = serde_json::to_vec(diff).unwrap_or_default();
+        line.push(b'\n');
+        if let Err(e) = file
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .write_all(&line)
+        {
+            tracing::warn!(error = %e, "failed to write shadow log");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::test::TestClient;
+    use serde_json::json;
+
+    use super::*;
+
+    fn jwt(claims: Value) -> String {
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        format!("Bearer e30.{payload}.signature")
+    }
+
+    fn request() -> salvo::test::RequestBuilder {
+        TestClient::get("http://127.0.0.1/admin-api/system/user/page")
+    }
+
+    #[test]
+    fn matches_headers_cookies_tenants_and_users() {
+        let rules = Rules::new(
+            vec![
+                RuleConfig::Header {
+                    name: "x-canary".into(),
+                    values: vec!["rust".into()],
+                },
+                RuleConfig::Cookie {
+                    name: "canary".into(),
+                    values: vec!["1".into()],
+                },
+                RuleConfig::Tenant {
+                    values: vec!["t1".into()],
+                },
+                RuleConfig::User {
+                    values: vec!["u1".into()],
+                },
+            ],
+            "tenant-id",
+        );
+
+        assert!(!rules.matches(&request().build()));
+        assert!(rules.matches(&request().add_header("x-canary", "rust", true).build()));
+        assert!(!rules.matches(&request().add_header("x-canary", "java", true).build()));
+        assert!(rules.matches(&request().add_header("cookie", "canary=1", true).build()));
+        assert!(rules.matches(&request().add_header("tenant-id", "t1", true).build()));
+        let token = jwt(json!({ "uid": "u2", "tid": "t1" }));
+        assert!(rules.matches(&request().add_header("authorization", token, true).build()));
+        let token = jwt(json!({ "uid": "u1", "tid": "t2" }));
+        assert!(rules.matches(&request().add_header("authorization", token, true).build()));
+        let token = jwt(json!({ "uid": "u2", "tid": "t2" }));
+        assert!(!rules.matches(&request().add_header("authorization", token, true).build()));
+    }
+
+    #[test]
+    fn weight_is_stable_per_caller() {
+        let none = Rules::new(vec![RuleConfig::Weight { percent: 0 }], "tenant-id");
+        let all = Rules::new(vec![RuleConfig::Weight { percent: 100 }], "tenant-id");
+        let half = Rules::new(vec![RuleConfig::Weight { percent: 50 }], "tenant-id");
+
+        let mut canary = 0;
+        for user in 0..200 {
+            let token = jwt(json!({ "uid": format!("user-{user}") }));
+            let req = || request().add_header("authorization", &token, true).build();
+            assert!(!none.matches(&req()));
+            assert!(all.matches(&req()));
+            let first = half.matches(&req());
+            assert!((0..5).all(|_| half.matches(&req()) == first));
+            canary += first as usize;
+        }
+        assert!((60..140).contains(&canary), "{canary}");
+    }
+
+    #[test]
+    fn compares_json_fields_and_skips_ignored_ones() {
+        let shadow = Shadow::new(&ShadowConfig {
+            percent: 100,
+            methods: vec!["get".into()],
+            ignore_fields: vec!["traceId".into()],
+        })
+        .unwrap();
+        let answer = |status: StatusCode, body: Value| Answer {
+            status,
+            body: body.to_string().into(),
+        };
+
+        let primary = answer(
+            StatusCode::OK,
+            json!({ "code": 0, "traceId": "a", "data": { "list": [1, 2], "total": 2 } }),
+        );
+        let same = answer(
+            StatusCode::OK,
+            json!({ "code": 0, "traceId": "b", "data": { "list": [1, 2], "total": 2 } }),
+        );
+        assert!(shadow.compare(&primary, &same).is_empty());
+
+        let different = answer(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            json!({ "code": 0, "data": { "list": [1, 3], "extra": true } }),
+        );
+        assert_eq!(
+            shadow.compare(&primary, &different),
+            [
+                "status: 200 OK != 500 Internal Server Error",
+                "$.data.list[1]: 2 != 3",
+                "$.data.total: 2 != null",
+                "$.data.extra: null != true",
+            ]
+        );
+        assert!(shadow.samples(&Method::GET));
+        assert!(!shadow.samples(&Method::POST));
+    }
+
+    #[test]
+    fn only_safe_methods_are_mirrored() {
+        let config = |methods: &[&str]| ShadowConfig {
+            percent: 100,
+            methods: methods.iter().map(|method| method.to_string()).collect(),
+            ignore_fields: Vec::new(),
+        };
+        assert!(Shadow::new(&config(&["get", "HEAD", "options"])).is_ok());
+        for unsafe_method in ["POST", "put", "PATCH", "DELETE"] {
+            assert!(Shadow::new(&config(&["GET", unsafe_method])).is_err());
+        }
+    }
+}
@@ -0,0 +1,138 @@
+use figment::Figment;
+use figment::providers::{Env, Format, Toml};
+use serde::Deserialize;
+
+use crate::canary::RuleConfig;
+use crate::upstream::LoadBalance;
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct GatewayConfig {
+    #[serde(default = "default_listen_addr")]
+    pub listen_addr: String,
+    /// Largest request body forwarded upstream, in bytes.
+    #[serde(default = "default_max_body_size")]
+    pub max_body_size: usize,
+    /// Needed by routes that discover their upstreams by service name.
+    pub nacos: Option<NacosConfig>,
+    /// Header carrying the tenant id, read by `tenant` canary rules.
+    #[serde(default = "default_tenant_header")]
+    pub tenant_header: String,
+    /// File that shadow response differences are appended to as JSON lines. They are only
+    /// logged when unset.
+    pub shadow_log: Option<String>,
+    #[serde(default)]
+    pub routes: Vec<RouteConfig>,
+}
+
+impl GatewayConfig {
+    /// `gateway.toml`, or the file named by `GATEWAY_CONFIG`.
+    pub fn path() -> String {
+        Env::var_or("GATEWAY_CONFIG", "gateway.toml")
+    }
+
+    /// The config file overridden by `GATEWAY_` variables.
+    pub fn load() -> Result<Self, Box<figment::Error>> {
+        Figment::new()
+            .merge(Toml::file(Self::path()))
+            .merge(Env::prefixed("GATEWAY_").global())
+            .extract()
+            .map_err(Box::new)
+    }
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct NacosConfig {
+    /// Server, namespace, group and credentials, as the module servers read them.
+    #[serde(flatten)]
+    pub server: daoyi_framework::config::NacosConfig,
+    /// How long a service's instance list is reused, in seconds.
+    #[serde(default = "default_refresh_interval")]
+    pub refresh_interval: u64,
+}
+
+/// Upstream of `/admin-api/{module}/**` and `/app-api/{module}/**`.
+#[derive(Deserialize, Clone, Debug)]
+pub struct RouteConfig {
+    pub module: String,
+    /// Fixed upstream servers.
+    #[serde(default)]
+    pub servers: Vec<ServerConfig>,
+    /// Service name looked up in Nacos instead of `servers`.
+    pub service: Option<String>,
+    #[serde(default)]
+    pub load_balance: LoadBalance,
+    /// Time allowed for one upstream attempt, in seconds.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Further attempts on another upstream. Requests that may have reached an upstream are only
+    /// retried for idempotent methods.
+    #[serde(default = "default_retries")]
+    pub retries: u32,
+    /// New backend taking part of the module's traffic during a gray release.
+    pub canary: Option<CanaryConfig>,
+}
+
+/// Upstreams of a gray release, and which requests they receive.
+#[derive(Deserialize, Clone, Debug)]
+pub struct CanaryConfig {
+    #[serde(default)]
+    pub servers: Vec<ServerConfig>,
+    pub service: Option<String>,
+    #[serde(default)]
+    pub load_balance: LoadBalance,
+    /// A request matching any rule goes to the canary. Without rules it only receives shadow
+    /// traffic.
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    pub shadow: Option<ShadowConfig>,
+}
+
+/// Copies of primary requests sent to the canary, whose answers are compared but discarded.
+#[derive(Deserialize, Clone, Debug)]
+pub struct ShadowConfig {
+    /// Share of eligible requests mirrored.
+    #[serde(default = "default_shadow_percent")]
+    pub percent: u8,
+    /// Methods mirrored. Only safe ones are accepted, since the canary usually shares the database.
+    #[serde(default = "default_shadow_methods")]
+    pub methods: Vec<String>,
+    /// JSON fields left out of the comparison, such as timestamps or trace ids.
+    #[serde(default)]
+    pub ignore_fields: Vec<String>,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct ServerConfig {
+    /// Base URL, e.g. `http://127.0.0.1:8008`.
+    pub url: String,
+    #[serde(default = "default_weight")]
+    pub weight: u32,
+}
+
+fn default_listen_addr() -> String {
+    "0.0.0.0:48080".into()
+}
+fn default_max_body_size() -> usize {
+    10 * 1024 * 1024
+}
+fn default_tenant_header() -> String {
+    "tenant-id".into()
+}
+fn default_refresh_interval() -> u64 {
+    5
+}
+fn default_timeout() -> u64 {
+    30
+}
+fn default_retries() -> u32 {
+    1
+}
+fn default_weight() -> u32 {
+    1
+}
+fn default_shadow_percent() -> u8 {
+    100
+}
+fn default_shadow_methods() -> Vec<String> {
+    vec!["GET".into(), "HEAD".into()]
+}
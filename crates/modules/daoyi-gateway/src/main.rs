@@ -1,3 +1,108 @@
-fn main() {
-    println!("Hello, world!");
+//! Reverse proxy in front of the module servers, routing `/admin-api/{module}/**` and
+//! `/app-api/{module}/**` to the upstreams configured for each module.
+
+use std::time::Duration;
+
+use salvo::prelude::*;
+use salvo::server::ServerHandle;
+use tokio::signal;
+use tracing::info;
+use tracing_subscriber::EnvFilter;
+
+mod canary;
+mod config;
+mod proxy;
+mod upstream;
+
+use config::GatewayConfig;
+use proxy::Gateway;
+
+/// How often the config file is checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(5);
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+        )
+        .init();
+
+    let config = match GatewayConfig::load() {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("It looks like your config is invalid. The following error occurred: {e}");
+            std::process::exit(1);
+        }
+    };
+    let gateway = match Gateway::new(&config) {
+        Ok(gateway) => gateway,
+        Err(e) => {
+            eprintln!("Invalid gateway routes: {e}");
+            std::process::exit(1);
+        }
+    };
+
+    tokio::spawn(watch_config(gateway.clone()));
+
+    info!("gateway listening on {}", config.listen_addr);
+    let acceptor = TcpListener::new(config.listen_addr.clone()).bind().await;
+    let server = Server::new(acceptor);
+    tokio::spawn(shutdown_signal(server.handle()));
+    server.serve(Service::new(gateway.router())).await;
+}
+
+/// Reload routes and canary rules whenever the config file changes. A config that fails to load
+/// leaves the previous one in place.
+async fn watch_config(gateway: Gateway) {
+    let path = GatewayConfig::path();
+    let modified = || {
+        std::fs::metadata(&path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+    };
+    let mut last_modified = modified();
+    let mut ticker = tokio::time::interval(RELOAD_INTERVAL);
+    loop {
+        ticker.tick().await;
+        let current = modified();
+        if current == last_modified {
+            continue;
+        }
+        last_modified = current;
+        let reloaded = GatewayConfig::load()
+            .map_err(anyhow::Error::from)
+            .and_then(|config| gateway.reload(&config));
+        match reloaded {
+            Ok(()) => info!("gateway config reloaded from {path}"),
+            Err(e) => {
+                tracing::error!(error = %e, "gateway config reload failed, keeping the previous one")
+            }
+        }
+    }
+}
+
+async fn shutdown_signal(handle: ServerHandle) {
+    let ctrl_c = async {
+        signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        signal::unix::signal(signal::unix::SignalKind::terminate())
+            .expect("failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("ctrl_c signal received"),
+        _ = terminate => info!("terminate signal received"),
+    }
+    handle.stop_graceful(std::time::Duration::from_secs(60));
 }
@@ -0,0 +1,830 @@
+//! Forwards `/admin-api/{module}/**` and `/app-api/{module}/**` to the module's upstreams.
+//!
+//! Paths and queries are forwarded unchanged. Every end-to-end header passes through, including
+//! `Authorization`, the tenant header and cookies, so upstreams authenticate the caller
+//! themselves.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, ensure};
+use arc_swap::ArcSwap;
+use bytes::Bytes;
+use daoyi_framework::nacos::naming::Discovery;
+use salvo::http::header::{self, HeaderMap, HeaderName, HeaderValue};
+use salvo::http::{Method, StatusCode};
+use salvo::prelude::*;
+use serde_json::json;
+use thiserror::Error;
+
+use crate::canary::{Answer, Rules, Shadow, ShadowDiff, ShadowRecorder};
+use crate::config::{GatewayConfig, ServerConfig};
+use crate::upstream::{Balancer, LoadBalance, Upstream};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+/// Marks mirrored requests, so the canary can tell them apart. Only the gateway sets it.
+const X_GATEWAY_SHADOW: HeaderName = HeaderName::from_static("x-gateway-shadow");
+
+/// Upstream answers that say nothing was done, so another upstream may be tried.
+const RETRY_STATUSES: [StatusCode; 3] = [
+    StatusCode::BAD_GATEWAY,
+    StatusCode::SERVICE_UNAVAILABLE,
+    StatusCode::GATEWAY_TIMEOUT,
+];
+
+#[derive(Error, Debug)]
+enum GatewayError {
+    #[error("No route for module `{0}`.")]
+    NoRoute(String),
+    #[error("No upstream is available.")]
+    NoUpstream,
+    #[error("Request body is too large.")]
+    PayloadTooLarge,
+    #[error("Upstream did not answer in time.")]
+    Timeout,
+    #[error("Upstream request failed.")]
+    BadGateway,
+}
+
+impl GatewayError {
+    fn status(&self) -> StatusCode {
+        match self {
+            Self::NoRoute(_) => StatusCode::NOT_FOUND,
+            Self::NoUpstream => StatusCode::SERVICE_UNAVAILABLE,
+            Self::PayloadTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            Self::Timeout => StatusCode::GATEWAY_TIMEOUT,
+            Self::BadGateway => StatusCode::BAD_GATEWAY,
+        }
+    }
+}
+
+/// Same envelope as the module servers, with the HTTP status as the code.
+#[async_trait]
+impl Writer for GatewayError {
+    async fn write(self, _req: &mut Request, _depot: &mut Depot, res: &mut Response) {
+        let status = self.status();
+        res.status_code(status);
+        res.render(Json(json!({
+            "code": status.as_u16(),
+            "msg": self.to_string(),
+            "data": null,
+        })));
+    }
+}
+
+enum Source {
+    Static(Arc<Vec<Upstream>>),
+    Discovery(String),
+}
+
+/// Upstreams of one side of a route.
+struct Pool {
+    source: Source,
+    balancer: Balancer,
+}
+
+impl Pool {
+    fn new(
+        module: &str,
+        servers: &[ServerConfig],
+        service: Option<&String>,
+        load_balance: LoadBalance,
+        config: &GatewayConfig,
+    ) -> anyhow::Result<Self> {
+        let source = match (service, servers.is_empty()) {
+            (Some(service), true) => {
+                ensure!(
+                    config.nacos.is_some(),
+                    "route `{module}` discovers its upstreams but [nacos] is not configured"
+                );
+                Source::Discovery(service.clone())
+            }
+            (None, false) => Source::Static(Arc::new(
+                servers
+                    .iter()
+                    .map(|server| Upstream::new(&server.url, server.weight))
+                    .collect(),
+            )),
+            _ => bail!("route `{module}` needs either `servers` or `service`"),
+        };
+        Ok(Self {
+            source,
+            balancer: Balancer::new(load_balance),
+        })
+    }
+}
+
+struct Canary {
+    pool: Pool,
+    rules: Rules,
+    shadow: Option<Shadow>,
+}
+
+struct Route {
+    primary: Pool,
+    canary: Option<Canary>,
+    timeout: Duration,
+    retries: u32,
+}
+
+/// A request as sent upstream, kept so it can be sent again.
+#[derive(Clone)]
+struct Outgoing {
+    method: Method,
+    path: String,
+    headers: HeaderMap,
+    body: Bytes,
+}
+
+/// Everything built from the config, replaced as a whole on reload.
+struct Inner {
+    routes: HashMap<String, Route>,
+    discovery: Option<Discovery>,
+    client: reqwest::Client,
+    max_body_size: usize,
+    recorder: ShadowRecorder,
+}
+
+impl Inner {
+    fn new(config: &GatewayConfig) -> anyhow::Result<Self> {
+        let mut routes = HashMap::new();
+        for route in &config.routes {
+            let canary = match &route.canary {
+                Some(canary) => Some(Canary {
+                    pool: Pool::new(
+                        &route.module,
+                        &canary.servers,
+                        canary.service.as_ref(),
+                        canary.load_balance,
+                        config,
+                    )?,
+                    rules: Rules::new(canary.rules.clone(), &config.tenant_header),
+                    shadow: canary.shadow.as_ref().map(Shadow::new).transpose()?,
+                }),
+                None => None,
+            };
+            let route_state = Route {
+                primary: Pool::new(
+                    &route.module,
+                    &route.servers,
+                    route.service.as_ref(),
+                    route.load_balance,
+                    config,
+                )?,
+                canary,
+                timeout: Duration::from_secs(route.timeout),
+                retries: route.retries,
+            };
+            ensure!(
+                routes.insert(route.module.clone(), route_state).is_none(),
+                "module `{}` is routed twice",
+                route.module
+            );
+        }
+
+        let client = reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+        Ok(Self {
+            routes,
+            discovery: config.nacos.as_ref().map(|nacos| {
+                Discovery::new(&nacos.server).cache_for(Duration::from_secs(nacos.refresh_interval))
+            }),
+            client,
+            max_body_size: config.max_body_size,
+            recorder: ShadowRecorder::open(config.shadow_log.as_deref())?,
+        })
+    }
+
+    async fn upstreams(&self, pool: &Pool) -> Result<Arc<Vec<Upstream>>, GatewayError> {
+        match &pool.source {
+            Source::Static(upstreams) => Ok(upstreams.clone()),
+            Source::Discovery(service) => {
+                let discovery = self
+                    .discovery
+                    .as_ref()
+                    .expect("discovery routes require nacos");
+                match discovery.healthy_instances(service).await {
+                    Ok(instances) => Ok(Arc::new(
+                        instances.into_iter().map(Upstream::from).collect(),
+                    )),
+                    Err(e) => {
+                        tracing::warn!(service, error = %e, "service discovery failed");
+                        Err(GatewayError::NoUpstream)
+                    }
+                }
+            }
+        }
+    }
+
+    /// Send `outgoing` to an upstream of `pool`, trying others as `route` allows.
+    async fn send(
+        &self,
+        route: &Route,
+        pool: &Pool,
+        outgoing: &Outgoing,
+    ) -> Result<reqwest::Response, GatewayError> {
+        let upstreams = self.upstreams(pool).await?;
+        let idempotent = is_idempotent(&outgoing.method);
+        let mut tried = Vec::new();
+        loop {
+            let upstream = pool
+                .balancer
+                .pick(&upstreams, &tried)
+                .ok_or(GatewayError::NoUpstream)?;
+            tried.push(upstream.url.clone());
+            let can_retry = tried.len() <= route.retries as usize;
+            let result = self
+                .client
+                .request(
+                    outgoing.method.clone(),
+                    format!("{}{}", upstream.url, outgoing.path),
+                )
+                .headers(outgoing.headers.clone())
+                .body(outgoing.body.clone())
+                .timeout(route.timeout)
+                .send()
+                .await;
+            match result {
+                Ok(upstream_res)
+                    if can_retry
+                        && idempotent
+                        && RETRY_STATUSES.contains(&upstream_res.status()) =>
+                {
+                    tracing::warn!(upstream = upstream.url, status = %upstream_res.status(), "retrying on another upstream");
+                }
+                Ok(upstream_res) => return Ok(upstream_res),
+                // A failed connection never reached the upstream, so any method may be retried.
+                Err(e) if can_retry && (idempotent || e.is_connect()) => {
+                    tracing::warn!(upstream = upstream.url, error = %e, "retrying on another upstream");
+                }
+                Err(e) if e.is_timeout() => return Err(GatewayError::Timeout),
+                Err(e) => {
+                    tracing::warn!(upstream = upstream.url, error = %e, "upstream request failed");
+                    return Err(GatewayError::BadGateway);
+                }
+            }
+        }
+    }
+
+    /// Send a copy of a primary request to the canary and record how its answer differs.
+    async fn mirror(&self, module: String, mut outgoing: Outgoing, primary: Answer) {
+        let Some(route) = self.routes.get(&module) else {
+            return;
+        };
+        let Some(canary) = &route.canary else {
+            return;
+        };
+        let Some(shadow) = &canary.shadow else {
+            return;
+        };
+        outgoing
+            .headers
+            .insert(X_GATEWAY_SHADOW, HeaderValue::from_static("true"));
+        let answer = match self.send(route, &canary.pool, &outgoing).await {
+            Ok(res) => {
+                let status = res.status();
+                match res.bytes().await {
+                    Ok(body) => Answer { status, body },
+                    Err(_) => Answer {
+                        status: StatusCode::BAD_GATEWAY,
+                        body: Bytes::new(),
+                    },
+                }
+            }
+            Err(e) => Answer {
+                status: e.status(),
+                body: Bytes::new(),
+            },
+        };
+        let differences = shadow.compare(&primary, &answer);
+        if !differences.is_empty() {
+            self.recorder.record(&ShadowDiff {
+                module,
+                method: outgoing.method.to_string(),
+                path: outgoing.path,
+                differences,
+            });
+        }
+    }
+}
+
+/// Routes requests with the current config. Cloning shares it, so a reload reaches every clone.
+#[derive(Clone)]
+pub struct Gateway {
+    inner: Arc<ArcSwap<Inner>>,
+}
+
+impl Gateway {
+    pub fn new(config: &GatewayConfig) -> anyhow::Result<Self> {
+        Ok(Self {
+            inner: Arc::new(ArcSwap::from_pointee(Inner::new(config)?)),
+        })
+    }
+
+    /// Apply new routes and rules. Requests in flight finish with the old ones. `listen_addr`
+    /// only takes effect on restart.
+    pub fn reload(&self, config: &GatewayConfig) -> anyhow::Result<()> {
+        self.inner.store(Arc::new(Inner::new(config)?));
+        Ok(())
+    }
+
+    pub fn router(&self) -> Router {
+        Router::new()
+            .push(Router::with_path("admin-api/{module}/{**rest}").goal(self.clone()))
+            .push(Router::with_path("app-api/{module}/{**rest}").goal(self.clone()))
+    }
+
+    async fn forward(&self, req: &mut Request, res: &mut Response) -> Result<(), GatewayError> {
+        let inner = self.inner.load_full();
+        let module = req.param::<String>("module").unwrap_or_default();
+        let route = inner
+            .routes
+            .get(&module)
+            .ok_or_else(|| GatewayError::NoRoute(module.clone()))?;
+        let body = req
+            .payload_with_max_size(inner.max_body_size)
+            .await
+            .map_err(|_| GatewayError::PayloadTooLarge)?
+            .clone();
+        let outgoing = Outgoing {
+            method: req.method().clone(),
+            path: req
+                .uri()
+                .path_and_query()
+                .map_or("/", |path| path.as_str())
+                .to_owned(),
+            headers: forward_headers(req),
+            body,
+        };
+
+        if let Some(canary) = &route.canary
+            && canary.rules.matches(req)
+        {
+            match inner.send(route, &canary.pool, &outgoing).await {
+                // Better the old backend than no answer.
+                Err(GatewayError::NoUpstream) => {
+                    tracing::warn!(module, "no canary upstream, using the primary");
+                }
+                result => {
+                    copy_response(res, result?);
+                    return Ok(());
+                }
+            }
+        }
+
+        let upstream_res = inner.send(route, &route.primary, &outgoing).await?;
+        let shadowed = route
+            .canary
+            .as_ref()
+            .and_then(|canary| canary.shadow.as_ref())
+            .is_some_and(|shadow| shadow.samples(&outgoing.method));
+        if !shadowed {
+            copy_response(res, upstream_res);
+            return Ok(());
+        }
+
+        // The primary answer is buffered to compare it, and still returned as is.
+        copy_head(res, &upstream_res);
+        let status = upstream_res.status();
+        let body = upstream_res.bytes().await.map_err(|e| {
+            tracing::warn!(error = %e, "reading upstream response failed");
+            GatewayError::BadGateway
+        })?;
+        res.body(body.clone());
+        let inner = inner.clone();
+        tokio::spawn(async move {
+            inner
+                .mirror(module, outgoing, Answer { status, body })
+                .await;
+        });
+        Ok(())
+    }
+}
+
+fn copy_head(res: &mut Response, upstream_res: &reqwest::Response) {
+    res.status_code(upstream_res.status());
+    for (name, value) in upstream_res.headers() {
+        if !is_hop_by_hop(name) && name != header::CONTENT_LENGTH {
+            res.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+}
+
+fn copy_response(res: &mut Response, upstream_res: reqwest::Response) {
+    copy_head(res, &upstream_res);
+    res.stream(upstream_res.bytes_stream());
+}
+
+#[async_trait]
+impl Handler for Gateway {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        _ctrl: &mut FlowCtrl,
+    ) {
+        if let Err(e) = self.forward(req, res).await {
+            e.write(req, depot, res).await;
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE | Method::TRACE
+    )
+}
+
+/// Headers that only apply to a single connection and must not be forwarded.
+fn is_hop_by_hop(name: &HeaderName) -> bool {
+    matches!(
+        name.as_str(),
+        "connection"
+            | "keep-alive"
+            | "proxy-authenticate"
+            | "proxy-authorization"
+            | "te"
+            | "trailer"
+            | "transfer-encoding"
+            | "upgrade"
+    )
+}
+
+fn forward_headers(req: &Request) -> HeaderMap {
+    let mut headers = HeaderMap::with_capacity(req.headers().len() + 3);
+    for (name, value) in req.headers() {
+        if !is_hop_by_hop(name)
+            && name != header::HOST
+            && name != header::CONTENT_LENGTH
+            && name != X_GATEWAY_SHADOW
+        {
+            headers.append(name.clone(), value.clone());
+        }
+    }
+
+    if let Some(addr) = req.remote_addr().clone().into_std() {
+        let forwarded_for = match req
+            .headers()
+            .get(X_FORWARDED_FOR)
+            .and_then(|value| value.to_str().ok())
+        {
+            Some(forwarded_for) => format!("{forwarded_for}, {}", addr.ip()),
+            None => addr.ip().to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+            headers.insert(X_FORWARDED_FOR, value);
+        }
+    }
+    if let Some(host) = req.headers().get(header::HOST) {
+        headers.insert(X_FORWARDED_HOST, host.clone());
+    }
+    if let Ok(proto) = HeaderValue::from_str(req.scheme().as_str()) {
+        headers.insert(X_FORWARDED_PROTO, proto);
+    }
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::test::{ResponseExt, TestClient};
+    use serde_json::Value;
+
+    use super::*;
+    use crate::canary::RuleConfig;
+    use crate::config::{CanaryConfig, NacosConfig, RouteConfig, ShadowConfig};
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    async fn serve(handler: impl Handler) -> u16 {
+        let port = free_port();
+        let acceptor = TcpListener::new(("127.0.0.1", port)).bind().await;
+        let router = Router::with_path("{**rest}").goal(handler);
+        tokio::spawn(Server::new(acceptor).serve(router));
+        port
+    }
+
+    /// Upstream echoing what it received. Upstream `a` fails `/flaky` with 503.
+    struct Echo(&'static str);
+
+    #[async_trait]
+    impl Handler for Echo {
+        async fn handle(
+            &self,
+            req: &mut Request,
+            _depot: &mut Depot,
+            res: &mut Response,
+            _ctrl: &mut FlowCtrl,
+        ) {
+            let path = req.uri().path().to_owned();
+            if path.ends_with("/slow") {
+                tokio::time::sleep(Duration::from_secs(3)).await;
+            }
+            if path.ends_with("/flaky") && self.0 == "a" {
+                res.status_code(StatusCode::SERVICE_UNAVAILABLE);
+                return;
+            }
+            let body = req
+                .payload()
+                .await
+                .map(|body| String::from_utf8_lossy(body).into_owned())
+                .unwrap_or_default();
+            res.render(Json(json!({
+                "upstream": self.0,
+                "method": req.method().as_str(),
+                "path": req.uri().path_and_query().map(|path| path.as_str()),
+                "authorization": req.header::<String>("authorization"),
+                "tenant": req.header::<String>("tenant-id"),
+                "shadow": req.header::<String>("x-gateway-shadow"),
+                "body": body,
+            })));
+        }
+    }
+
+    async fn upstream(name: &'static str) -> String {
+        format!("http://127.0.0.1:{}", serve(Echo(name)).await)
+    }
+
+    fn route(module: &str, urls: &[String]) -> RouteConfig {
+        RouteConfig {
+            module: module.to_owned(),
+            servers: urls
+                .iter()
+                .map(|url| ServerConfig {
+                    url: url.clone(),
+                    weight: 1,
+                })
+                .collect(),
+            service: None,
+            load_balance: LoadBalance::RoundRobin,
+            timeout: 5,
+            retries: 1,
+            canary: None,
+        }
+    }
+
+    fn canary(url: &str, rules: Vec<RuleConfig>) -> CanaryConfig {
+        CanaryConfig {
+            servers: vec![ServerConfig {
+                url: url.to_owned(),
+                weight: 1,
+            }],
+            service: None,
+            load_balance: LoadBalance::RoundRobin,
+            rules,
+            shadow: None,
+        }
+    }
+
+    fn config(routes: Vec<RouteConfig>, nacos: Option<NacosConfig>) -> GatewayConfig {
+        GatewayConfig {
+            listen_addr: String::new(),
+            max_body_size: 1024,
+            nacos,
+            tenant_header: "tenant-id".into(),
+            shadow_log: None,
+            routes,
+        }
+    }
+
+    fn gateway(routes: Vec<RouteConfig>, nacos: Option<NacosConfig>) -> Service {
+        Service::new(Gateway::new(&config(routes, nacos)).unwrap().router())
+    }
+
+    async fn call(service: &Service, method: Method, path: &str) -> (StatusCode, Value) {
+        let url = format!("http://127.0.0.1{path}");
+        let client = match method {
+            Method::POST => TestClient::post(url),
+            _ => TestClient::get(url),
+        };
+        let mut res = client.send(service).await;
+        let status = res.status_code.unwrap_or(StatusCode::OK);
+        (status, res.take_json::<Value>().await.unwrap_or_default())
+    }
+
+    #[tokio::test]
+    async fn forwards_path_body_and_auth_headers() {
+        let service = gateway(vec![route("system", &[upstream("a").await])], None);
+
+        let mut res = TestClient::post("http://127.0.0.1/app-api/system/user/update?id=1")
+            .add_header("authorization", "Bearer token", true)
+            .add_header("tenant-id", "1", true)
+            .add_header("x-gateway-shadow", "true", true)
+            .text("payload")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        let body = res.take_json::<Value>().await.unwrap();
+        assert_eq!(body["method"], "POST");
+        assert_eq!(body["path"], "/app-api/system/user/update?id=1");
+        assert_eq!(body["authorization"], "Bearer token");
+        assert_eq!(body["tenant"], "1");
+        assert_eq!(body["body"], "payload");
+        // Clients cannot pass their requests off as mirrored ones.
+        assert_eq!(body["shadow"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn unknown_module_is_not_found() {
+        let service = gateway(vec![route("system", &[upstream("a").await])], None);
+
+        let (status, body) = call(&service, Method::GET, "/admin-api/mall/spu/page").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], 404);
+    }
+
+    #[tokio::test]
+    async fn round_robin_alternates_upstreams() {
+        let urls = [upstream("a").await, upstream("b").await];
+        let service = gateway(vec![route("system", &urls)], None);
+
+        let mut seen = Vec::new();
+        for _ in 0..4 {
+            let (_, body) = call(&service, Method::GET, "/admin-api/system/ping").await;
+            seen.push(body["upstream"].as_str().unwrap().to_owned());
+        }
+        assert_eq!(seen, ["a", "b", "a", "b"]);
+    }
+
+    #[tokio::test]
+    async fn retries_on_another_upstream_when_one_is_down() {
+        let down = format!("http://127.0.0.1:{}", free_port());
+        let service = gateway(vec![route("system", &[down, upstream("b").await])], None);
+
+        for method in [Method::GET, Method::POST] {
+            let (status, body) = call(&service, method, "/admin-api/system/ping").await;
+            assert_eq!(status, StatusCode::OK);
+            assert_eq!(body["upstream"], "b");
+        }
+    }
+
+    #[tokio::test]
+    async fn only_idempotent_methods_are_retried_after_an_upstream_error() {
+        let urls = [upstream("a").await, upstream("b").await];
+
+        let service = gateway(vec![route("system", &urls)], None);
+        let (status, body) = call(&service, Method::GET, "/admin-api/system/flaky").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["upstream"], "b");
+
+        let service = gateway(vec![route("system", &urls)], None);
+        let (status, _) = call(&service, Method::POST, "/admin-api/system/flaky").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+    }
+
+    #[tokio::test]
+    async fn slow_upstream_times_out() {
+        let mut route = route("system", &[upstream("a").await]);
+        route.timeout = 1;
+        route.retries = 0;
+        let service = gateway(vec![route], None);
+
+        let (status, body) = call(&service, Method::GET, "/admin-api/system/slow").await;
+        assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(body["code"], 504);
+    }
+
+    /// Nacos answering every instance list with one local instance on the given port.
+    struct InstanceList(u16);
+
+    #[async_trait]
+    impl Handler for InstanceList {
+        async fn handle(
+            &self,
+            _req: &mut Request,
+            _depot: &mut Depot,
+            res: &mut Response,
+            _ctrl: &mut FlowCtrl,
+        ) {
+            res.render(Json(json!({
+                "hosts": [{
+                    "ip": "127.0.0.1",
+                    "port": self.0,
+                    "weight": 1.0,
+                    "healthy": true,
+                    "enabled": true,
+                    "metadata": {},
+                }],
+            })));
+        }
+    }
+
+    #[tokio::test]
+    async fn routes_to_discovered_instances() {
+        let upstream_port = serve(Echo("a")).await;
+        let nacos_port = serve(InstanceList(upstream_port)).await;
+        let mut route = route("infra", &[]);
+        route.service = Some("infra-server".into());
+        let nacos = NacosConfig {
+            server: daoyi_framework::config::NacosConfig {
+                server_url: format!("http://127.0.0.1:{nacos_port}/nacos"),
+                ..Default::default()
+            },
+            refresh_interval: 5,
+        };
+        let service = gateway(vec![route], Some(nacos));
+
+        let (status, body) = call(&service, Method::GET, "/admin-api/infra/file/list").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["upstream"], "a");
+    }
+
+    #[tokio::test]
+    async fn routes_matching_tenants_to_the_canary() {
+        let mut route = route("system", &[upstream("java").await]);
+        let rules = vec![RuleConfig::Tenant {
+            values: vec!["1".into()],
+        }];
+        route.canary = Some(canary(&upstream("rust").await, rules));
+        let service = gateway(vec![route], None);
+
+        for (tenant, expected) in [("1", "rust"), ("2", "java")] {
+            let mut res = TestClient::get("http://127.0.0.1/admin-api/system/user/page")
+                .add_header("tenant-id", tenant, true)
+                .send(&service)
+                .await;
+            let body = res.take_json::<Value>().await.unwrap();
+            assert_eq!(body["upstream"], expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn shadow_requests_record_differences() {
+        let shadow_log = std::env::temp_dir().join(format!("shadow-{}.log", free_port()));
+        let mut route = route("system", &[upstream("java").await]);
+        let mut canary = canary(&upstream("rust").await, Vec::new());
+        canary.shadow = Some(ShadowConfig {
+            percent: 100,
+            methods: vec!["GET".into()],
+            // Only the mirrored request carries the shadow marker.
+            ignore_fields: vec!["shadow".into()],
+        });
+        route.canary = Some(canary);
+        let mut config = config(vec![route], None);
+        config.shadow_log = Some(shadow_log.to_string_lossy().into_owned());
+        let service = Service::new(Gateway::new(&config).unwrap().router());
+
+        let (status, body) = call(&service, Method::GET, "/admin-api/system/user/page").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["upstream"], "java");
+        let (_, body) = call(&service, Method::POST, "/admin-api/system/user/create").await;
+        assert_eq!(body["upstream"], "java");
+
+        let mut log = String::new();
+        for _ in 0..50 {
+            log = std::fs::read_to_string(&shadow_log).unwrap_or_default();
+            if !log.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        let _ = std::fs::remove_file(&shadow_log);
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 1, "{log}");
+        let diff = serde_json::from_str::<Value>(lines[0]).unwrap();
+        assert_eq!(diff["module"], "system");
+        assert_eq!(diff["method"], "GET");
+        assert_eq!(diff["path"], "/admin-api/system/user/page");
+        assert_eq!(
+            diff["differences"],
+            json!([r#"$.upstream: "java" != "rust""#])
+        );
+    }
+
+    #[tokio::test]
+    async fn reload_applies_new_rules() {
+        let java = upstream("java").await;
+        let rust = upstream("rust").await;
+        let gateway = Gateway::new(&config(
+            vec![route("system", std::slice::from_ref(&java))],
+            None,
+        ))
+        .unwrap();
+        let service = Service::new(gateway.router());
+        let (_, body) = call(&service, Method::GET, "/admin-api/system/ping").await;
+        assert_eq!(body["upstream"], "java");
+
+        let mut canary_route = route("system", &[java]);
+        canary_route.canary = Some(canary(&rust, vec![RuleConfig::Weight { percent: 100 }]));
+        gateway.reload(&config(vec![canary_route], None)).unwrap();
+        let (_, body) = call(&service, Method::GET, "/admin-api/system/ping").await;
+        assert_eq!(body["upstream"], "rust");
+
+        let mut invalid = route("system", &[]);
+        invalid.service = Some("system-server".into());
+        assert!(gateway.reload(&config(vec![invalid], None)).is_err());
+        let (_, body) = call(&service, Method::GET, "/admin-api/system/ping").await;
+        assert_eq!(body["upstream"], "rust");
+    }
+}
@@ -0,0 +1,140 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use daoyi_framework::nacos::naming::Instance;
+use serde::Deserialize;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Upstream {
+    /// Base URL without a trailing slash.
+    pub url: String,
+    pub weight: u32,
+}
+
+impl Upstream {
+    pub fn new(url: &str, weight: u32) -> Self {
+        Self {
+            url: url.trim_end_matches('/').to_owned(),
+            weight,
+        }
+    }
+}
+
+impl From<Instance> for Upstream {
+    fn from(instance: Instance) -> Self {
+        let scheme = if instance.secure() { "https" } else { "http" };
+        // Nacos weights are fractional; keep two decimals.
+        let weight = (instance.weight * 100.0).round() as u32;
+        Self::new(
+            &format!("{scheme}://{}:{}", instance.ip, instance.port),
+            weight,
+        )
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LoadBalance {
+    #[default]
+    RoundRobin,
+    /// Random choice in proportion to the upstream weights.
+    Weighted,
+}
+
+pub struct Balancer {
+    strategy: LoadBalance,
+    next: AtomicUsize,
+}
+
+impl Balancer {
+    pub fn new(strategy: LoadBalance) -> Self {
+        Self {
+            strategy,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Pick an upstream, avoiding those in `tried` unless nothing else is left.
+    pub fn pick<'a>(&self, upstreams: &'a [Upstream], tried: &[String]) -> Option<&'a Upstream> {
+        let mut candidates = upstreams
+            .iter()
+            .filter(|upstream| !tried.contains(&upstream.url))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            candidates = upstreams.iter().collect();
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+
+        let total = candidates
+            .iter()
+            .map(|upstream| upstream.weight as u64)
+            .sum::<u64>();
+        if self.strategy == LoadBalance::Weighted && total > 0 {
+            let mut point = rand::random_range(0..total);
+            for upstream in &candidates {
+                if point < upstream.weight as u64 {
+                    return Some(upstream);
+                }
+                point -= upstream.weight as u64;
+            }
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+        Some(candidates[index])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn upstreams(weights: &[u32]) -> Vec<Upstream> {
+        weights
+            .iter()
+            .enumerate()
+            .map(|(i, weight)| Upstream::new(&format!("http://10.0.0.{i}:8008"), *weight))
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_cycles_and_skips_tried() {
+        let upstreams = upstreams(&[1, 1, 1]);
+        let balancer = Balancer::new(LoadBalance::RoundRobin);
+        let picked = (0..6)
+            .map(|_| balancer.pick(&upstreams, &[]).unwrap().url.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(picked[..3], picked[3..]);
+        assert_ne!(picked[0], picked[1]);
+
+        let tried = vec![upstreams[0].url.clone(), upstreams[1].url.clone()];
+        for _ in 0..3 {
+            assert_eq!(balancer.pick(&upstreams, &tried), Some(&upstreams[2]));
+        }
+        assert!(balancer.pick(&[], &[]).is_none());
+    }
+
+    #[test]
+    fn instances_keep_their_scheme_and_weight() {
+        let mut instance = Instance::new("10.0.0.1", 8008);
+        instance.weight = 0.25;
+        assert_eq!(
+            Upstream::from(instance.clone()),
+            Upstream::new("http://10.0.0.1:8008", 25)
+        );
+        instance.metadata.insert("secure".into(), "true".into());
+        assert_eq!(Upstream::from(instance).url, "https://10.0.0.1:8008");
+    }
+
+    #[test]
+    fn weighted_never_picks_zero_weight() {
+        let upstreams = upstreams(&[0, 3, 1]);
+        let balancer = Balancer::new(LoadBalance::Weighted);
+        let mut counts = [0; 3];
+        for _ in 0..4000 {
+            let upstream = balancer.pick(&upstreams, &[]).unwrap();
+            counts[upstreams.iter().position(|u| u == upstream).unwrap()] += 1;
+        }
+        assert_eq!(counts[0], 0);
+        assert!(counts[1] > counts[2] * 2, "{counts:?}");
+    }
+}
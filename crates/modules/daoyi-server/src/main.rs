@@ -0,0 +1,16 @@
+//! Every module in one process, for deployments that do not split them into separate servers.
+//!
+//! Modules mount under their own prefixes, so adding one is a `.module(...)` line here plus the
+//! dependency.
+
+use daoyi_framework::App;
+
+#[tokio::main]
+async fn main() {
+    App::builder()
+        .module(daoyi_cloud_rs::module().prefix("admin-api/system"))
+        .catcher(daoyi_cloud_rs::catcher())
+        .hoop(daoyi_cloud_rs::cors_hoop())
+        .run()
+        .await;
+}
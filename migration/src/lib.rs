@@ -1,6 +1,10 @@
 pub use sea_orm_migration::prelude::*;
 
 mod m20220101_000001_create_table;
+mod m20261018_000001_create_rbac_tables;
+mod m20261018_000002_create_tenants;
+mod m20261018_000003_create_departments;
+mod m20261018_000004_create_audit_logs;
 
 pub struct Migrator;
 
@@ -13,7 +17,13 @@ enum InfraMigrationTable {
 #[async_trait::async_trait]
 impl MigratorTrait for Migrator {
     fn migrations() -> Vec<Box<dyn MigrationTrait>> {
-        vec![Box::new(m20220101_000001_create_table::Migration)]
+        vec![
+            Box::new(m20220101_000001_create_table::Migration),
+            Box::new(m20261018_000001_create_rbac_tables::Migration),
+            Box::new(m20261018_000002_create_tenants::Migration),
+            Box::new(m20261018_000003_create_departments::Migration),
+            Box::new(m20261018_000004_create_audit_logs::Migration),
+        ]
     }
 
     fn migration_table_name() -> sea_query::DynIden {
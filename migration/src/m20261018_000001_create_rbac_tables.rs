@@ -0,0 +1,275 @@
+use sea_orm_migration::prelude::*;
+use sea_query::{Alias, SeaRc, TableRef};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const ADMIN_ROLE_ID: &str = "01JAD00000000000000000R000";
+const SEED_USER_ID: &str = "cdd0e080-5bb1-4442-b6f7-2ba60dbd0555";
+
+/// Permissions seeded for the built-in endpoints: (id, code, name).
+const PERMISSIONS: &[(&str, &str, &str)] = &[
+    ("01JAD00000000000000000P000", "*:*:*", "全部权限"),
+    (
+        "01JAD00000000000000000P001",
+        "system:user:query",
+        "用户查询",
+    ),
+    (
+        "01JAD00000000000000000P002",
+        "system:user:create",
+        "用户新增",
+    ),
+    (
+        "01JAD00000000000000000P003",
+        "system:user:update",
+        "用户修改",
+    ),
+    (
+        "01JAD00000000000000000P004",
+        "system:user:delete",
+        "用户删除",
+    ),
+    (
+        "01JAD00000000000000000P005",
+        "system:user:assign-role",
+        "分配用户角色",
+    ),
+    (
+        "01JAD00000000000000000P006",
+        "system:role:query",
+        "角色查询",
+    ),
+    (
+        "01JAD00000000000000000P007",
+        "system:role:create",
+        "角色新增",
+    ),
+    (
+        "01JAD00000000000000000P008",
+        "system:role:update",
+        "角色修改",
+    ),
+    (
+        "01JAD00000000000000000P009",
+        "system:role:delete",
+        "角色删除",
+    ),
+    (
+        "01JAD00000000000000000P00A",
+        "system:role:assign-permission",
+        "分配角色权限",
+    ),
+    (
+        "01JAD00000000000000000P00B",
+        "system:permission:query",
+        "权限查询",
+    ),
+    (
+        "01JAD00000000000000000P00C",
+        "system:permission:create",
+        "权限新增",
+    ),
+    (
+        "01JAD00000000000000000P00D",
+        "system:permission:update",
+        "权限修改",
+    ),
+    (
+        "01JAD00000000000000000P00E",
+        "system:permission:delete",
+        "权限删除",
+    ),
+];
+
+fn table(iden: impl IntoIden) -> TableRef {
+    TableRef::SchemaTable(SeaRc::new(Alias::new("infra")), iden.into_iden())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(Roles::Table))
+                    .if_not_exists()
+                    .col(ColumnDef::new(Roles::Id).string().not_null().primary_key())
+                    .col(ColumnDef::new(Roles::Code).string().not_null().unique_key())
+                    .col(ColumnDef::new(Roles::Name).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(Permissions::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Permissions::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(Permissions::Code)
+                            .string()
+                            .not_null()
+                            .unique_key(),
+                    )
+                    .col(ColumnDef::new(Permissions::Name).string().not_null())
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(RolePermissions::Table))
+                    .if_not_exists()
+                    .col(ColumnDef::new(RolePermissions::RoleId).string().not_null())
+                    .col(
+                        ColumnDef::new(RolePermissions::PermissionId)
+                            .string()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(RolePermissions::RoleId)
+                            .col(RolePermissions::PermissionId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_role_permissions_role_id")
+                            .from(table(RolePermissions::Table), RolePermissions::RoleId)
+                            .to(table(Roles::Table), Roles::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_role_permissions_permission_id")
+                            .from(table(RolePermissions::Table), RolePermissions::PermissionId)
+                            .to(table(Permissions::Table), Permissions::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(UserRoles::Table))
+                    .if_not_exists()
+                    .col(ColumnDef::new(UserRoles::UserId).string().not_null())
+                    .col(ColumnDef::new(UserRoles::RoleId).string().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(UserRoles::UserId)
+                            .col(UserRoles::RoleId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_roles_user_id")
+                            .from(table(UserRoles::Table), UserRoles::UserId)
+                            .to(table(Users::Table), Users::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_user_roles_role_id")
+                            .from(table(UserRoles::Table), UserRoles::RoleId)
+                            .to(table(Roles::Table), Roles::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Seed an administrator role holding every permission and grant it to the seed user.
+        let insert = Query::insert()
+            .into_table(table(Roles::Table))
+            .columns([Roles::Id, Roles::Code, Roles::Name])
+            .values_panic([
+                ADMIN_ROLE_ID.into(),
+                "super_admin".into(),
+                "超级管理员".into(),
+            ])
+            .to_owned();
+        manager.exec_stmt(insert).await?;
+
+        let mut insert = Query::insert()
+            .into_table(table(Permissions::Table))
+            .columns([Permissions::Id, Permissions::Code, Permissions::Name])
+            .to_owned();
+        for (id, code, name) in PERMISSIONS {
+            insert.values_panic([(*id).into(), (*code).into(), (*name).into()]);
+        }
+        manager.exec_stmt(insert).await?;
+
+        let insert = Query::insert()
+            .into_table(table(RolePermissions::Table))
+            .columns([RolePermissions::RoleId, RolePermissions::PermissionId])
+            .values_panic([ADMIN_ROLE_ID.into(), PERMISSIONS[0].0.into()])
+            .to_owned();
+        manager.exec_stmt(insert).await?;
+
+        let insert = Query::insert()
+            .into_table(table(UserRoles::Table))
+            .columns([UserRoles::UserId, UserRoles::RoleId])
+            .values_panic([SEED_USER_ID.into(), ADMIN_ROLE_ID.into()])
+            .to_owned();
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for iden in [
+            UserRoles::Table.into_iden(),
+            RolePermissions::Table.into_iden(),
+            Permissions::Table.into_iden(),
+            Roles::Table.into_iden(),
+        ] {
+            manager
+                .drop_table(Table::drop().table(table(iden)).to_owned())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Roles {
+    Table,
+    Id,
+    Code,
+    Name,
+}
+
+#[derive(Iden)]
+enum Permissions {
+    Table,
+    Id,
+    Code,
+    Name,
+}
+
+#[derive(Iden)]
+enum RolePermissions {
+    Table,
+    RoleId,
+    PermissionId,
+}
+
+#[derive(Iden)]
+enum UserRoles {
+    Table,
+    UserId,
+    RoleId,
+}
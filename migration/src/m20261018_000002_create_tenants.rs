@@ -0,0 +1,156 @@
+use sea_orm_migration::prelude::*;
+use sea_query::{Alias, SeaRc, TableRef};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Tenant owning every row that existed before multi-tenancy was introduced.
+const DEFAULT_TENANT_ID: &str = "01JAD00000000000000000T000";
+
+fn table(iden: impl IntoIden) -> TableRef {
+    TableRef::SchemaTable(SeaRc::new(Alias::new("infra")), iden.into_iden())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(Tenants::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Tenants::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Tenants::Name).string().not_null())
+                    .col(ColumnDef::new(Tenants::Domain).string().null().unique_key())
+                    .to_owned(),
+            )
+            .await?;
+
+        let insert = Query::insert()
+            .into_table(table(Tenants::Table))
+            .columns([Tenants::Id, Tenants::Name])
+            .values_panic([DEFAULT_TENANT_ID.into(), "默认租户".into()])
+            .to_owned();
+        manager.exec_stmt(insert).await?;
+
+        let db = manager.get_connection();
+        for (iden, unique_col, old_constraint, new_index) in [
+            (
+                Users::Table.into_iden(),
+                Users::Username.into_iden(),
+                "users_username_key",
+                "idx_users_tenant_id_username",
+            ),
+            (
+                Roles::Table.into_iden(),
+                Roles::Code.into_iden(),
+                "roles_code_key",
+                "idx_roles_tenant_id_code",
+            ),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table(iden.clone()))
+                        .add_column(
+                            ColumnDef::new(TenantId)
+                                .string()
+                                .not_null()
+                                .default(DEFAULT_TENANT_ID),
+                        )
+                        .add_foreign_key(
+                            TableForeignKey::new()
+                                .name(format!("fk_{}_tenant_id", iden.to_string()))
+                                .from_tbl(table(iden.clone()))
+                                .from_col(TenantId)
+                                .to_tbl(table(Tenants::Table))
+                                .to_col(Tenants::Id),
+                        )
+                        .to_owned(),
+                )
+                .await?;
+
+            // Uniqueness now only holds within a tenant.
+            db.execute_unprepared(&format!(
+                r#"ALTER TABLE "infra"."{}" DROP CONSTRAINT IF EXISTS "{old_constraint}""#,
+                iden.to_string()
+            ))
+            .await?;
+            manager
+                .create_index(
+                    Index::create()
+                        .name(new_index)
+                        .table(table(iden.clone()))
+                        .col(TenantId)
+                        .col(unique_col)
+                        .unique()
+                        .to_owned(),
+                )
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        for (iden, unique_col, old_constraint) in [
+            (
+                Users::Table.into_iden(),
+                Users::Username.into_iden(),
+                "users_username_key",
+            ),
+            (
+                Roles::Table.into_iden(),
+                Roles::Code.into_iden(),
+                "roles_code_key",
+            ),
+        ] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(table(iden.clone()))
+                        .drop_column(TenantId)
+                        .to_owned(),
+                )
+                .await?;
+            db.execute_unprepared(&format!(
+                r#"ALTER TABLE "infra"."{}" ADD CONSTRAINT "{old_constraint}" UNIQUE ("{}")"#,
+                iden.to_string(),
+                unique_col.to_string()
+            ))
+            .await?;
+        }
+
+        manager
+            .drop_table(Table::drop().table(table(Tenants::Table)).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+struct TenantId;
+
+#[derive(Iden)]
+enum Tenants {
+    Table,
+    Id,
+    Name,
+    Domain,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    Username,
+}
+
+#[derive(Iden)]
+enum Roles {
+    Table,
+    Code,
+}
@@ -0,0 +1,194 @@
+use sea_orm_migration::prelude::*;
+use sea_query::{Alias, SeaRc, TableRef};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+const ASSIGN_DEPT_PERMISSION_ID: &str = "01JAD00000000000000000P00F";
+
+fn table(iden: impl IntoIden) -> TableRef {
+    TableRef::SchemaTable(SeaRc::new(Alias::new("infra")), iden.into_iden())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(Departments::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(Departments::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(Departments::TenantId).string().not_null())
+                    .col(ColumnDef::new(Departments::ParentId).string().null())
+                    .col(ColumnDef::new(Departments::Name).string().not_null())
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_departments_tenant_id")
+                            .from(table(Departments::Table), Departments::TenantId)
+                            .to(table(Tenants::Table), Tenants::Id),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_departments_parent_id")
+                            .from(table(Departments::Table), Departments::ParentId)
+                            .to(table(Departments::Table), Departments::Id)
+                            .on_delete(ForeignKeyAction::Restrict),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table(Users::Table))
+                    .add_column(ColumnDef::new(Users::DeptId).string().null())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_users_dept_id")
+                            .from_tbl(table(Users::Table))
+                            .from_col(Users::DeptId)
+                            .to_tbl(table(Departments::Table))
+                            .to_col(Departments::Id)
+                            .on_delete(ForeignKeyAction::SetNull),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // 1 = all data, which keeps existing roles unrestricted.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table(Roles::Table))
+                    .add_column(
+                        ColumnDef::new(Roles::DataScope)
+                            .small_integer()
+                            .not_null()
+                            .default(1),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(RoleDepts::Table))
+                    .if_not_exists()
+                    .col(ColumnDef::new(RoleDepts::RoleId).string().not_null())
+                    .col(ColumnDef::new(RoleDepts::DeptId).string().not_null())
+                    .primary_key(
+                        Index::create()
+                            .col(RoleDepts::RoleId)
+                            .col(RoleDepts::DeptId),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_role_depts_role_id")
+                            .from(table(RoleDepts::Table), RoleDepts::RoleId)
+                            .to(table(Roles::Table), Roles::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk_role_depts_dept_id")
+                            .from(table(RoleDepts::Table), RoleDepts::DeptId)
+                            .to(table(Departments::Table), Departments::Id)
+                            .on_delete(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        let insert = Query::insert()
+            .into_table(table(Permissions::Table))
+            .columns([Permissions::Id, Permissions::Code, Permissions::Name])
+            .values_panic([
+                ASSIGN_DEPT_PERMISSION_ID.into(),
+                "system:role:assign-dept".into(),
+                "分配角色数据权限".into(),
+            ])
+            .to_owned();
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let delete = Query::delete()
+            .from_table(table(Permissions::Table))
+            .and_where(Expr::col(Permissions::Id).eq(ASSIGN_DEPT_PERMISSION_ID))
+            .to_owned();
+        manager.exec_stmt(delete).await?;
+        manager
+            .drop_table(Table::drop().table(table(RoleDepts::Table)).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table(Roles::Table))
+                    .drop_column(Roles::DataScope)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(table(Users::Table))
+                    .drop_column(Users::DeptId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(table(Departments::Table)).to_owned())
+            .await
+    }
+}
+
+#[derive(Iden)]
+enum Tenants {
+    Table,
+    Id,
+}
+
+#[derive(Iden)]
+enum Departments {
+    Table,
+    Id,
+    TenantId,
+    ParentId,
+    Name,
+}
+
+#[derive(Iden)]
+enum Users {
+    Table,
+    DeptId,
+}
+
+#[derive(Iden)]
+enum Roles {
+    Table,
+    Id,
+    DataScope,
+}
+
+#[derive(Iden)]
+enum Permissions {
+    Table,
+    Id,
+    Code,
+    Name,
+}
+
+#[derive(Iden)]
+enum RoleDepts {
+    Table,
+    RoleId,
+    DeptId,
+}
@@ -0,0 +1,246 @@
+use sea_orm_migration::prelude::*;
+use sea_query::{Alias, SeaRc, TableRef};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+/// Permissions for the audit log query endpoints: (id, code, name).
+const PERMISSIONS: &[(&str, &str, &str)] = &[
+    (
+        "01JAD00000000000000000P010",
+        "system:access-log:query",
+        "访问日志查询",
+    ),
+    (
+        "01JAD00000000000000000P011",
+        "system:login-log:query",
+        "登录日志查询",
+    ),
+    (
+        "01JAD00000000000000000P012",
+        "system:operation-log:query",
+        "操作日志查询",
+    ),
+];
+
+fn table(iden: impl IntoIden) -> TableRef {
+    TableRef::SchemaTable(SeaRc::new(Alias::new("infra")), iden.into_iden())
+}
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(AccessLogs::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(AccessLogs::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(AccessLogs::TenantId).string().null())
+                    .col(ColumnDef::new(AccessLogs::UserId).string().null())
+                    .col(ColumnDef::new(AccessLogs::Method).string().not_null())
+                    .col(ColumnDef::new(AccessLogs::Path).string().not_null())
+                    .col(
+                        ColumnDef::new(AccessLogs::Status)
+                            .small_integer()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(AccessLogs::LatencyMs)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(AccessLogs::Ip).string().not_null())
+                    .col(ColumnDef::new(AccessLogs::UserAgent).string().null())
+                    .col(
+                        ColumnDef::new(AccessLogs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(LoginLogs::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(LoginLogs::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(LoginLogs::TenantId).string().null())
+                    .col(ColumnDef::new(LoginLogs::UserId).string().null())
+                    .col(ColumnDef::new(LoginLogs::Username).string().not_null())
+                    .col(ColumnDef::new(LoginLogs::Success).boolean().not_null())
+                    .col(ColumnDef::new(LoginLogs::Reason).string().null())
+                    .col(ColumnDef::new(LoginLogs::Ip).string().not_null())
+                    .col(ColumnDef::new(LoginLogs::UserAgent).string().null())
+                    .col(
+                        ColumnDef::new(LoginLogs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(table(OperationLogs::Table))
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(OperationLogs::Id)
+                            .string()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(OperationLogs::TenantId).string().null())
+                    .col(ColumnDef::new(OperationLogs::UserId).string().not_null())
+                    .col(ColumnDef::new(OperationLogs::Module).string().not_null())
+                    .col(ColumnDef::new(OperationLogs::Method).string().not_null())
+                    .col(ColumnDef::new(OperationLogs::Path).string().not_null())
+                    .col(
+                        ColumnDef::new(OperationLogs::Status)
+                            .small_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(OperationLogs::Success).boolean().not_null())
+                    .col(
+                        ColumnDef::new(OperationLogs::LatencyMs)
+                            .big_integer()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(OperationLogs::Ip).string().not_null())
+                    .col(
+                        ColumnDef::new(OperationLogs::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        for (name, iden) in [
+            (
+                "idx_access_logs_tenant_id_created_at",
+                AccessLogs::Table.into_iden(),
+            ),
+            (
+                "idx_login_logs_tenant_id_created_at",
+                LoginLogs::Table.into_iden(),
+            ),
+            (
+                "idx_operation_logs_tenant_id_created_at",
+                OperationLogs::Table.into_iden(),
+            ),
+        ] {
+            manager
+                .create_index(
+                    Index::create()
+                        .name(name)
+                        .table(table(iden))
+                        .col(TenantId)
+                        .col(CreatedAt)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        let mut insert = Query::insert()
+            .into_table(table(Permissions::Table))
+            .columns([Permissions::Id, Permissions::Code, Permissions::Name])
+            .to_owned();
+        for (id, code, name) in PERMISSIONS {
+            insert.values_panic([(*id).into(), (*code).into(), (*name).into()]);
+        }
+        manager.exec_stmt(insert).await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let ids = PERMISSIONS.iter().map(|(id, _, _)| *id);
+        let delete = Query::delete()
+            .from_table(table(Permissions::Table))
+            .and_where(Expr::col(Permissions::Id).is_in(ids))
+            .to_owned();
+        manager.exec_stmt(delete).await?;
+        for iden in [
+            OperationLogs::Table.into_iden(),
+            LoginLogs::Table.into_iden(),
+            AccessLogs::Table.into_iden(),
+        ] {
+            manager
+                .drop_table(Table::drop().table(table(iden)).to_owned())
+                .await?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Iden)]
+struct TenantId;
+
+#[derive(Iden)]
+struct CreatedAt;
+
+#[derive(Iden)]
+enum Permissions {
+    Table,
+    Id,
+    Code,
+    Name,
+}
+
+#[derive(Iden)]
+enum AccessLogs {
+    Table,
+    Id,
+    TenantId,
+    UserId,
+    Method,
+    Path,
+    Status,
+    LatencyMs,
+    Ip,
+    UserAgent,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum LoginLogs {
+    Table,
+    Id,
+    TenantId,
+    UserId,
+    Username,
+    Success,
+    Reason,
+    Ip,
+    UserAgent,
+    CreatedAt,
+}
+
+#[derive(Iden)]
+enum OperationLogs {
+    Table,
+    Id,
+    TenantId,
+    UserId,
+    Module,
+    Method,
+    Path,
+    Status,
+    Success,
+    LatencyMs,
+    Ip,
+    CreatedAt,
+}
@@ -0,0 +1,290 @@
+//! Audit records, written to the database in batches by a background task.
+
+use std::sync::{Mutex, OnceLock, PoisonError};
+use std::time::Duration;
+
+use salvo::async_trait;
+use sea_orm::EntityTrait;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+
+use crate::db::{self, Unscoped};
+use crate::entities::prelude::{AccessLogs, LoginLogs, OperationLogs};
+use crate::entities::{access_logs, login_logs, operation_logs};
+
+const CHANNEL_CAPACITY: usize = 10_000;
+const BATCH_SIZE: usize = 500;
+const FLUSH_INTERVAL: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Debug)]
+pub enum Record {
+    Access(access_logs::ActiveModel),
+    Login(login_logs::ActiveModel),
+    Operation(operation_logs::ActiveModel),
+}
+
+static SENDER: OnceLock<mpsc::Sender<Record>> = OnceLock::new();
+/// Stops the writer task, and the task itself, until `shutdown`.
+static WRITER: Mutex<Option<(oneshot::Sender<()>, JoinHandle<()>)>> = Mutex::new(None);
+
+/// Start the writer task. Must be called after `db::init`.
+pub fn init() {
+    let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+    SENDER.set(sender).expect("audit sender should be set");
+    let (stop, stopped) = oneshot::channel();
+    let writer = Writer {
+        batch_size: BATCH_SIZE,
+        flush_interval: FLUSH_INTERVAL,
+    };
+    let task = tokio::spawn(writer.run(receiver, stopped, Database));
+    *WRITER.lock().unwrap_or_else(PoisonError::into_inner) = Some((stop, task));
+}
+
+/// Write the records still queued and stop the writer task.
+///
+/// Records queued afterwards are dropped.
+pub async fn shutdown() -> anyhow::Result<()> {
+    let writer = WRITER.lock().unwrap_or_else(PoisonError::into_inner).take();
+    if let Some((stop, task)) = writer {
+        // Only fails if the task already ended, which awaiting it reports.
+        let _ = stop.send(());
+        task.await?;
+    }
+    Ok(())
+}
+
+/// Queue a record without waiting for it to be written.
+///
+/// Records are dropped, with a warning, when the writer falls too far behind.
+pub fn record(record: Record) {
+    #[cfg(test)]
+    testing::RECORDED.with_borrow_mut(|records| records.push(record.clone()));
+    let Some(sender) = SENDER.get() else {
+        return;
+    };
+    if let Err(e) = sender.try_send(record) {
+        tracing::warn!(error = %e, "audit record dropped");
+    }
+}
+
+#[derive(Default)]
+struct Batch {
+    access: Vec<access_logs::ActiveModel>,
+    login: Vec<login_logs::ActiveModel>,
+    operation: Vec<operation_logs::ActiveModel>,
+}
+
+impl Batch {
+    fn push(&mut self, record: Record) {
+        match record {
+            Record::Access(model) => self.access.push(model),
+            Record::Login(model) => self.login.push(model),
+            Record::Operation(model) => self.operation.push(model),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.access.len() + self.login.len() + self.operation.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Where batches are written. Leaves the batch empty, whether writing succeeded or not.
+#[async_trait]
+trait Sink: Send + Sync + 'static {
+    async fn write(&self, batch: &mut Batch);
+}
+
+struct Database;
+
+#[async_trait]
+impl Sink for Database {
+    async fn write(&self, batch: &mut Batch) {
+        let conn = db::pool();
+        if !batch.access.is_empty()
+            && let Err(e) = Unscoped::<AccessLogs>::insert_many(batch.access.drain(..))
+                .exec(conn)
+                .await
+        {
+            tracing::error!(error = ?e, "failed to write access logs");
+        }
+        if !batch.login.is_empty()
+            && let Err(e) = Unscoped::<LoginLogs>::insert_many(batch.login.drain(..))
+                .exec(conn)
+                .await
+        {
+            tracing::error!(error = ?e, "failed to write login logs");
+        }
+        if !batch.operation.is_empty()
+            && let Err(e) = Unscoped::<OperationLogs>::insert_many(batch.operation.drain(..))
+                .exec(conn)
+                .await
+        {
+            tracing::error!(error = ?e, "failed to write operation logs");
+        }
+    }
+}
+
+/// Writes a batch once it holds `batch_size` records, or every `flush_interval`.
+struct Writer {
+    batch_size: usize,
+    flush_interval: Duration,
+}
+
+impl Writer {
+    /// Write records until the channel closes or `stop` fires, then write those left queued.
+    async fn run(
+        self,
+        mut receiver: mpsc::Receiver<Record>,
+        mut stop: oneshot::Receiver<()>,
+        sink: impl Sink,
+    ) {
+        let mut batch = Batch::default();
+        // The first tick comes one interval in, not right away.
+        let start = tokio::time::Instant::now() + self.flush_interval;
+        let mut ticker = tokio::time::interval_at(start, self.flush_interval);
+        let mut stopping = false;
+        loop {
+            tokio::select! {
+                received = receiver.recv() => match received {
+                    Some(record) => {
+                        batch.push(record);
+                        if batch.len() >= self.batch_size {
+                            sink.write(&mut batch).await;
+                        }
+                    }
+                    None => break,
+                },
+                _ = ticker.tick() => {
+                    if !batch.is_empty() {
+                        sink.write(&mut batch).await;
+                    }
+                }
+                // Closing lets `recv` hand out what is queued, then end the loop.
+                _ = &mut stop, if !stopping => {
+                    stopping = true;
+                    receiver.close();
+                }
+            }
+        }
+        if !batch.is_empty() {
+            sink.write(&mut batch).await;
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::cell::RefCell;
+
+    use super::Record;
+
+    thread_local! {
+        /// Every record of the test running on this thread.
+        pub(super) static RECORDED: RefCell<Vec<Record>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Take the records queued so far by the test running on this thread.
+    pub fn take() -> Vec<Record> {
+        RECORDED.take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use sea_orm::Set;
+
+    use super::*;
+
+    /// Sizes of the batches written.
+    #[derive(Clone, Default)]
+    struct Sizes(Arc<Mutex<Vec<usize>>>);
+
+    impl Sizes {
+        fn get(&self) -> Vec<usize> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl Sink for Sizes {
+        async fn write(&self, batch: &mut Batch) {
+            self.0.lock().unwrap().push(batch.len());
+            *batch = Batch::default();
+        }
+    }
+
+    fn login(id: usize) -> Record {
+        Record::Login(login_logs::ActiveModel {
+            id: Set(id.to_string()),
+            ..Default::default()
+        })
+    }
+
+    fn start(
+        batch_size: usize,
+        flush_interval: Duration,
+    ) -> (
+        mpsc::Sender<Record>,
+        oneshot::Sender<()>,
+        JoinHandle<()>,
+        Sizes,
+    ) {
+        let (sender, receiver) = mpsc::channel(CHANNEL_CAPACITY);
+        let (stop, stopped) = oneshot::channel();
+        let sizes = Sizes::default();
+        let writer = Writer {
+            batch_size,
+            flush_interval,
+        };
+        let task = tokio::spawn(writer.run(receiver, stopped, sizes.clone()));
+        (sender, stop, task, sizes)
+    }
+
+    #[tokio::test]
+    async fn full_batches_are_written_at_once() {
+        let (sender, stop, task, sizes) = start(2, Duration::from_secs(3600));
+        for id in 0..5 {
+            sender.send(login(id)).await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(sizes.get(), [2, 2]);
+
+        stop.send(()).unwrap();
+        task.await.unwrap();
+        assert_eq!(sizes.get(), [2, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn partial_batches_are_written_every_interval() {
+        let (sender, _stop, _task, sizes) = start(100, Duration::from_millis(100));
+        for id in 0..3 {
+            sender.send(login(id)).await.unwrap();
+        }
+        assert!(sizes.get().is_empty());
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        assert_eq!(sizes.get(), [3]);
+
+        sender.send(login(3)).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        assert_eq!(sizes.get(), [3, 1]);
+    }
+
+    #[tokio::test]
+    async fn stopping_writes_what_is_queued() {
+        let (sender, stop, task, sizes) = start(100, Duration::from_secs(3600));
+        for id in 0..7 {
+            sender.send(login(id)).await.unwrap();
+        }
+        stop.send(()).unwrap();
+        task.await.unwrap();
+        assert_eq!(sizes.get(), [7]);
+        // Nothing is accepted once the writer stopped.
+        assert!(sender.try_send(login(7)).is_err());
+    }
+}
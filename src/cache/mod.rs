@@ -0,0 +1,3 @@
+pub use daoyi_framework::cache::*;
+
+pub mod user;
@@ -0,0 +1,75 @@
+use sea_orm::DbErr;
+use serde::{Deserialize, Serialize};
+
+use super::{Cache, redis};
+use crate::db::{self, TenantScope};
+use crate::entities::prelude::Users;
+use crate::entities::users;
+
+/// What is cached of a user. Leaves out the password hash, so reading Redis does not give
+/// access to the hashes.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct CachedUser {
+    pub id: String,
+    pub username: String,
+    pub tenant_id: String,
+    pub dept_id: Option<String>,
+}
+
+impl From<users::Model> for CachedUser {
+    fn from(model: users::Model) -> Self {
+        Self {
+            id: model.id,
+            username: model.username,
+            tenant_id: model.tenant_id,
+            dept_id: model.dept_id,
+        }
+    }
+}
+
+/// Users by id, namespaced by tenant so ids never resolve across tenants.
+fn cache() -> Cache<CachedUser> {
+    let redis = redis();
+    Cache::new(redis.clone(), "user", redis.default_ttl())
+}
+
+fn id(scope: &TenantScope, user_id: &str) -> String {
+    format!("{}:{user_id}", scope.tenant_id())
+}
+
+/// User of the scope's tenant, read through the cache.
+pub async fn find_by_id(scope: &TenantScope, user_id: &str) -> Result<Option<CachedUser>, DbErr> {
+    cache()
+        .get_or_load(&id(scope, user_id), async {
+            let user = scope
+                .find_by_id::<Users, _>(user_id.to_owned())
+                .one(db::pool())
+                .await?;
+            Ok(user.map(CachedUser::from))
+        })
+        .await
+}
+
+/// Forget a user after it was updated or deleted.
+pub async fn evict(scope: &TenantScope, user_id: &str) {
+    cache().evict(&id(scope, user_id)).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_users_carry_no_password_hash() {
+        let user = CachedUser::from(users::Model {
+            id: "u1".into(),
+            username: "zhangsan".into(),
+            password: "$argon2id$v=19$m=19456,t=2,p=1$c2FsdA$aGFzaA".into(),
+            tenant_id: "t1".into(),
+            dept_id: None,
+        });
+        let json = serde_json::to_string(&user).unwrap();
+        assert!(!json.contains("password"));
+        assert!(!json.contains("argon2"));
+    }
+}
@@ -0,0 +1,134 @@
+//! The framework's settings, and those only the system module reads: the `[login]` and
+//! `[rate_limit]` sections and `tenant.platform_id`, extracted from the same sources.
+
+use std::sync::{Arc, Mutex, PoisonError};
+
+pub use daoyi_framework::config::*;
+use daoyi_framework::rate_limit::RateLimitConfig;
+use serde::Deserialize;
+
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct SystemConfig {
+    #[serde(default)]
+    pub login: LoginConfig,
+    #[serde(default)]
+    pub rate_limit: RateLimitConfig,
+    #[serde(default)]
+    pub tenant: PlatformConfig,
+}
+
+/// Brute-force protection for `/api/login`. Durations are in seconds.
+#[derive(Deserialize, Clone, Debug)]
+pub struct LoginConfig {
+    /// Failures per account or client address after which a captcha is required.
+    #[serde(default = "default_captcha_after")]
+    pub captcha_after: u32,
+    /// Failures after which logins are locked out.
+    #[serde(default = "default_lock_after")]
+    pub lock_after: u32,
+    /// First lockout, doubled with every further failure.
+    #[serde(default = "default_base_lockout")]
+    pub base_lockout: u64,
+    #[serde(default = "default_max_lockout")]
+    pub max_lockout: u64,
+    /// Failures are forgotten after this long without a new one.
+    #[serde(default = "default_forget_after")]
+    pub forget_after: u64,
+    #[serde(default = "default_captcha_ttl")]
+    pub captcha_ttl: u64,
+}
+impl Default for LoginConfig {
+    fn default() -> Self {
+        Self {
+            captcha_after: default_captcha_after(),
+            lock_after: default_lock_after(),
+            base_lockout: default_base_lockout(),
+            max_lockout: default_max_lockout(),
+            forget_after: default_forget_after(),
+            captcha_ttl: default_captcha_ttl(),
+        }
+    }
+}
+
+/// The part of `[tenant]` next to the framework's `TenantConfig`.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct PlatformConfig {
+    /// Tenant of the platform operators, the only one allowed to change what every tenant
+    /// shares, such as the permission catalog. Nobody may when unset.
+    pub platform_id: Option<String>,
+}
+
+/// Last settings extracted, and the server configuration they go with.
+static SYSTEM: Mutex<Option<(Arc<ServerConfig>, Arc<SystemConfig>)>> = Mutex::new(None);
+
+/// Check the settings of the system module, so invalid ones stop the server at startup.
+pub fn validate() -> anyhow::Result<()> {
+    extract::<SystemConfig>().map_err(|e| anyhow::anyhow!("invalid system module config: {e}"))?;
+    Ok(())
+}
+
+/// Settings of the system module, extracted again whenever the Nacos document changes. A
+/// version that does not extract is logged and the previous one kept, as for the framework's.
+pub fn system() -> Arc<SystemConfig> {
+    let server = get();
+    let mut cached = SYSTEM.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some((extracted_with, system)) = &*cached
+        && Arc::ptr_eq(extracted_with, &server)
+    {
+        return system.clone();
+    }
+    let system = match extract::<SystemConfig>() {
+        Ok(system) => Arc::new(system),
+        Err(e) => {
+            tracing::error!(error = %e, "ignoring invalid system module config");
+            cached
+                .as_ref()
+                .map(|(_, system)| system.clone())
+                .unwrap_or_default()
+        }
+    };
+    *cached = Some((server, system.clone()));
+    system
+}
+
+fn default_captcha_after() -> u32 {
+    3
+}
+fn default_lock_after() -> u32 {
+    5
+}
+fn default_base_lockout() -> u64 {
+    60
+}
+fn default_max_lockout() -> u64 {
+    3600
+}
+fn default_forget_after() -> u64 {
+    24 * 3600
+}
+fn default_captcha_ttl() -> u64 {
+    120
+}
+
+#[cfg(test)]
+mod tests {
+    use daoyi_framework::rate_limit::RateLimitStoreKind;
+
+    use super::*;
+
+    #[test]
+    fn system_sections_come_from_the_same_sources() {
+        testing::init(concat!(env!("CARGO_MANIFEST_DIR"), "/config-example.toml"));
+        validate().unwrap();
+
+        let system = system();
+        assert_eq!(
+            system.tenant.platform_id.as_deref(),
+            Some("01JAD00000000000000000T000")
+        );
+        assert_eq!(system.login.lock_after, 5);
+        assert_eq!(system.rate_limit.store, RateLimitStoreKind::Memory);
+        // Extracted once per version of the server configuration.
+        assert!(Arc::ptr_eq(&system, &super::system()));
+    }
+}
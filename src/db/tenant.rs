@@ -0,0 +1,134 @@
+use salvo::prelude::*;
+use sea_orm::{ColumnTrait, DeleteMany, EntityTrait, PrimaryKeyTrait, QueryFilter, Select};
+
+use crate::error::code;
+use crate::hoops::tenant::CurrentTenant;
+use crate::{AppResult, config};
+
+/// Table whose rows belong to a single tenant, implemented by the handles in
+/// `entities::prelude`. The entities of these tables are private, so every query goes through
+/// a `TenantScope`.
+pub trait TenantScoped {
+    type Entity: EntityTrait;
+
+    fn tenant_column() -> <Self::Entity as EntityTrait>::Column;
+}
+
+/// Entity of a tenant-scoped table without the tenant filter, for statements that cross tenants
+/// on purpose, such as writing audit rows that each carry their own `tenant_id`.
+pub type Unscoped<T> = <T as TenantScoped>::Entity;
+
+/// Query entry point bound to the tenant of the current request.
+///
+/// Every statement built here on a `TenantScoped` table is filtered by `tenant_id`.
+#[derive(Clone, Debug)]
+pub struct TenantScope {
+    tenant_id: String,
+}
+
+impl TenantScope {
+    pub fn new(tenant_id: impl Into<String>) -> Self {
+        Self {
+            tenant_id: tenant_id.into(),
+        }
+    }
+
+    /// Scope of the tenant resolved for this request, or the configured default tenant.
+    pub fn from_depot(depot: &Depot) -> AppResult<Self> {
+        if let Ok(CurrentTenant(tenant_id)) = depot.obtain::<CurrentTenant>() {
+            return Ok(Self::new(tenant_id.clone()));
+        }
+        config::get()
+            .tenant
+            .default_id
+            .as_ref()
+            .map(Self::new)
+            .ok_or_else(|| code::TENANT_REQUIRED.into())
+    }
+
+    pub fn tenant_id(&self) -> &str {
+        &self.tenant_id
+    }
+
+    pub fn find<T: TenantScoped>(&self) -> Select<T::Entity> {
+        T::Entity::find().filter(T::tenant_column().eq(self.tenant_id.as_str()))
+    }
+
+    pub fn find_by_id<T, V>(&self, values: V) -> Select<T::Entity>
+    where
+        T: TenantScoped,
+        V: Into<<<T::Entity as EntityTrait>::PrimaryKey as PrimaryKeyTrait>::ValueType>,
+    {
+        T::Entity::find_by_id(values).filter(T::tenant_column().eq(self.tenant_id.as_str()))
+    }
+
+    pub fn delete_many<T: TenantScoped>(&self) -> DeleteMany<T::Entity> {
+        T::Entity::delete_many().filter(T::tenant_column().eq(self.tenant_id.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ConnectOptions, ConnectionTrait, Database, DbBackend, QueryTrait};
+
+    use super::*;
+    use crate::entities::prelude::Users;
+    use crate::entities::users;
+
+    #[test]
+    fn statements_are_filtered_by_tenant() {
+        let scope = TenantScope::new("t1");
+        let statements = [
+            scope.find::<Users>().build(DbBackend::Postgres),
+            scope
+                .find_by_id::<Users, _>("u1")
+                .build(DbBackend::Postgres),
+            scope.delete_many::<Users>().build(DbBackend::Postgres),
+        ];
+        for statement in statements {
+            assert!(
+                statement
+                    .to_string()
+                    .contains(r#""users"."tenant_id" = 't1'"#)
+            );
+        }
+    }
+
+    /// Runs against the Postgres in `DATABASE_URL`, like `data_scope::filters_rows_on_postgres`.
+    #[tokio::test]
+    #[ignore = "requires a local Postgres in DATABASE_URL"]
+    async fn ids_of_other_tenants_do_not_resolve() {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL should be set");
+        let mut opt = ConnectOptions::new(url);
+        opt.max_connections(1).min_connections(1);
+        let conn = Database::connect(opt).await.unwrap();
+        conn.execute_unprepared(
+            "CREATE TEMPORARY TABLE users (
+                id text PRIMARY KEY,
+                username text NOT NULL,
+                password text NOT NULL,
+                tenant_id text NOT NULL,
+                dept_id text
+            );
+            INSERT INTO users VALUES
+                ('u1', 'ours', '', 't1', NULL),
+                ('u2', 'theirs', '', 't2', NULL);",
+        )
+        .await
+        .unwrap();
+
+        let scope = TenantScope::new("t1");
+        let ours = scope.find_by_id::<Users, _>("u1").one(&conn).await.unwrap();
+        assert_eq!(ours.map(|user| user.username).as_deref(), Some("ours"));
+        let theirs = scope.find_by_id::<Users, _>("u2").one(&conn).await.unwrap();
+        assert_eq!(theirs, None);
+
+        let deleted = scope
+            .delete_many::<Users>()
+            .filter(users::Column::Id.eq("u2"))
+            .exec(&conn)
+            .await
+            .unwrap();
+        assert_eq!(deleted.rows_affected, 0);
+    }
+}
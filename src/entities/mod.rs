@@ -1,5 +1,33 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.3
 
 pub mod prelude;
+mod scoped;
 
-pub mod users;
+pub mod permissions;
+pub mod role_depts;
+pub mod role_permissions;
+pub mod tenants;
+pub mod user_roles;
+
+/// Declares the module of a tenant-scoped table with everything but its `Entity`, which only
+/// this module can name. Queries reach the table through `TenantScope` and the handles in
+/// `prelude`, so none of them can leave out the tenant.
+macro_rules! tenant_scoped {
+    ($($module:ident: $entity:ident = $file:literal,)*) => {$(
+        #[path = $file]
+        mod $entity;
+        pub mod $module {
+            pub use super::$entity::*;
+            pub(super) use super::$entity::Entity;
+        }
+    )*};
+}
+
+tenant_scoped! {
+    access_logs: access_logs_entity = "access_logs.rs",
+    departments: departments_entity = "departments.rs",
+    login_logs: login_logs_entity = "login_logs.rs",
+    operation_logs: operation_logs_entity = "operation_logs.rs",
+    roles: roles_entity = "roles.rs",
+    users: users_entity = "users.rs",
+}
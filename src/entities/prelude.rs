@@ -1,3 +1,8 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.3
 
-pub use super::users::Entity as Users;
+pub use super::permissions::Entity as Permissions;
+pub use super::role_depts::Entity as RoleDepts;
+pub use super::role_permissions::Entity as RolePermissions;
+pub use super::scoped::{AccessLogs, Departments, LoginLogs, OperationLogs, Roles, Users};
+pub use super::tenants::Entity as Tenants;
+pub use super::user_roles::Entity as UserRoles;
@@ -0,0 +1,37 @@
+//! Handles of the tenant-scoped tables, standing in for their private entities.
+
+use super::{access_logs, departments, login_logs, operation_logs, roles, users};
+use crate::db::{DataScoped, TenantScoped};
+
+macro_rules! handles {
+    ($($handle:ident => $module:ident,)*) => {$(
+        pub struct $handle;
+
+        impl TenantScoped for $handle {
+            type Entity = $module::Entity;
+
+            fn tenant_column() -> $module::Column {
+                $module::Column::TenantId
+            }
+        }
+    )*};
+}
+
+handles! {
+    AccessLogs => access_logs,
+    Departments => departments,
+    LoginLogs => login_logs,
+    OperationLogs => operation_logs,
+    Roles => roles,
+    Users => users,
+}
+
+impl DataScoped for users::Entity {
+    fn dept_column() -> users::Column {
+        users::Column::DeptId
+    }
+
+    fn owner_column() -> users::Column {
+        users::Column::Id
+    }
+}
@@ -8,12 +8,50 @@ use serde::{Deserialize, Serialize};
 pub struct Model {
     #[sea_orm(primary_key, auto_increment = false)]
     pub id: String,
-    #[sea_orm(unique)]
     pub username: String,
     pub password: String,
+    pub tenant_id: String,
+    pub dept_id: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::departments::Entity",
+        from = "Column::DeptId",
+        to = "super::departments::Column::Id",
+        on_update = "NoAction",
+        on_delete = "SetNull"
+    )]
+    Departments,
+    #[sea_orm(
+        belongs_to = "super::tenants::Entity",
+        from = "Column::TenantId",
+        to = "super::tenants::Column::Id",
+        on_update = "NoAction",
+        on_delete = "NoAction"
+    )]
+    Tenants,
+    #[sea_orm(has_many = "super::user_roles::Entity")]
+    UserRoles,
+}
+
+impl Related<super::user_roles::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserRoles.def()
+    }
+}
+
+impl Related<super::departments::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Departments.def()
+    }
+}
+
+impl Related<super::tenants::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Tenants.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
@@ -0,0 +1,120 @@
+//! Error codes returned in the `code` field of `CommonResult`: the framework's global codes and
+//! the business codes of this server, `1_MMM_SSS_EEE` as in the Java services.
+
+use salvo::http::StatusCode;
+
+pub use daoyi_framework::error::code::*;
+
+// System module: authentication.
+pub const AUTH_LOGIN_BAD_CREDENTIALS: ErrorCode = ErrorCode::new(
+    1_002_000_000,
+    StatusCode::UNAUTHORIZED,
+    "Incorrect username or password.",
+);
+pub const AUTH_LOGIN_CAPTCHA_INVALID: ErrorCode = ErrorCode::new(
+    1_002_000_001,
+    StatusCode::BAD_REQUEST,
+    "Captcha is missing or incorrect.",
+);
+pub const AUTH_LOGIN_LOCKED: ErrorCode = ErrorCode::new(
+    1_002_000_002,
+    StatusCode::TOO_MANY_REQUESTS,
+    "Too many failed login attempts.",
+);
+pub const AUTH_REFRESH_TOKEN_INVALID: ErrorCode = ErrorCode::new(
+    1_002_000_003,
+    StatusCode::UNAUTHORIZED,
+    "Refresh token is invalid or has been revoked.",
+);
+pub const AUTH_CSRF_TOKEN_INVALID: ErrorCode = ErrorCode::new(
+    1_002_000_004,
+    StatusCode::FORBIDDEN,
+    "CSRF token is missing or incorrect.",
+);
+
+// System module: permissions.
+pub const PERMISSION_NOT_EXISTS: ErrorCode = ErrorCode::new(
+    1_002_001_000,
+    StatusCode::NOT_FOUND,
+    "Permission does not exist.",
+);
+pub const PERMISSION_PLATFORM_ONLY: ErrorCode = ErrorCode::new(
+    1_002_001_001,
+    StatusCode::FORBIDDEN,
+    "Only the platform tenant may change permissions.",
+);
+pub const PERMISSION_SOME_NOT_EXISTS: ErrorCode = ErrorCode::new(
+    1_002_001_002,
+    StatusCode::BAD_REQUEST,
+    "Some permissions do not exist.",
+);
+pub const PERMISSION_ALL_NOT_GRANTABLE: ErrorCode = ErrorCode::new(
+    1_002_001_003,
+    StatusCode::FORBIDDEN,
+    "Only the platform tenant and holders of `*:*:*` may grant it.",
+);
+
+// System module: roles.
+pub const ROLE_NOT_EXISTS: ErrorCode =
+    ErrorCode::new(1_002_002_000, StatusCode::NOT_FOUND, "Role does not exist.");
+pub const ROLE_SOME_NOT_EXISTS: ErrorCode = ErrorCode::new(
+    1_002_002_001,
+    StatusCode::BAD_REQUEST,
+    "Some roles do not exist.",
+);
+
+// System module: users.
+pub const USER_NOT_EXISTS: ErrorCode =
+    ErrorCode::new(1_002_003_000, StatusCode::NOT_FOUND, "User does not exist.");
+
+// System module: departments.
+pub const DEPT_SOME_NOT_EXISTS: ErrorCode = ErrorCode::new(
+    1_002_004_000,
+    StatusCode::BAD_REQUEST,
+    "Some departments do not exist.",
+);
+
+// System module: tenants.
+pub const TENANT_REQUIRED: ErrorCode = ErrorCode::new(
+    1_002_015_000,
+    StatusCode::BAD_REQUEST,
+    "Tenant is required.",
+);
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    #[test]
+    fn codes_are_unique() {
+        let codes = [
+            SUCCESS,
+            BAD_REQUEST,
+            UNAUTHORIZED,
+            FORBIDDEN,
+            NOT_FOUND,
+            METHOD_NOT_ALLOWED,
+            CONFLICT,
+            TOO_MANY_REQUESTS,
+            INTERNAL_SERVER_ERROR,
+            AUTH_LOGIN_BAD_CREDENTIALS,
+            AUTH_LOGIN_CAPTCHA_INVALID,
+            AUTH_LOGIN_LOCKED,
+            AUTH_REFRESH_TOKEN_INVALID,
+            AUTH_CSRF_TOKEN_INVALID,
+            PERMISSION_NOT_EXISTS,
+            PERMISSION_PLATFORM_ONLY,
+            PERMISSION_SOME_NOT_EXISTS,
+            PERMISSION_ALL_NOT_GRANTABLE,
+            ROLE_NOT_EXISTS,
+            ROLE_SOME_NOT_EXISTS,
+            USER_NOT_EXISTS,
+            DEPT_SOME_NOT_EXISTS,
+            TENANT_REQUIRED,
+        ];
+        let unique = codes.iter().map(|code| code.code).collect::<HashSet<_>>();
+        assert_eq!(unique.len(), codes.len());
+    }
+}
@@ -0,0 +1,4 @@
+pub use daoyi_framework::error::AppError;
+
+pub mod code;
+pub use code::ErrorCode;
@@ -0,0 +1,100 @@
+use std::fmt;
+use std::ops::Deref;
+
+use salvo::extract::{Extractible, Metadata};
+use salvo::oapi::extract::JsonBody;
+use salvo::oapi::{Components, EndpointArgRegister, Operation, ToSchema};
+use salvo::prelude::*;
+use serde::Deserialize;
+use validator::Validate;
+
+use crate::AppError;
+
+/// JSON request body checked with its `validator::Validate` rules before the handler runs.
+///
+/// Invalid bodies are answered with 400 and the messages of every failing field.
+/// Document the rules on the fields with `#[salvo(schema(...))]` so they show up in OpenAPI.
+#[derive(Debug)]
+pub struct ValidJson<T>(pub T);
+
+impl<T> ValidJson<T> {
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T> Deref for ValidJson<T> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'ex, T> Extractible<'ex> for ValidJson<T>
+where
+    T: Deserialize<'ex> + Validate + Send,
+{
+    fn metadata() -> &'static Metadata {
+        JsonBody::<T>::metadata()
+    }
+
+    async fn extract(
+        req: &'ex mut Request,
+    ) -> Result<Self, impl Writer + Send + fmt::Debug + 'static> {
+        let data = req.parse_json::<T>().await.map_err(AppError::from)?;
+        data.validate().map_err(AppError::from)?;
+        Ok::<_, AppError>(Self(data))
+    }
+}
+
+impl<'de, T> EndpointArgRegister for ValidJson<T>
+where
+    T: Deserialize<'de> + ToSchema,
+{
+    fn register(components: &mut Components, operation: &mut Operation, arg: &str) {
+        JsonBody::<T>::register(components, operation, arg);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::test::{ResponseExt, TestClient};
+    use serde_json::{Value, json};
+
+    use super::*;
+
+    #[derive(Deserialize, Validate)]
+    struct Input {
+        #[validate(length(min = 3, message = "name is too short"))]
+        name: String,
+    }
+
+    #[handler]
+    async fn echo(input: ValidJson<Input>) -> String {
+        input.into_inner().name
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_fields_with_their_messages() {
+        let service = Service::new(Router::new().post(echo));
+
+        let mut res = TestClient::post("http://127.0.0.1/")
+            .json(&json!({ "name": "ab" }))
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::BAD_REQUEST));
+        let body = res.take_json::<Value>().await.unwrap();
+        assert_eq!(body["code"], 400);
+        assert_eq!(body["data"]["name"][0], "name is too short");
+
+        let content = TestClient::post("http://127.0.0.1/")
+            .json(&json!({ "name": "abc" }))
+            .send(&service)
+            .await
+            .take_string()
+            .await
+            .unwrap();
+        assert_eq!(content, "abc");
+    }
+}
@@ -0,0 +1,229 @@
+use std::time::Instant;
+
+use salvo::http::Method;
+use salvo::http::header::USER_AGENT;
+use salvo::jwt_auth::JwtAuthDepotExt;
+use salvo::prelude::*;
+use sea_orm::Set;
+use time::OffsetDateTime;
+use ulid::Ulid;
+
+use crate::audit::{self, Record};
+use crate::entities::{access_logs, operation_logs};
+use crate::hoops::jwt::JwtClaims;
+use crate::hoops::tenant::CurrentTenant;
+use crate::utils;
+
+/// User and tenant of the request, once the inner handlers have run.
+fn caller(depot: &Depot) -> (Option<String>, Option<String>) {
+    let uid = depot
+        .jwt_auth_data::<JwtClaims>()
+        .map(|data| data.claims.uid.clone());
+    let tenant_id = depot
+        .obtain::<CurrentTenant>()
+        .ok()
+        .map(|CurrentTenant(tenant_id)| tenant_id.clone());
+    (uid, tenant_id)
+}
+
+fn status(res: &Response) -> StatusCode {
+    res.status_code.unwrap_or(StatusCode::OK)
+}
+
+/// Record every request in the access log.
+#[handler]
+pub async fn access_log_hoop(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    let started_at = Instant::now();
+    ctrl.call_next(req, depot, res).await;
+    let (user_id, tenant_id) = caller(depot);
+    audit::record(Record::Access(access_logs::ActiveModel {
+        id: Set(Ulid::new().to_string()),
+        tenant_id: Set(tenant_id),
+        user_id: Set(user_id),
+        method: Set(req.method().to_string()),
+        path: Set(req.uri().path().to_owned()),
+        status: Set(status(res).as_u16() as i16),
+        latency_ms: Set(started_at.elapsed().as_millis() as i64),
+        ip: Set(utils::client_ip(req)),
+        user_agent: Set(req.header::<String>(USER_AGENT)),
+        created_at: Set(OffsetDateTime::now_utc()),
+    }));
+}
+
+/// Path segment naming the module, e.g. `users` for `/api/users/{user_id}`, wherever the
+/// routes are mounted.
+fn module_of(path: &str) -> String {
+    path.split('/')
+        .skip_while(|segment| *segment != "api")
+        .nth(1)
+        .unwrap_or_default()
+        .to_owned()
+}
+
+/// Record authenticated requests that change data in the operation log.
+#[handler]
+pub async fn operation_log_hoop(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    if matches!(*req.method(), Method::GET | Method::HEAD | Method::OPTIONS) {
+        ctrl.call_next(req, depot, res).await;
+        return;
+    }
+    let module = module_of(req.uri().path());
+    let started_at = Instant::now();
+    ctrl.call_next(req, depot, res).await;
+    let (Some(user_id), tenant_id) = caller(depot) else {
+        return;
+    };
+    let status = status(res);
+    audit::record(Record::Operation(operation_logs::ActiveModel {
+        id: Set(Ulid::new().to_string()),
+        tenant_id: Set(tenant_id),
+        user_id: Set(user_id),
+        module: Set(module),
+        method: Set(req.method().to_string()),
+        path: Set(req.uri().path().to_owned()),
+        status: Set(status.as_u16() as i16),
+        success: Set(status.is_success()),
+        latency_ms: Set(started_at.elapsed().as_millis() as i64),
+        ip: Set(utils::client_ip(req)),
+        created_at: Set(OffsetDateTime::now_utc()),
+    }));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use salvo::test::TestClient;
+
+    use super::*;
+    use crate::audit::testing;
+    use crate::hoops::testing::SignIn;
+
+    #[handler]
+    async fn slow_create(res: &mut Response) {
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        res.status_code(StatusCode::CREATED);
+    }
+
+    #[handler]
+    async fn forbidden(res: &mut Response) {
+        res.status_code(StatusCode::FORBIDDEN);
+    }
+
+    fn service(signed_in: bool) -> Service {
+        let mut api = Router::with_path("api").hoop(operation_log_hoop);
+        if signed_in {
+            api = api.hoop(SignIn {
+                uid: "u1",
+                tid: "t1",
+            });
+        }
+        Service::new(
+            Router::new().hoop(access_log_hoop).push(
+                api.push(
+                    Router::with_path("users/{id}")
+                        .get(slow_create)
+                        .post(slow_create)
+                        .delete(forbidden),
+                ),
+            ),
+        )
+    }
+
+    fn access_logs(records: &[Record]) -> Vec<&access_logs::ActiveModel> {
+        records
+            .iter()
+            .filter_map(|record| match record {
+                Record::Access(model) => Some(model),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn operation_logs(records: &[Record]) -> Vec<&operation_logs::ActiveModel> {
+        records
+            .iter()
+            .filter_map(|record| match record {
+                Record::Operation(model) => Some(model),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn access_log_records_status_latency_and_caller() {
+        TestClient::get("http://127.0.0.1/api/users/1")
+            .add_header(USER_AGENT, "test-agent", true)
+            .send(&service(true))
+            .await;
+        let records = testing::take();
+        let [log] = access_logs(&records)[..] else {
+            panic!("expected one access log, got {records:?}");
+        };
+        assert_eq!(log.method, Set("GET".to_owned()));
+        assert_eq!(log.path, Set("/api/users/1".to_owned()));
+        assert_eq!(log.status, Set(201));
+        assert!(log.latency_ms.clone().unwrap() >= 30);
+        assert_eq!(log.user_id, Set(Some("u1".to_owned())));
+        assert_eq!(log.tenant_id, Set(Some("t1".to_owned())));
+        assert_eq!(log.user_agent, Set(Some("test-agent".to_owned())));
+
+        TestClient::get("http://127.0.0.1/api/users/1")
+            .send(&service(false))
+            .await;
+        let records = testing::take();
+        let [log] = access_logs(&records)[..] else {
+            panic!("expected one access log, got {records:?}");
+        };
+        assert_eq!(log.user_id, Set(None));
+        assert_eq!(log.tenant_id, Set(None));
+    }
+
+    #[tokio::test]
+    async fn operation_log_records_signed_in_writes_only() {
+        TestClient::post("http://127.0.0.1/api/users/1")
+            .send(&service(true))
+            .await;
+        TestClient::delete("http://127.0.0.1/api/users/1")
+            .send(&service(true))
+            .await;
+        let records = testing::take();
+        let [created, deleted] = operation_logs(&records)[..] else {
+            panic!("expected two operation logs, got {records:?}");
+        };
+        assert_eq!(created.module, Set("users".to_owned()));
+        assert_eq!(created.method, Set("POST".to_owned()));
+        assert_eq!(created.status, Set(201));
+        assert_eq!(created.success, Set(true));
+        assert!(created.latency_ms.clone().unwrap() >= 30);
+        assert_eq!(created.user_id, Set("u1".to_owned()));
+        assert_eq!(created.tenant_id, Set(Some("t1".to_owned())));
+        assert_eq!(deleted.status, Set(403));
+        assert_eq!(deleted.success, Set(false));
+
+        TestClient::get("http://127.0.0.1/api/users/1")
+            .send(&service(true))
+            .await;
+        TestClient::post("http://127.0.0.1/api/users/1")
+            .send(&service(false))
+            .await;
+        assert!(operation_logs(&testing::take()).is_empty());
+    }
+
+    #[test]
+    fn module_is_the_segment_after_api() {
+        assert_eq!(module_of("/api/users/1"), "users");
+        assert_eq!(module_of("/admin-api/system/api/roles/1"), "roles");
+        assert_eq!(module_of("/api"), "");
+    }
+}
@@ -1,9 +1,223 @@
-use salvo::cors::{AllowHeaders, AllowMethods, AllowOrigin, Cors, CorsHandler};
+//! CORS policy of the `[cors]` section, rebuilt when a config reload changes it.
 
-pub fn cors_hoop() -> CorsHandler {
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+
+use salvo::cors::{AllowHeaders, AllowMethods, AllowOrigin, Cors, CorsHandler, ExposeHeaders};
+use salvo::http::{HeaderName, Method};
+use salvo::prelude::*;
+
+use crate::config::{self, CorsConfig, ServerConfig};
+
+/// Entry of `cors.allowed_origins`.
+enum Origin {
+    Any,
+    Exact(String),
+    /// What is around the `*`.
+    Pattern {
+        prefix: String,
+        suffix: String,
+    },
+}
+
+impl Origin {
+    fn parse(origin: &str) -> Self {
+        let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+        if origin == "*" {
+            return Self::Any;
+        }
+        match origin.split_once('*') {
+            Some((prefix, suffix)) => Self::Pattern {
+                prefix: prefix.to_owned(),
+                suffix: suffix.to_owned(),
+            },
+            None => Self::Exact(origin),
+        }
+    }
+
+    fn matches(&self, origin: &str) -> bool {
+        let origin = origin.to_ascii_lowercase();
+        match self {
+            Self::Any => true,
+            Self::Exact(exact) => *exact == origin,
+            // The `*` stands for host name labels or a port, never for a `/` or `@` that would
+            // move the match into another part of the URL.
+            Self::Pattern { prefix, suffix } => origin
+                .strip_prefix(prefix.as_str())
+                .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                .is_some_and(|part| {
+                    !part.is_empty()
+                        && part
+                            .chars()
+                            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+                }),
+        }
+    }
+}
+
+fn parse_all<T: std::str::FromStr>(values: &[String], what: &str) -> Vec<T> {
+    values
+        .iter()
+        .filter_map(|value| {
+            let parsed = value.parse().ok();
+            if parsed.is_none() {
+                tracing::warn!(value, "ignoring invalid CORS {what}");
+            }
+            parsed
+        })
+        .collect()
+}
+
+fn cors_handler(config: &CorsConfig) -> CorsHandler {
+    let origins = config
+        .allowed_origins
+        .iter()
+        .map(|origin| Origin::parse(origin))
+        .collect::<Vec<_>>();
     Cors::new()
-        .allow_origin(AllowOrigin::any())
-        .allow_methods(AllowMethods::any())
-        .allow_headers(AllowHeaders::any())
+        .allow_origin(AllowOrigin::dynamic(move |origin, _req, _depot| {
+            origin
+                .filter(|origin| {
+                    origin
+                        .to_str()
+                        .is_ok_and(|origin| origins.iter().any(|allowed| allowed.matches(origin)))
+                })
+                .cloned()
+        }))
+        .allow_methods(AllowMethods::list(parse_all::<Method>(
+            &config.allowed_methods,
+            "method",
+        )))
+        .allow_headers(AllowHeaders::list(parse_all::<HeaderName>(
+            &config.allowed_headers,
+            "header",
+        )))
+        .expose_headers(ExposeHeaders::list(parse_all::<HeaderName>(
+            &config.exposed_headers,
+            "header",
+        )))
+        .allow_credentials(config.allow_credentials)
+        .max_age(Duration::from_secs(config.max_age))
         .into_handler()
 }
+
+/// Hoop applying the CORS policy of the current configuration.
+#[derive(Default)]
+pub struct CorsPolicy {
+    /// Handler and the configuration it was built from.
+    current: Mutex<Option<(Arc<ServerConfig>, Arc<CorsHandler>)>>,
+}
+
+impl CorsPolicy {
+    fn handler(&self) -> Arc<CorsHandler> {
+        let config = config::get();
+        let mut current = self.current.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some((built_from, handler)) = &*current
+            && Arc::ptr_eq(built_from, &config)
+        {
+            return handler.clone();
+        }
+        let handler = Arc::new(cors_handler(
+            &config.cors.resolve(config.environment.as_deref()),
+        ));
+        *current = Some((config, handler.clone()));
+        handler
+    }
+}
+
+#[async_trait]
+impl Handler for CorsPolicy {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        self.handler().handle(req, depot, res, ctrl).await;
+    }
+}
+
+pub fn cors_hoop() -> CorsPolicy {
+    CorsPolicy::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::http::header::{
+        ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_ORIGIN,
+        ACCESS_CONTROL_REQUEST_METHOD, ORIGIN,
+    };
+    use salvo::test::TestClient;
+
+    use super::*;
+    use crate::config::CorsEnvironmentConfig;
+
+    #[test]
+    fn patterns_match_subdomains_and_ports_only() {
+        let subdomains = Origin::parse("https://*.example.com");
+        assert!(subdomains.matches("https://admin.example.com"));
+        assert!(subdomains.matches("https://a.b.example.com"));
+        assert!(!subdomains.matches("https://example.com"));
+        assert!(!subdomains.matches("http://admin.example.com"));
+        assert!(!subdomains.matches("https://evil.com/.example.com"));
+        assert!(!subdomains.matches("https://admin.example.com.evil.com"));
+
+        let ports = Origin::parse("http://localhost:*");
+        assert!(ports.matches("http://localhost:5173"));
+        assert!(!ports.matches("http://localhost"));
+
+        assert!(Origin::parse("https://Admin.example.com/").matches("https://admin.example.com"));
+    }
+
+    #[handler]
+    async fn ok(res: &mut Response) {
+        res.render("ok");
+    }
+
+    async fn preflight(config: &CorsConfig, origin: &str) -> Response {
+        // On the service, as preflights match no route.
+        let service = Service::new(Router::new().post(ok)).hoop(cors_handler(config));
+        TestClient::options("http://127.0.0.1/")
+            .add_header(ORIGIN, origin, true)
+            .add_header(ACCESS_CONTROL_REQUEST_METHOD, "POST", true)
+            .send(&service)
+            .await
+    }
+
+    #[tokio::test]
+    async fn allows_only_the_origins_of_the_environment() {
+        let config = CorsConfig {
+            allowed_origins: vec!["https://admin.example.com".into()],
+            allow_credentials: true,
+            environments: [(
+                "dev".to_owned(),
+                CorsEnvironmentConfig {
+                    allowed_origins: Some(vec!["http://localhost:*".into()]),
+                    allow_credentials: None,
+                },
+            )]
+            .into(),
+            ..Default::default()
+        };
+        let prod = config.resolve(Some("prod"));
+        let res = preflight(&prod, "https://admin.example.com").await;
+        assert_eq!(
+            res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "https://admin.example.com"
+        );
+        assert_eq!(
+            res.headers().get(ACCESS_CONTROL_ALLOW_CREDENTIALS).unwrap(),
+            "true"
+        );
+        let res = preflight(&prod, "http://localhost:5173").await;
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+
+        let dev = config.resolve(Some("dev"));
+        let res = preflight(&dev, "http://localhost:5173").await;
+        assert_eq!(
+            res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).unwrap(),
+            "http://localhost:5173"
+        );
+    }
+}
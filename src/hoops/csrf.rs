@@ -0,0 +1,136 @@
+//! CSRF protection of cookie-authenticated requests by double submit.
+//!
+//! Pages embed the token of the `csrf_token` cookie and their scripts send it back in the
+//! `X-CSRF-Token` header. Another site can make the browser send the cookies but cannot read
+//! them to fill in the header. Requests with an `Authorization` header are exempt, as a browser
+//! only sends one when a script of an allowed origin sets it.
+
+use cookie::{Cookie, SameSite};
+use salvo::http::Method;
+use salvo::http::header::AUTHORIZATION;
+use salvo::prelude::*;
+
+use crate::error::code;
+use crate::{AppError, utils};
+
+pub const COOKIE: &str = "csrf_token";
+pub const HEADER: &str = "x-csrf-token";
+const TOKEN_LEN: usize = 32;
+
+fn cookie(token: String) -> Cookie<'static> {
+    Cookie::build((COOKIE, token))
+        .path("/")
+        .http_only(true)
+        .same_site(SameSite::Strict)
+        .build()
+}
+
+fn well_formed(token: &str) -> bool {
+    token.len() == TOKEN_LEN && token.bytes().all(|b| b.is_ascii_alphanumeric())
+}
+
+/// Compare in time independent of where the tokens differ.
+fn same(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+/// Token for a page to embed: the one of the browser's cookie, or a new one.
+pub fn token(req: &Request, res: &mut Response) -> String {
+    match req.cookie(COOKIE) {
+        Some(cookie) if well_formed(cookie.value()) => cookie.value().to_owned(),
+        _ => renew(res),
+    }
+}
+
+/// Replace the browser's token, so one planted before a login is useless after it.
+pub fn renew(res: &mut Response) -> String {
+    let token = utils::random_string(TOKEN_LEN);
+    res.add_cookie(cookie(token.clone()));
+    token
+}
+
+/// Refuse unsafe requests authenticated by the `jwt_token` cookie without a matching token.
+#[handler]
+pub async fn csrf_hoop(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    let safe = matches!(
+        *req.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+    );
+    if safe || req.headers().contains_key(AUTHORIZATION) || req.cookie("jwt_token").is_none() {
+        return;
+    }
+    if let Some(expected) = req.cookie(COOKIE)
+        && let Some(sent) = req.header::<String>(HEADER)
+        && well_formed(expected.value())
+        && same(expected.value(), &sent)
+    {
+        return;
+    }
+    tracing::warn!(
+        path = req.uri().path(),
+        "refusing request without a valid CSRF token"
+    );
+    AppError::from(code::AUTH_CSRF_TOKEN_INVALID)
+        .write(req, depot, res)
+        .await;
+    ctrl.skip_rest();
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::http::header::COOKIE as COOKIE_HEADER;
+    use salvo::test::{RequestBuilder, TestClient};
+
+    use super::*;
+
+    #[handler]
+    async fn ok(res: &mut Response) {
+        res.render("ok");
+    }
+
+    fn service() -> Service {
+        Service::new(Router::new().hoop(csrf_hoop).get(ok).post(ok))
+    }
+
+    async fn status(req: RequestBuilder) -> StatusCode {
+        req.send(&service())
+            .await
+            .status_code
+            .unwrap_or(StatusCode::OK)
+    }
+
+    #[tokio::test]
+    async fn cookie_authenticated_posts_need_the_token() {
+        let token = "a".repeat(TOKEN_LEN);
+        let cookies = format!("jwt_token=jwt; {COOKIE}={token}");
+        let url = "http://127.0.0.1/";
+
+        let forged = TestClient::post(url).add_header(COOKIE_HEADER, &cookies, true);
+        assert_eq!(status(forged).await, StatusCode::FORBIDDEN);
+        let wrong = TestClient::post(url)
+            .add_header(COOKIE_HEADER, &cookies, true)
+            .add_header(HEADER, "b".repeat(TOKEN_LEN), true);
+        assert_eq!(status(wrong).await, StatusCode::FORBIDDEN);
+        let valid = TestClient::post(url)
+            .add_header(COOKIE_HEADER, &cookies, true)
+            .add_header(HEADER, &token, true);
+        assert_eq!(status(valid).await, StatusCode::OK);
+
+        let read = TestClient::get(url).add_header(COOKIE_HEADER, &cookies, true);
+        assert_eq!(status(read).await, StatusCode::OK);
+        let bearer = TestClient::post(url)
+            .add_header(COOKIE_HEADER, &cookies, true)
+            .add_header(AUTHORIZATION, "Bearer jwt", true);
+        assert_eq!(status(bearer).await, StatusCode::OK);
+        assert_eq!(status(TestClient::post(url)).await, StatusCode::OK);
+    }
+}
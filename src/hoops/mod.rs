@@ -1,12 +1,25 @@
 use askama::Template;
 use salvo::http::ResBody;
 use salvo::prelude::*;
+use serde_json::Value;
 
+use crate::{CommonResult, ErrorCode};
+
+pub mod audit;
+pub use audit::{access_log_hoop, operation_log_hoop};
 pub mod custom_middleware_example;
-pub mod jwt;
+pub use daoyi_framework::jwt;
 pub use jwt::auth_hoop;
 mod cors;
 pub use cors::cors_hoop;
+pub mod csrf;
+pub use csrf::csrf_hoop;
+pub mod permission;
+pub use permission::{require_permission, require_platform_tenant};
+pub mod rate_limit;
+pub use rate_limit::{api_rate_limit, login_rate_limit};
+pub mod tenant;
+pub use tenant::tenant_hoop;
 
 #[derive(Template)]
 #[template(path = "error_404.html")]
@@ -28,3 +41,67 @@ pub async fn error_404(&self, res: &mut Response, ctrl: &mut FlowCtrl) {
         ctrl.skip_rest();
     }
 }
+
+/// Render errors of API requests in the `CommonResult` envelope instead of salvo's error body.
+#[handler]
+pub async fn error_envelope(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    let Some(status) = res.status_code else {
+        return;
+    };
+    // Matches wherever the module is mounted, e.g. `/admin-api/system/api/users`.
+    if !req.uri().path().contains("/api/") {
+        return;
+    }
+    let code = ErrorCode::from_status(status);
+    let msg = match &res.body {
+        ResBody::Error(e) => e.brief.clone(),
+        ResBody::None => code.msg.to_owned(),
+        _ => return,
+    };
+    res.render(Json(
+        CommonResult::<Value>::error(code, msg, None).correlated(depot),
+    ));
+    ctrl.skip_rest();
+}
+
+#[cfg(test)]
+pub(crate) mod testing {
+    use jsonwebtoken::{Header, TokenData};
+    use salvo::jwt_auth::JWT_AUTH_DATA_KEY;
+    use salvo::prelude::*;
+
+    use super::jwt::{JwtClaims, TokenKind};
+    use super::tenant::CurrentTenant;
+
+    /// Hoop that signs `uid` of `tid` in, as `tenant_hoop` and `auth_hoop` do for a valid token.
+    pub struct SignIn {
+        pub uid: &'static str,
+        pub tid: &'static str,
+    }
+
+    #[handler]
+    impl SignIn {
+        async fn handle(&self, depot: &mut Depot) {
+            depot.inject(CurrentTenant(self.tid.to_owned()));
+            depot.insert(
+                JWT_AUTH_DATA_KEY,
+                TokenData {
+                    header: Header::default(),
+                    claims: JwtClaims {
+                        uid: self.uid.to_owned(),
+                        tid: self.tid.to_owned(),
+                        jti: "jti".to_owned(),
+                        fid: "fid".to_owned(),
+                        typ: TokenKind::Access,
+                        exp: i64::MAX,
+                    },
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use std::collections::HashSet;
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use redis::{AsyncCommands, RedisResult};
+use salvo::jwt_auth::JwtAuthDepotExt;
+use salvo::prelude::*;
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter, QuerySelect};
+
+use crate::cache::{self, Cache, Redis};
+use crate::config::PlatformConfig;
+use crate::db::{self, TenantScope};
+use crate::entities::prelude::{Permissions, RolePermissions, Roles, UserRoles};
+use crate::entities::{role_permissions, user_roles};
+use crate::error::code;
+use crate::hoops::jwt::JwtClaims;
+use crate::{AppError, AppResult};
+
+/// Permission code granting every permission.
+pub const ALL_PERMISSIONS: &str = "*:*:*";
+
+const CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Permission sets shared by every instance, in the keys
+/// `<prefix>permissions:{generation}:{tenant}:{uid}`.
+///
+/// Bumping the generation in `<prefix>permissions-generation` invalidates every set at once on
+/// all instances; the sets of older generations expire on their own.
+pub struct PermissionCache {
+    redis: Redis,
+}
+
+impl PermissionCache {
+    pub fn new(redis: Redis) -> Self {
+        Self { redis }
+    }
+
+    fn generation_key(&self) -> String {
+        self.redis.key("permissions-generation")
+    }
+
+    async fn generation(&self) -> RedisResult<u64> {
+        let generation: Option<u64> = self.redis.conn().get(self.generation_key()).await?;
+        Ok(generation.unwrap_or_default())
+    }
+
+    /// Cached set of `uid` in the scope's tenant, or the one `load` returns.
+    ///
+    /// Redis failures are logged and fall back to `load`, so the cache never fails a request.
+    pub async fn get_or_load<F>(
+        &self,
+        scope: &TenantScope,
+        uid: &str,
+        load: F,
+    ) -> Result<HashSet<String>, DbErr>
+    where
+        F: Future<Output = Result<HashSet<String>, DbErr>>,
+    {
+        let generation = match self.generation().await {
+            Ok(generation) => generation,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to read the permission cache generation");
+                return load.await;
+            }
+        };
+        let sets = Cache::new(self.redis.clone(), "permissions", CACHE_TTL);
+        let id = format!("{generation}:{}:{uid}", scope.tenant_id());
+        let codes = sets
+            .get_or_load(&id, async { load.await.map(Some) })
+            .await?;
+        Ok(codes.unwrap_or_default())
+    }
+
+    /// Drop every cached set, on every instance. Failures are only logged, leaving sets to
+    /// expire within `CACHE_TTL`.
+    pub async fn invalidate_all(&self) {
+        let bumped: RedisResult<u64> = self.redis.conn().incr(self.generation_key(), 1).await;
+        if let Err(e) = bumped {
+            tracing::warn!(error = %e, "failed to invalidate cached permissions");
+        }
+    }
+}
+
+static CACHE: LazyLock<PermissionCache> =
+    LazyLock::new(|| PermissionCache::new(cache::redis().clone()));
+
+/// Drop every cached permission set. Call after changing roles, permissions or their grants.
+pub async fn invalidate_all() {
+    CACHE.invalidate_all().await;
+}
+
+/// Permission codes granted to `uid` through its roles in the scope's tenant, cached for a
+/// short while.
+pub async fn permissions_of(scope: &TenantScope, uid: &str) -> Result<HashSet<String>, DbErr> {
+    CACHE.get_or_load(scope, uid, load(scope, uid)).await
+}
+
+async fn load(scope: &TenantScope, uid: &str) -> Result<HashSet<String>, DbErr> {
+    let conn = db::pool();
+    let role_ids = scope
+        .find::<Roles>()
+        .inner_join(UserRoles)
+        .filter(user_roles::Column::UserId.eq(uid))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|role| role.id)
+        .collect::<Vec<_>>();
+    if role_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+    Ok(Permissions::find()
+        .inner_join(RolePermissions)
+        .filter(role_permissions::Column::RoleId.is_in(role_ids))
+        .distinct()
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|permission| permission.code)
+        .collect())
+}
+
+/// Permission codes of the caller, loaded once per request and kept in the `Depot`.
+#[derive(Clone, Debug)]
+pub struct Granted(Arc<HashSet<String>>);
+
+impl Granted {
+    pub async fn from_depot(depot: &mut Depot) -> AppResult<Self> {
+        if let Ok(granted) = depot.obtain::<Granted>() {
+            return Ok(granted.clone());
+        }
+        let Some((uid, tid)) = depot
+            .jwt_auth_data::<JwtClaims>()
+            .map(|data| (data.claims.uid.clone(), data.claims.tid.clone()))
+        else {
+            return Err(code::UNAUTHORIZED.into());
+        };
+        let granted = Self(Arc::new(
+            permissions_of(&TenantScope::new(tid), &uid).await?,
+        ));
+        depot.inject(granted.clone());
+        Ok(granted)
+    }
+
+    pub fn allows(&self, code: &str) -> bool {
+        self.0.contains(code) || self.0.contains(ALL_PERMISSIONS)
+    }
+}
+
+/// Hoop rejecting callers whose roles do not grant `code`. Must run after `auth_hoop`.
+pub fn require_permission(code: &'static str) -> RequirePermission {
+    RequirePermission { code }
+}
+
+pub struct RequirePermission {
+    code: &'static str,
+}
+
+#[async_trait]
+impl Handler for RequirePermission {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        match Granted::from_depot(depot).await {
+            Ok(granted) if granted.allows(self.code) => {
+                ctrl.call_next(req, depot, res).await;
+            }
+            Ok(_) => {
+                code::FORBIDDEN
+                    .with_msg(format!("Missing permission `{}`.", self.code))
+                    .write(req, depot, res)
+                    .await;
+                ctrl.skip_rest();
+            }
+            Err(e) => {
+                e.write(req, depot, res).await;
+                ctrl.skip_rest();
+            }
+        }
+    }
+}
+
+/// Whether the caller may grant `ALL_PERMISSIONS`: only the platform tenant of `config` and
+/// those already holding it may, so tenant admins cannot raise their roles above the catalog.
+pub async fn may_grant_all(config: &PlatformConfig, depot: &mut Depot) -> AppResult<bool> {
+    let tid = depot
+        .jwt_auth_data::<JwtClaims>()
+        .map(|data| data.claims.tid.as_str());
+    if tid.is_some() && tid == config.platform_id.as_deref() {
+        return Ok(true);
+    }
+    Ok(Granted::from_depot(depot).await?.allows(ALL_PERMISSIONS))
+}
+
+/// Hoop rejecting callers outside the platform tenant of `config`. Must run after `auth_hoop`.
+pub fn require_platform_tenant(config: &PlatformConfig) -> RequirePlatformTenant {
+    RequirePlatformTenant {
+        platform_id: config.platform_id.clone(),
+    }
+}
+
+pub struct RequirePlatformTenant {
+    platform_id: Option<String>,
+}
+
+#[async_trait]
+impl Handler for RequirePlatformTenant {
+    async fn handle(
+        &self,
+        req: &mut Request,
+        depot: &mut Depot,
+        res: &mut Response,
+        ctrl: &mut FlowCtrl,
+    ) {
+        let Some(tid) = depot
+            .jwt_auth_data::<JwtClaims>()
+            .map(|data| data.claims.tid.clone())
+        else {
+            AppError::from(code::UNAUTHORIZED)
+                .write(req, depot, res)
+                .await;
+            ctrl.skip_rest();
+            return;
+        };
+        if self.platform_id.as_deref() == Some(tid.as_str()) {
+            ctrl.call_next(req, depot, res).await;
+        } else {
+            AppError::from(code::PERMISSION_PLATFORM_ONLY)
+                .write(req, depot, res)
+                .await;
+            ctrl.skip_rest();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use daoyi_framework::cache::testing::RedisServer;
+    use salvo::test::TestClient;
+
+    use super::*;
+    use crate::hoops::testing::SignIn;
+
+    /// Hoop granting `codes` to the caller, as if loaded from their roles.
+    struct Grant(&'static [&'static str]);
+
+    #[handler]
+    impl Grant {
+        async fn handle(&self, depot: &mut Depot) {
+            let codes = self.0.iter().map(|code| code.to_string()).collect();
+            depot.inject(Granted(Arc::new(codes)));
+        }
+    }
+
+    #[handler]
+    async fn ok() -> &'static str {
+        "ok"
+    }
+
+    async fn status(router: Router) -> StatusCode {
+        TestClient::get("http://127.0.0.1/api/users")
+            .send(&Service::new(router))
+            .await
+            .status_code
+            .unwrap_or(StatusCode::OK)
+    }
+
+    fn guarded(codes: &'static [&'static str], hoop: impl Handler) -> Router {
+        Router::with_path("api/users")
+            .hoop(SignIn {
+                uid: "u1",
+                tid: "t1",
+            })
+            .hoop(Grant(codes))
+            .hoop(hoop)
+            .get(ok)
+    }
+
+    #[tokio::test]
+    async fn granted_permissions_are_allowed() {
+        let hoop = require_permission("system:user:query");
+        let router = guarded(&["system:user:create", "system:user:query"], hoop);
+        assert_eq!(status(router).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn missing_permissions_are_forbidden() {
+        let hoop = require_permission("system:user:delete");
+        let router = guarded(&["system:user:query"], hoop);
+        assert_eq!(status(router).await, StatusCode::FORBIDDEN);
+        let router = guarded(&[], require_permission("system:user:query"));
+        assert_eq!(status(router).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn wildcard_grants_every_permission() {
+        let router = guarded(&[ALL_PERMISSIONS], require_permission("system:user:delete"));
+        assert_eq!(status(router).await, StatusCode::OK);
+        // Only the full wildcard is special.
+        let router = guarded(&["system:*:*"], require_permission("system:user:delete"));
+        assert_eq!(status(router).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn anonymous_callers_are_unauthorized() {
+        let router = Router::with_path("api/users")
+            .hoop(require_permission("system:user:query"))
+            .get(ok);
+        assert_eq!(status(router).await, StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn only_the_platform_tenant_passes() {
+        let tenant = |platform_id: Option<&str>| PlatformConfig {
+            platform_id: platform_id.map(str::to_owned),
+        };
+        let router = guarded(&[], require_platform_tenant(&tenant(Some("t1"))));
+        assert_eq!(status(router).await, StatusCode::OK);
+        let router = guarded(&[], require_platform_tenant(&tenant(Some("t0"))));
+        assert_eq!(status(router).await, StatusCode::FORBIDDEN);
+        let router = guarded(&[], require_platform_tenant(&tenant(None)));
+        assert_eq!(status(router).await, StatusCode::FORBIDDEN);
+    }
+
+    /// Hoop letting the request through only if the caller may grant `ALL_PERMISSIONS` with
+    /// this platform tenant.
+    struct MayGrantAll(Option<&'static str>);
+
+    #[handler]
+    impl MayGrantAll {
+        async fn handle(&self, depot: &mut Depot, res: &mut Response, ctrl: &mut FlowCtrl) {
+            let config = PlatformConfig {
+                platform_id: self.0.map(str::to_owned),
+            };
+            if !may_grant_all(&config, depot).await.unwrap() {
+                res.status_code(StatusCode::FORBIDDEN);
+                ctrl.skip_rest();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn only_the_platform_tenant_and_wildcard_holders_grant_the_wildcard() {
+        let router = guarded(&[], MayGrantAll(Some("t1")));
+        assert_eq!(status(router).await, StatusCode::OK);
+        let router = guarded(&[ALL_PERMISSIONS], MayGrantAll(Some("t0")));
+        assert_eq!(status(router).await, StatusCode::OK);
+        let router = guarded(&["system:role:update"], MayGrantAll(Some("t0")));
+        assert_eq!(status(router).await, StatusCode::FORBIDDEN);
+        let router = guarded(&["system:role:update"], MayGrantAll(None));
+        assert_eq!(status(router).await, StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn invalidation_reaches_every_instance() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let first = PermissionCache::new(server.connect().await);
+        let second = PermissionCache::new(server.connect().await);
+        let scope = TenantScope::new("t1");
+        let codes = |codes: &[&str]| -> HashSet<String> {
+            codes.iter().map(|code| code.to_string()).collect()
+        };
+        let loaded = |found: &[&str]| {
+            let found = codes(found);
+            async move { Ok::<_, DbErr>(found) }
+        };
+
+        let cached = first
+            .get_or_load(&scope, "u1", loaded(&["a"]))
+            .await
+            .unwrap();
+        assert_eq!(cached, codes(&["a"]));
+        let cached = second
+            .get_or_load(&scope, "u1", loaded(&["b"]))
+            .await
+            .unwrap();
+        assert_eq!(cached, codes(&["a"]));
+        // Sets are cached per tenant.
+        let other = TenantScope::new("t2");
+        let cached = second
+            .get_or_load(&other, "u1", loaded(&["c"]))
+            .await
+            .unwrap();
+        assert_eq!(cached, codes(&["c"]));
+
+        second.invalidate_all().await;
+        let cached = first
+            .get_or_load(&scope, "u1", loaded(&["b"]))
+            .await
+            .unwrap();
+        assert_eq!(cached, codes(&["b"]));
+    }
+}
@@ -0,0 +1,24 @@
+//! Limits of the API routes, counted in the store chosen by `[rate_limit] store`.
+
+use std::time::Duration;
+
+use daoyi_framework::rate_limit::{self, Key, RateLimit};
+
+use crate::config;
+
+/// `/api/login` and `/api/captcha`: 20 requests a minute per client address, on top of the
+/// per-account lockout of failed logins.
+pub fn login_rate_limit() -> RateLimit {
+    RateLimit::sliding_window("login", 20, Duration::from_secs(60))
+        .by(Key::Ip)
+        .store(rate_limit::store(&config::system().rate_limit))
+}
+
+/// Authenticated API routes: bursts of 60 requests, then 10 a second per user and route.
+/// Goes below `auth_hoop` so the user is known.
+pub fn api_rate_limit() -> RateLimit {
+    RateLimit::token_bucket("api", 60, 10.0)
+        .by(Key::User)
+        .by(Key::Route)
+        .store(rate_limit::store(&config::system().rate_limit))
+}
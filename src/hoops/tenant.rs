@@ -0,0 +1,78 @@
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex, PoisonError};
+use std::time::{Duration, Instant};
+
+use salvo::http::header::HOST;
+use salvo::prelude::*;
+use sea_orm::{ColumnTrait, DbErr, EntityTrait, QueryFilter};
+
+pub use daoyi_framework::tenant::CurrentTenant;
+
+use crate::entities::prelude::Tenants;
+use crate::entities::tenants;
+use crate::{AppError, config, db};
+
+const DOMAIN_CACHE_TTL: Duration = Duration::from_secs(60);
+
+/// Tenant of each domain, and when it was loaded.
+type Domains = HashMap<String, (Instant, Option<String>)>;
+
+static DOMAIN_CACHE: LazyLock<Mutex<Domains>> = LazyLock::new(Default::default);
+
+async fn tenant_of_domain(host: &str) -> Result<Option<String>, DbErr> {
+    if let Some((loaded_at, tenant_id)) = DOMAIN_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(host)
+        && loaded_at.elapsed() < DOMAIN_CACHE_TTL
+    {
+        return Ok(tenant_id.clone());
+    }
+    let tenant_id = Tenants::find()
+        .filter(tenants::Column::Domain.eq(host))
+        .one(db::pool())
+        .await?
+        .map(|tenant| tenant.id);
+    DOMAIN_CACHE
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(host.to_owned(), (Instant::now(), tenant_id.clone()));
+    Ok(tenant_id)
+}
+
+/// Resolve the tenant from the tenant header, falling back to the host name.
+#[handler]
+pub async fn tenant_hoop(
+    req: &mut Request,
+    depot: &mut Depot,
+    res: &mut Response,
+    ctrl: &mut FlowCtrl,
+) {
+    let config = config::get();
+    let header = &config.tenant.header;
+    let tenant_id = match req.header::<String>(header.as_str()) {
+        Some(tenant_id) => Some(tenant_id),
+        None => {
+            let host = req
+                .headers()
+                .get(HOST)
+                .and_then(|host| host.to_str().ok())
+                .map(|host| host.split(':').next().unwrap_or(host).to_owned());
+            match host {
+                Some(host) => match tenant_of_domain(&host).await {
+                    Ok(tenant_id) => tenant_id,
+                    Err(e) => {
+                        AppError::from(e).write(req, depot, res).await;
+                        ctrl.skip_rest();
+                        return;
+                    }
+                },
+                None => None,
+            }
+        }
+    };
+    if let Some(tenant_id) = tenant_id {
+        depot.inject(CurrentTenant(tenant_id));
+    }
+    ctrl.call_next(req, depot, res).await;
+}
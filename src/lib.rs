@@ -0,0 +1,72 @@
+//! The system module: authentication, users, roles, permissions, tenants and audit logs, with
+//! the login page and OpenAPI docs.
+
+use daoyi_framework::Module;
+use salvo::catcher::Catcher;
+
+mod audit;
+mod cache;
+mod config;
+mod db;
+mod entities;
+mod extract;
+mod hoops;
+mod models;
+mod routers;
+mod utils;
+
+mod error;
+pub use daoyi_framework::{
+    AppResult, CommonResult, Empty, EmptyResult, JsonResult, empty_ok, json_ok,
+};
+pub use error::{AppError, ErrorCode};
+pub use hoops::cors_hoop;
+
+/// Routes of this module, mounted at the root unless given a `prefix`.
+///
+/// The pages and their scripts use relative URLs, and the API paths are recognized wherever they
+/// are mounted, so the module works under a prefix such as `admin-api/system`.
+pub fn module() -> Module {
+    Module::new("system", routers::root)
+        .on_startup(start)
+        .on_shutdown(audit::shutdown)
+}
+
+async fn start() -> anyhow::Result<()> {
+    config::validate()?;
+    audit::init();
+    Ok(())
+}
+
+/// Renders API errors in the `CommonResult` envelope and other 404s as an HTML page.
+pub fn catcher() -> Catcher {
+    Catcher::default()
+        .hoop(hoops::error_envelope)
+        .hoop(hoops::error_404)
+}
+
+#[cfg(test)]
+mod tests {
+    use salvo::prelude::*;
+    use salvo::test::{ResponseExt, TestClient};
+
+    use crate::config;
+
+    #[tokio::test]
+    async fn test_hello_world() {
+        config::testing::init(concat!(env!("CARGO_MANIFEST_DIR"), "/config-example.toml"));
+
+        let service = Service::new(crate::routers::root());
+
+        let content = TestClient::get(format!(
+            "http://{}",
+            config::get().listen_addr.replace("0.0.0.0", "127.0.0.1")
+        ))
+        .send(&service)
+        .await
+        .take_string()
+        .await
+        .unwrap();
+        assert_eq!(content, "Hello World from salvo");
+    }
+}
@@ -6,3 +6,134 @@ pub struct SafeUser {
     pub id: String,
     pub username: String,
 }
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Role {
+    pub id: String,
+    pub code: String,
+    pub name: String,
+    pub data_scope: i16,
+}
+impl From<crate::entities::roles::Model> for Role {
+    fn from(model: crate::entities::roles::Model) -> Self {
+        Self {
+            id: model.id,
+            code: model.code,
+            name: model.name,
+            data_scope: model.data_scope,
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct Permission {
+    pub id: String,
+    pub code: String,
+    pub name: String,
+}
+impl From<crate::entities::permissions::Model> for Permission {
+    fn from(model: crate::entities::permissions::Model) -> Self {
+        Self {
+            id: model.id,
+            code: model.code,
+            name: model.name,
+        }
+    }
+}
+
+/// One page of a paginated list.
+#[derive(Serialize, ToSchema, Debug)]
+pub struct PageData<T> {
+    pub data: Vec<T>,
+    pub total: u64,
+    pub current_page: u64,
+    pub page_size: u64,
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct AccessLog {
+    pub id: String,
+    pub user_id: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub status: i16,
+    pub latency_ms: i64,
+    pub ip: String,
+    pub user_agent: Option<String>,
+    /// Unix timestamp in seconds.
+    pub created_at: i64,
+}
+impl From<crate::entities::access_logs::Model> for AccessLog {
+    fn from(model: crate::entities::access_logs::Model) -> Self {
+        Self {
+            id: model.id,
+            user_id: model.user_id,
+            method: model.method,
+            path: model.path,
+            status: model.status,
+            latency_ms: model.latency_ms,
+            ip: model.ip,
+            user_agent: model.user_agent,
+            created_at: model.created_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct LoginLog {
+    pub id: String,
+    pub user_id: Option<String>,
+    pub username: String,
+    pub success: bool,
+    /// `locked`, `captcha` or `bad_credentials` for refused logins.
+    pub reason: Option<String>,
+    pub ip: String,
+    pub user_agent: Option<String>,
+    /// Unix timestamp in seconds.
+    pub created_at: i64,
+}
+impl From<crate::entities::login_logs::Model> for LoginLog {
+    fn from(model: crate::entities::login_logs::Model) -> Self {
+        Self {
+            id: model.id,
+            user_id: model.user_id,
+            username: model.username,
+            success: model.success,
+            reason: model.reason,
+            ip: model.ip,
+            user_agent: model.user_agent,
+            created_at: model.created_at.unix_timestamp(),
+        }
+    }
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct OperationLog {
+    pub id: String,
+    pub user_id: String,
+    pub module: String,
+    pub method: String,
+    pub path: String,
+    pub status: i16,
+    pub success: bool,
+    pub latency_ms: i64,
+    pub ip: String,
+    /// Unix timestamp in seconds.
+    pub created_at: i64,
+}
+impl From<crate::entities::operation_logs::Model> for OperationLog {
+    fn from(model: crate::entities::operation_logs::Model) -> Self {
+        Self {
+            id: model.id,
+            user_id: model.user_id,
+            module: model.module,
+            method: model.method,
+            path: model.path,
+            status: model.status,
+            success: model.success,
+            latency_ms: model.latency_ms,
+            ip: model.ip,
+            created_at: model.created_at.unix_timestamp(),
+        }
+    }
+}
@@ -1,28 +1,44 @@
+use std::sync::LazyLock;
+
 use askama::Template;
 use cookie::Cookie;
+use salvo::http::header::{RETRY_AFTER, USER_AGENT};
+use salvo::jwt_auth::JwtAuthDepotExt;
 use salvo::oapi::extract::*;
 use salvo::prelude::*;
-use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use sea_orm::{ColumnTrait, QueryFilter, Set};
 use serde::{Deserialize, Serialize};
+use time::OffsetDateTime;
+use ulid::Ulid;
 
+use crate::audit::{self, Record};
+use crate::db::TenantScope;
 use crate::entities::users::Model;
-use crate::entities::{prelude::Users, users};
-use crate::hoops::jwt;
-use crate::{AppResult, JsonResult, db, json_ok, utils};
+use crate::entities::{login_logs, prelude::Users, users};
+use crate::error::code;
+use crate::hoops::csrf;
+use crate::hoops::jwt::{self, JwtClaims, TokenPair};
+use crate::utils::captcha;
+use crate::utils::login_guard::{self, Check};
+use crate::{AppResult, EmptyResult, JsonResult, db, empty_ok, json_ok, utils};
 
 #[handler]
-pub async fn login_page(res: &mut Response) -> AppResult<()> {
+pub async fn login_page(req: &mut Request, res: &mut Response) -> AppResult<()> {
     #[derive(Template)]
     #[template(path = "login.html")]
-    struct LoginTemplate {}
+    struct LoginTemplate {
+        csrf_token: String,
+    }
     if let Some(cookie) = res.cookies().get("jwt_token") {
         let token = cookie.value().to_string();
-        if jwt::decode_token(&token) {
-            res.render(Redirect::other("/users"));
+        if jwt::decode_token(&token).await {
+            res.render(Redirect::other("users"));
             return Ok(());
         }
     }
-    let hello_tmpl = LoginTemplate {};
+    let hello_tmpl = LoginTemplate {
+        csrf_token: csrf::token(req, res),
+    };
     res.render(Text::Html(hello_tmpl.render().unwrap()));
     Ok(())
 }
@@ -31,6 +47,9 @@ pub async fn login_page(res: &mut Response) -> AppResult<()> {
 pub struct LoginInData {
     pub username: String,
     pub password: String,
+    /// Required once the account or client address has failed too many times.
+    pub captcha_id: Option<String>,
+    pub captcha_code: Option<String>,
 }
 #[derive(Serialize, ToSchema, Default, Debug)]
 pub struct LoginOutData {
@@ -38,45 +57,198 @@ pub struct LoginOutData {
     pub username: String,
     pub token: String,
     pub exp: i64,
+    pub refresh_token: String,
+    pub refresh_exp: i64,
+}
+
+/// Verified when the user does not exist, so both failures take as long.
+static DUMMY_HASH: LazyLock<String> = LazyLock::new(|| {
+    utils::hash_password(&utils::random_string(16)).expect("dummy password should hash")
+});
+
+/// Queue a login log entry; `failure` is the reason a login was refused.
+fn login_log(
+    req: &Request,
+    scope: &TenantScope,
+    username: &str,
+    user_id: Option<String>,
+    failure: Option<&str>,
+) {
+    audit::record(Record::Login(login_logs::ActiveModel {
+        id: Set(Ulid::new().to_string()),
+        tenant_id: Set(Some(scope.tenant_id().to_owned())),
+        user_id: Set(user_id),
+        username: Set(username.to_owned()),
+        success: Set(failure.is_none()),
+        reason: Set(failure.map(str::to_owned)),
+        ip: Set(utils::client_ip(req)),
+        user_agent: Set(req.header::<String>(USER_AGENT)),
+        created_at: Set(OffsetDateTime::now_utc()),
+    }));
 }
+
 #[endpoint(tags("auth"))]
 pub async fn post_login(
     idata: JsonBody<LoginInData>,
+    req: &mut Request,
+    depot: &mut Depot,
     res: &mut Response,
 ) -> JsonResult<LoginOutData> {
     let idata = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let account_key = format!("account:{}:{}", scope.tenant_id(), idata.username);
+    let keys = [account_key.clone(), format!("ip:{}", utils::client_ip(req))];
+    let guard = login_guard::guard();
+    match guard.check(&keys).await {
+        Check::Locked(remaining) => {
+            let secs = remaining.as_secs() + 1;
+            login_log(req, &scope, &idata.username, None, Some("locked"));
+            res.add_header(RETRY_AFTER, secs, true)?;
+            return Err(code::AUTH_LOGIN_LOCKED.with_msg(format!(
+                "Too many failed login attempts, try again in {secs} seconds."
+            )));
+        }
+        Check::Allowed {
+            captcha_required: true,
+        } => {
+            let passed = match (&idata.captcha_id, &idata.captcha_code) {
+                (Some(id), Some(code)) => captcha::verify(id, code).await,
+                _ => false,
+            };
+            if !passed {
+                login_log(req, &scope, &idata.username, None, Some("captcha"));
+                return Err(code::AUTH_LOGIN_CAPTCHA_INVALID.into());
+            }
+        }
+        Check::Allowed {
+            captcha_required: false,
+        } => {}
+    }
+
     let conn = db::pool();
-    let Some(Model {
+    let user = scope
+        .find::<Users>()
+        .filter(users::Column::Username.eq(idata.username.as_str()))
+        .one(conn)
+        .await?;
+    let verified = match &user {
+        Some(user) => utils::verify_password(&idata.password, &user.password).is_ok(),
+        None => {
+            let _ = utils::verify_password(&idata.password, &DUMMY_HASH);
+            false
+        }
+    };
+    let Model {
         id,
         username,
-        password,
-    }) = Users::find()
-        .filter(users::Column::Username.eq(idata.username))
-        .one(conn)
-        .await?
-    else {
-        return Err(StatusError::unauthorized()
-            .brief("User does not exist.")
-            .into());
+        tenant_id,
+        ..
+    } = match user {
+        Some(user) if verified => user,
+        user => {
+            guard.record_failure(&keys).await;
+            let user_id = user.map(|user| user.id);
+            login_log(
+                req,
+                &scope,
+                &idata.username,
+                user_id,
+                Some("bad_credentials"),
+            );
+            return Err(code::AUTH_LOGIN_BAD_CREDENTIALS.into());
+        }
     };
+    guard.record_success(&account_key).await;
+    login_log(req, &scope, &username, Some(id.clone()), None);
 
-    if utils::verify_password(&idata.password, &password).is_err() {
-        return Err(StatusError::unauthorized()
-            .brief("Account not exist or password is incorrect.")
-            .into());
-    }
-
-    let (token, exp) = jwt::get_token(&id)?;
+    let TokenPair {
+        access_token,
+        access_exp,
+        refresh_token,
+        refresh_exp,
+    } = jwt::issue_tokens(&id, &tenant_id).await?;
     let odata = LoginOutData {
         id,
         username,
-        token,
-        exp,
+        token: access_token,
+        exp: access_exp,
+        refresh_token,
+        refresh_exp,
     };
-    let cookie = Cookie::build(("jwt_token", odata.token.clone()))
+    res.add_cookie(token_cookie(odata.token.clone()));
+    csrf::renew(res);
+    json_ok(odata)
+}
+
+#[derive(Serialize, ToSchema, Debug)]
+pub struct CaptchaOutData {
+    pub captcha_id: String,
+    /// `data:` URL of the challenge image.
+    pub image: String,
+}
+/// New single-use captcha for `/api/login`.
+#[endpoint(tags("auth"))]
+pub async fn get_captcha() -> JsonResult<CaptchaOutData> {
+    let captcha = captcha::generate().await?;
+    json_ok(CaptchaOutData {
+        captcha_id: captcha.id,
+        image: captcha.image,
+    })
+}
+
+fn token_cookie(token: String) -> Cookie<'static> {
+    Cookie::build(("jwt_token", token))
         .path("/")
         .http_only(true)
-        .build();
-    res.add_cookie(cookie);
+        .build()
+}
+
+#[derive(Deserialize, ToSchema, Default, Debug)]
+pub struct RefreshInData {
+    pub refresh_token: String,
+}
+#[derive(Serialize, ToSchema, Default, Debug)]
+pub struct RefreshOutData {
+    pub token: String,
+    pub exp: i64,
+    pub refresh_token: String,
+    pub refresh_exp: i64,
+}
+/// Rotate a refresh token. Each refresh token can be used once; presenting a used one
+/// revokes every token of its login.
+#[endpoint(tags("auth"))]
+pub async fn post_refresh(
+    idata: JsonBody<RefreshInData>,
+    res: &mut Response,
+) -> JsonResult<RefreshOutData> {
+    let Some(pair) = jwt::refresh_tokens(&idata.into_inner().refresh_token).await? else {
+        return Err(code::AUTH_REFRESH_TOKEN_INVALID.into());
+    };
+    let odata = RefreshOutData {
+        token: pair.access_token,
+        exp: pair.access_exp,
+        refresh_token: pair.refresh_token,
+        refresh_exp: pair.refresh_exp,
+    };
+    res.add_cookie(token_cookie(odata.token.clone()));
     json_ok(odata)
 }
+
+/// Public keys for verifying tokens issued by this server.
+#[handler]
+pub async fn jwks(res: &mut Response) {
+    res.render(Json(jwt::key_ring().jwks()));
+}
+
+/// Revoke the current access token and the refresh tokens of the same login.
+#[endpoint(tags("auth"))]
+pub async fn post_logout(depot: &mut Depot, res: &mut Response) -> EmptyResult {
+    if let Some(claims) = depot
+        .jwt_auth_data::<JwtClaims>()
+        .map(|data| data.claims.clone())
+    {
+        jwt::revoke(&claims).await?;
+    }
+    res.add_cookie(Cookie::build(("jwt_token", "")).path("/").removal().build());
+    empty_ok()
+}
@@ -1,11 +1,16 @@
+use daoyi_framework::metrics;
 use rust_embed::RustEmbed;
 use salvo::prelude::*;
 use salvo::serve_static::{EmbeddedFileExt, static_embed};
 
+mod audit;
 mod auth;
 mod demo;
+mod permission;
+mod role;
 mod user;
 
+use crate::hoops::{require_permission, require_platform_tenant};
 use crate::{config, hoops};
 
 #[derive(RustEmbed)]
@@ -18,21 +23,185 @@ pub fn root() -> Router {
         .into_handler();
     let router = Router::new()
         .hoop(Logger::new())
+        .hoop(metrics::hoop)
+        .hoop(hoops::access_log_hoop)
         .get(demo::hello)
         .push(Router::with_path("login").get(auth::login_page))
+        .push(Router::with_path(".well-known/jwks.json").get(auth::jwks))
         .push(Router::with_path("users").get(user::list_page))
         .push(
             Router::with_path("api")
-                .push(Router::with_path("login").post(auth::post_login))
+                .hoop(hoops::tenant_hoop)
+                .hoop(hoops::csrf_hoop)
+                .hoop(hoops::operation_log_hoop)
+                .push(
+                    Router::with_path("login")
+                        .hoop(hoops::login_rate_limit())
+                        .post(auth::post_login),
+                )
+                .push(
+                    Router::with_path("captcha")
+                        .hoop(hoops::login_rate_limit())
+                        .get(auth::get_captcha),
+                )
+                .push(Router::with_path("refresh").post(auth::post_refresh))
+                .push(
+                    Router::with_path("logout")
+                        .hoop(hoops::auth_hoop(&config::get().jwt))
+                        .post(auth::post_logout),
+                )
                 .push(
                     Router::with_path("users")
                         .hoop(hoops::auth_hoop(&config::get().jwt))
-                        .get(user::list_users)
-                        .post(user::create_user)
+                        .hoop(hoops::api_rate_limit())
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:user:query"))
+                                .get(user::list_users),
+                        )
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:user:create"))
+                                .post(user::create_user),
+                        )
                         .push(
                             Router::with_path("{user_id}")
-                                .put(user::update_user)
-                                .delete(user::delete_user),
+                                .push(
+                                    Router::new()
+                                        .hoop(require_permission("system:user:update"))
+                                        .put(user::update_user),
+                                )
+                                .push(
+                                    Router::new()
+                                        .hoop(require_permission("system:user:delete"))
+                                        .delete(user::delete_user),
+                                )
+                                .push(
+                                    Router::with_path("roles")
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission("system:user:query"))
+                                                .get(user::list_user_roles),
+                                        )
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission("system:user:assign-role"))
+                                                .put(user::assign_user_roles),
+                                        ),
+                                ),
+                        ),
+                )
+                .push(
+                    Router::with_path("roles")
+                        .hoop(hoops::auth_hoop(&config::get().jwt))
+                        .hoop(hoops::api_rate_limit())
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:role:query"))
+                                .get(role::list_roles),
+                        )
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:role:create"))
+                                .post(role::create_role),
+                        )
+                        .push(
+                            Router::with_path("{role_id}")
+                                .push(
+                                    Router::new()
+                                        .hoop(require_permission("system:role:update"))
+                                        .put(role::update_role),
+                                )
+                                .push(
+                                    Router::new()
+                                        .hoop(require_permission("system:role:delete"))
+                                        .delete(role::delete_role),
+                                )
+                                .push(
+                                    Router::with_path("permissions")
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission("system:role:query"))
+                                                .get(role::list_role_permissions),
+                                        )
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission(
+                                                    "system:role:assign-permission",
+                                                ))
+                                                .put(role::assign_role_permissions),
+                                        ),
+                                )
+                                .push(
+                                    Router::with_path("depts")
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission("system:role:query"))
+                                                .get(role::list_role_depts),
+                                        )
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission("system:role:assign-dept"))
+                                                .put(role::assign_role_depts),
+                                        ),
+                                ),
+                        ),
+                )
+                .push(
+                    Router::with_path("permissions")
+                        .hoop(hoops::auth_hoop(&config::get().jwt))
+                        .hoop(hoops::api_rate_limit())
+                        .push(
+                            Router::new()
+                                .hoop(require_permission("system:permission:query"))
+                                .get(permission::list_permissions),
+                        )
+                        .push(
+                            // Every tenant shares the catalog, so only the platform edits it.
+                            Router::new()
+                                .hoop(require_platform_tenant(&config::system().tenant))
+                                .push(
+                                    Router::new()
+                                        .hoop(require_permission("system:permission:create"))
+                                        .post(permission::create_permission),
+                                )
+                                .push(
+                                    Router::with_path("{permission_id}")
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission(
+                                                    "system:permission:update",
+                                                ))
+                                                .put(permission::update_permission),
+                                        )
+                                        .push(
+                                            Router::new()
+                                                .hoop(require_permission(
+                                                    "system:permission:delete",
+                                                ))
+                                                .delete(permission::delete_permission),
+                                        ),
+                                ),
+                        ),
+                )
+                .push(
+                    Router::with_path("audit")
+                        .hoop(hoops::auth_hoop(&config::get().jwt))
+                        .hoop(hoops::api_rate_limit())
+                        .push(
+                            Router::with_path("access-logs")
+                                .hoop(require_permission("system:access-log:query"))
+                                .get(audit::list_access_logs),
+                        )
+                        .push(
+                            Router::with_path("login-logs")
+                                .hoop(require_permission("system:login-log:query"))
+                                .get(audit::list_login_logs),
+                        )
+                        .push(
+                            Router::with_path("operation-logs")
+                                .hoop(require_permission("system:operation-log:query"))
+                                .get(audit::list_operation_logs),
                         ),
                 ),
         )
@@ -41,5 +210,5 @@ pub fn root() -> Router {
     let doc = OpenApi::new("salvo web api", "0.0.1").merge_router(&router);
     router
         .unshift(doc.into_router("/api-doc/openapi.json"))
-        .unshift(Scalar::new("/api-doc/openapi.json").into_router("scalar"))
+        .unshift(Scalar::new("api-doc/openapi.json").into_router("scalar"))
 }
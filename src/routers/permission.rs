@@ -0,0 +1,81 @@
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use sea_orm::{ActiveModelTrait, EntityTrait, QueryOrder, Set};
+use serde::Deserialize;
+use ulid::Ulid;
+use validator::Validate;
+
+use crate::entities::permissions;
+use crate::entities::prelude::Permissions;
+use crate::error::code;
+use crate::extract::ValidJson;
+use crate::hoops::permission;
+use crate::models::Permission;
+use crate::{EmptyResult, JsonResult, db, empty_ok, json_ok};
+
+#[endpoint(tags("permissions"))]
+pub async fn list_permissions() -> JsonResult<Vec<Permission>> {
+    let conn = db::pool();
+    let permissions = Permissions::find()
+        .order_by_asc(permissions::Column::Code)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(Permission::from)
+        .collect();
+    json_ok(permissions)
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct PermissionInData {
+    /// Permission code such as `system:user:delete`.
+    #[validate(length(min = 1, message = "code must not be empty"))]
+    #[salvo(schema(min_length = 1))]
+    pub code: String,
+    #[validate(length(min = 1, message = "name must not be empty"))]
+    #[salvo(schema(min_length = 1))]
+    pub name: String,
+}
+#[endpoint(tags("permissions"))]
+pub async fn create_permission(idata: ValidJson<PermissionInData>) -> JsonResult<Permission> {
+    let PermissionInData { code, name } = idata.into_inner();
+    let conn = db::pool();
+    let model = permissions::ActiveModel {
+        id: Set(Ulid::new().to_string()),
+        code: Set(code),
+        name: Set(name),
+    };
+    let permission = model.insert(conn).await?;
+    json_ok(permission.into())
+}
+
+#[endpoint(tags("permissions"), parameters(("permission_id", description = "permission id")))]
+pub async fn update_permission(
+    permission_id: PathParam<String>,
+    idata: ValidJson<PermissionInData>,
+) -> JsonResult<Permission> {
+    let PermissionInData { code, name } = idata.into_inner();
+    let conn = db::pool();
+    let Some(model) = Permissions::find_by_id(permission_id.into_inner())
+        .one(conn)
+        .await?
+    else {
+        return Err(code::PERMISSION_NOT_EXISTS.into());
+    };
+    let mut model: permissions::ActiveModel = model.into();
+    model.code = Set(code);
+    model.name = Set(name);
+    let model = model.update(conn).await?;
+    permission::invalidate_all().await;
+    json_ok(model.into())
+}
+
+#[endpoint(tags("permissions"))]
+pub async fn delete_permission(permission_id: PathParam<String>) -> EmptyResult {
+    let conn = db::pool();
+    Permissions::delete_by_id(permission_id.into_inner())
+        .exec(conn)
+        .await?;
+    permission::invalidate_all().await;
+    empty_ok()
+}
@@ -0,0 +1,337 @@
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use sea_orm::{
+    ActiveModelTrait, ColumnTrait, ConnectionTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set, TransactionTrait,
+};
+use serde::Deserialize;
+use ulid::Ulid;
+use validator::Validate;
+
+use crate::db::TenantScope;
+use crate::db::data_scope::DataScopeKind;
+use crate::entities::prelude::{Departments, Permissions, RoleDepts, RolePermissions, Roles};
+use crate::entities::{departments, permissions, role_depts, role_permissions, roles};
+use crate::error::code;
+use crate::extract::ValidJson;
+use crate::hoops::permission;
+use crate::models::{Permission, Role};
+use crate::{AppResult, EmptyResult, JsonResult, config, db, empty_ok, json_ok};
+
+#[endpoint(tags("roles"))]
+pub async fn list_roles(depot: &mut Depot) -> JsonResult<Vec<Role>> {
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    let roles = scope
+        .find::<Roles>()
+        .order_by_asc(roles::Column::Code)
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(Role::from)
+        .collect();
+    json_ok(roles)
+}
+
+#[derive(Deserialize, Debug, Validate, ToSchema)]
+pub struct RoleInData {
+    #[validate(length(min = 1, message = "code must not be empty"))]
+    #[salvo(schema(min_length = 1))]
+    pub code: String,
+    #[validate(length(min = 1, message = "name must not be empty"))]
+    #[salvo(schema(min_length = 1))]
+    pub name: String,
+    /// 1 all, 2 custom departments, 3 own department, 4 own department and below, 5 self only.
+    #[serde(default = "default_data_scope")]
+    #[validate(range(min = 1, max = 5, message = "data_scope must be between 1 and 5"))]
+    #[salvo(schema(minimum = 1, maximum = 5))]
+    pub data_scope: i16,
+}
+
+fn default_data_scope() -> i16 {
+    DataScopeKind::All as i16
+}
+#[endpoint(tags("roles"))]
+pub async fn create_role(idata: ValidJson<RoleInData>, depot: &mut Depot) -> JsonResult<Role> {
+    let RoleInData {
+        code,
+        name,
+        data_scope,
+    } = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    let model = roles::ActiveModel {
+        id: Set(Ulid::new().to_string()),
+        code: Set(code),
+        name: Set(name),
+        tenant_id: Set(scope.tenant_id().to_owned()),
+        data_scope: Set(data_scope),
+    };
+    let role = model.insert(conn).await?;
+    json_ok(role.into())
+}
+
+#[endpoint(tags("roles"), parameters(("role_id", description = "role id")))]
+pub async fn update_role(
+    role_id: PathParam<String>,
+    idata: ValidJson<RoleInData>,
+    depot: &mut Depot,
+) -> JsonResult<Role> {
+    let RoleInData {
+        code,
+        name,
+        data_scope,
+    } = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    let Some(role) = scope
+        .find_by_id::<Roles, _>(role_id.into_inner())
+        .one(conn)
+        .await?
+    else {
+        return Err(code::ROLE_NOT_EXISTS.into());
+    };
+    let mut role: roles::ActiveModel = role.into();
+    role.code = Set(code);
+    role.name = Set(name);
+    role.data_scope = Set(data_scope);
+    let role = role.update(conn).await?;
+    permission::invalidate_all().await;
+    json_ok(role.into())
+}
+
+#[endpoint(tags("roles"))]
+pub async fn delete_role(role_id: PathParam<String>, depot: &mut Depot) -> EmptyResult {
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    scope
+        .delete_many::<Roles>()
+        .filter(roles::Column::Id.eq(role_id.into_inner()))
+        .exec(conn)
+        .await?;
+    permission::invalidate_all().await;
+    empty_ok()
+}
+
+#[endpoint(tags("roles"), parameters(("role_id", description = "role id")))]
+pub async fn list_role_permissions(
+    role_id: PathParam<String>,
+    depot: &mut Depot,
+) -> JsonResult<Vec<Permission>> {
+    let role_id = role_id.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    if scope
+        .find_by_id::<Roles, _>(role_id.clone())
+        .one(conn)
+        .await?
+        .is_none()
+    {
+        return Err(code::ROLE_NOT_EXISTS.into());
+    }
+    let permissions = Permissions::find()
+        .inner_join(RolePermissions)
+        .filter(role_permissions::Column::RoleId.eq(role_id))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(Permission::from)
+        .collect();
+    json_ok(permissions)
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AssignPermissionsInData {
+    pub permission_ids: Vec<String>,
+}
+/// The permissions of `permission_ids`, once each, or an error if some do not exist.
+async fn known_permissions<C: ConnectionTrait>(
+    conn: &C,
+    mut permission_ids: Vec<String>,
+) -> AppResult<Vec<permissions::Model>> {
+    permission_ids.sort();
+    permission_ids.dedup();
+    let known = Permissions::find()
+        .filter(permissions::Column::Id.is_in(permission_ids.clone()))
+        .all(conn)
+        .await?;
+    if known.len() != permission_ids.len() {
+        return Err(code::PERMISSION_SOME_NOT_EXISTS.into());
+    }
+    Ok(known)
+}
+
+/// Replace the permissions granted to a role.
+#[endpoint(tags("roles"), parameters(("role_id", description = "role id")))]
+pub async fn assign_role_permissions(
+    role_id: PathParam<String>,
+    idata: JsonBody<AssignPermissionsInData>,
+    depot: &mut Depot,
+) -> EmptyResult {
+    let role_id = role_id.into_inner();
+    let AssignPermissionsInData { permission_ids } = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    if scope
+        .find_by_id::<Roles, _>(role_id.clone())
+        .one(conn)
+        .await?
+        .is_none()
+    {
+        return Err(code::ROLE_NOT_EXISTS.into());
+    }
+    let known_permissions = known_permissions(conn, permission_ids).await?;
+    if known_permissions
+        .iter()
+        .any(|known| known.code == permission::ALL_PERMISSIONS)
+        && !permission::may_grant_all(&config::system().tenant, depot).await?
+    {
+        return Err(code::PERMISSION_ALL_NOT_GRANTABLE.into());
+    }
+    let permission_ids = known_permissions
+        .into_iter()
+        .map(|permission| permission.id)
+        .collect::<Vec<_>>();
+
+    let txn = conn.begin().await?;
+    RolePermissions::delete_many()
+        .filter(role_permissions::Column::RoleId.eq(role_id.clone()))
+        .exec(&txn)
+        .await?;
+    if !permission_ids.is_empty() {
+        RolePermissions::insert_many(permission_ids.into_iter().map(|permission_id| {
+            role_permissions::ActiveModel {
+                role_id: Set(role_id.clone()),
+                permission_id: Set(permission_id),
+            }
+        }))
+        .exec(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+    permission::invalidate_all().await;
+    empty_ok()
+}
+
+#[endpoint(tags("roles"), parameters(("role_id", description = "role id")))]
+pub async fn list_role_depts(
+    role_id: PathParam<String>,
+    depot: &mut Depot,
+) -> JsonResult<Vec<String>> {
+    let role_id = role_id.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    if scope
+        .find_by_id::<Roles, _>(role_id.clone())
+        .one(conn)
+        .await?
+        .is_none()
+    {
+        return Err(code::ROLE_NOT_EXISTS.into());
+    }
+    let dept_ids = RoleDepts::find()
+        .filter(role_depts::Column::RoleId.eq(role_id))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(|role_dept| role_dept.dept_id)
+        .collect();
+    json_ok(dept_ids)
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AssignDeptsInData {
+    pub dept_ids: Vec<String>,
+}
+/// Replace the departments visible to a role with a custom data scope.
+#[endpoint(tags("roles"), parameters(("role_id", description = "role id")))]
+pub async fn assign_role_depts(
+    role_id: PathParam<String>,
+    idata: JsonBody<AssignDeptsInData>,
+    depot: &mut Depot,
+) -> EmptyResult {
+    let role_id = role_id.into_inner();
+    let AssignDeptsInData { dept_ids } = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    if scope
+        .find_by_id::<Roles, _>(role_id.clone())
+        .one(conn)
+        .await?
+        .is_none()
+    {
+        return Err(code::ROLE_NOT_EXISTS.into());
+    }
+    let known_depts = scope
+        .find::<Departments>()
+        .filter(departments::Column::Id.is_in(dept_ids.clone()))
+        .count(conn)
+        .await?;
+    if known_depts != dept_ids.len() as u64 {
+        return Err(code::DEPT_SOME_NOT_EXISTS.into());
+    }
+
+    let txn = conn.begin().await?;
+    RoleDepts::delete_many()
+        .filter(role_depts::Column::RoleId.eq(role_id.clone()))
+        .exec(&txn)
+        .await?;
+    if !dept_ids.is_empty() {
+        RoleDepts::insert_many(dept_ids.into_iter().map(|dept_id| role_depts::ActiveModel {
+            role_id: Set(role_id.clone()),
+            dept_id: Set(dept_id),
+        }))
+        .exec(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+    empty_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use sea_orm::{ConnectOptions, Database};
+
+    use super::*;
+    use crate::AppError;
+
+    /// Runs against the Postgres in `DATABASE_URL`, like `data_scope::filters_rows_on_postgres`.
+    #[tokio::test]
+    #[ignore = "requires a local Postgres in DATABASE_URL"]
+    async fn only_existing_permissions_are_granted_once() {
+        let url = std::env::var("DATABASE_URL").expect("DATABASE_URL should be set");
+        let mut opt = ConnectOptions::new(url);
+        opt.max_connections(1).min_connections(1);
+        let conn = Database::connect(opt).await.unwrap();
+        conn.execute_unprepared(
+            "CREATE TEMPORARY TABLE permissions (
+                id text PRIMARY KEY,
+                code text NOT NULL,
+                name text NOT NULL
+            );
+            INSERT INTO permissions VALUES
+                ('p1', 'system:user:query', 'Query users'),
+                ('p2', '*:*:*', 'Everything');",
+        )
+        .await
+        .unwrap();
+
+        let ids = |ids: &[&str]| ids.iter().map(|id| id.to_string()).collect::<Vec<_>>();
+        let known = known_permissions(&conn, ids(&["p2", "p1", "p2"]))
+            .await
+            .unwrap();
+        let known = known
+            .iter()
+            .map(|known| known.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(known.len(), 2);
+        assert!(known.contains(&"p1") && known.contains(&"p2"));
+
+        match known_permissions(&conn, ids(&["p1", "p9"])).await {
+            Err(AppError::Code(code, _)) => {
+                assert_eq!(code.code, code::PERMISSION_SOME_NOT_EXISTS.code)
+            }
+            other => panic!("unknown ids should be refused, got {other:?}"),
+        }
+    }
+}
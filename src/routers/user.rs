@@ -4,18 +4,26 @@ use salvo::oapi::extract::*;
 use salvo::prelude::*;
 use sea_orm::{
     ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QuerySelect, Set,
+    TransactionTrait,
 };
 use serde::{Deserialize, Serialize};
 use ulid::Ulid;
 use validator::Validate;
 
-use crate::entities::{prelude::Users, users};
-use crate::models::SafeUser;
-use crate::{AppResult, EmptyResult, JsonResult, db, empty_ok, json_ok, utils};
+use crate::db::{DataScope, DataScopeFilter, TenantScope};
+use crate::entities::prelude::{Roles, UserRoles, Users};
+use crate::entities::{roles, user_roles, users};
+use crate::error::code;
+use crate::extract::ValidJson;
+use crate::hoops::{csrf, permission};
+use crate::models::{Role, SafeUser};
+use crate::{AppResult, EmptyResult, JsonResult, cache, db, empty_ok, json_ok, utils};
 
 #[derive(Template)]
 #[template(path = "user_list_page.html")]
-pub struct UserListPageTemplate {}
+pub struct UserListPageTemplate {
+    csrf_token: String,
+}
 
 #[derive(Template)]
 #[template(path = "user_list_frag.html")]
@@ -26,8 +34,8 @@ pub async fn list_page(req: &mut Request, res: &mut Response) -> AppResult<()> {
     let is_fragment = req.headers().get("X-Fragment-Header");
     if let Some(cookie) = res.cookies().get("jwt_token") {
         let token = cookie.value().to_string();
-        if !jwt::decode_token(&token) {
-            res.render(Redirect::other("/login"));
+        if !jwt::decode_token(&token).await {
+            res.render(Redirect::other("login"));
         }
     }
     match is_fragment {
@@ -36,7 +44,9 @@ pub async fn list_page(req: &mut Request, res: &mut Response) -> AppResult<()> {
             res.render(Text::Html(hello_tmpl.render().unwrap()));
         }
         None => {
-            let hello_tmpl = UserListPageTemplate {};
+            let hello_tmpl = UserListPageTemplate {
+                csrf_token: csrf::token(req, res),
+            };
             res.render(Text::Html(hello_tmpl.render().unwrap()));
         }
     }
@@ -46,13 +56,19 @@ pub async fn list_page(req: &mut Request, res: &mut Response) -> AppResult<()> {
 #[derive(Deserialize, Debug, Validate, ToSchema, Default)]
 pub struct CreateInData {
     #[validate(length(min = 5, message = "username length must be greater than 5"))]
+    #[salvo(schema(min_length = 5))]
     pub username: String,
     #[validate(length(min = 6, message = "password length must be greater than 5"))]
+    #[salvo(schema(min_length = 6))]
     pub password: String,
 }
 #[endpoint(tags("users"))]
-pub async fn create_user(idata: JsonBody<CreateInData>) -> JsonResult<SafeUser> {
+pub async fn create_user(
+    idata: ValidJson<CreateInData>,
+    depot: &mut Depot,
+) -> JsonResult<SafeUser> {
     let CreateInData { username, password } = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
     let id = Ulid::new().to_string();
     let password = utils::hash_password(&password)?;
     let conn = db::pool();
@@ -60,8 +76,10 @@ pub async fn create_user(idata: JsonBody<CreateInData>) -> JsonResult<SafeUser>
         id: Set(id.clone()),
         username: Set(username.clone()),
         password: Set(password.clone()),
+        tenant_id: Set(scope.tenant_id().to_owned()),
+        dept_id: Set(None),
     };
-    Users::insert(model).exec(conn).await?;
+    model.insert(conn).await?;
 
     json_ok(SafeUser { id, username })
 }
@@ -69,27 +87,37 @@ pub async fn create_user(idata: JsonBody<CreateInData>) -> JsonResult<SafeUser>
 #[derive(Deserialize, Debug, Validate, ToSchema)]
 struct UpdateInData {
     #[validate(length(min = 5, message = "username length must be greater than 5"))]
+    #[salvo(schema(min_length = 5))]
     username: String,
     #[validate(length(min = 6, message = "password length must be greater than 5"))]
+    #[salvo(schema(min_length = 6))]
     password: String,
 }
 #[endpoint(tags("users"), parameters(("user_id", description = "user id")))]
 pub async fn update_user(
     user_id: PathParam<String>,
-    idata: JsonBody<UpdateInData>,
+    idata: ValidJson<UpdateInData>,
+    depot: &mut Depot,
 ) -> JsonResult<SafeUser> {
     let user_id = user_id.into_inner();
     let UpdateInData { username, password } = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
     let conn = db::pool();
 
-    let Some(user) = Users::find_by_id(user_id).one(conn).await? else {
-        return Err(anyhow::anyhow!("User does not exist.").into());
+    // From the database: the cached user has no password to carry into the update.
+    let Some(user) = scope
+        .find_by_id::<Users, _>(user_id.clone())
+        .one(conn)
+        .await?
+    else {
+        return Err(code::USER_NOT_EXISTS.into());
     };
     let mut user: users::ActiveModel = user.into();
     user.username = Set(username.to_owned());
     user.password = Set(utils::hash_password(&password)?);
 
     let user: users::Model = user.update(conn).await?;
+    cache::user::evict(&scope, &user_id).await;
     json_ok(SafeUser {
         id: user.id,
         username: user.username,
@@ -97,10 +125,16 @@ pub async fn update_user(
 }
 
 #[endpoint(tags("users"))]
-pub async fn delete_user(user_id: PathParam<String>) -> EmptyResult {
+pub async fn delete_user(user_id: PathParam<String>, depot: &mut Depot) -> EmptyResult {
     let user_id = user_id.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
     let conn = db::pool();
-    Users::delete_by_id(user_id).exec(conn).await?;
+    scope
+        .delete_many::<Users>()
+        .filter(users::Column::Id.eq(user_id.clone()))
+        .exec(conn)
+        .await?;
+    cache::user::evict(&scope, &user_id).await;
     empty_ok()
 }
 
@@ -130,11 +164,13 @@ pub struct UserListResponse {
 }
 
 #[endpoint(tags("users"))]
-pub async fn list_users(query: &mut Request) -> JsonResult<UserListResponse> {
+pub async fn list_users(query: &mut Request, depot: &mut Depot) -> JsonResult<UserListResponse> {
     let query: UserListQuery = query.extract().await?;
+    let scope = TenantScope::from_depot(depot)?;
+    let data_scope = DataScope::from_depot(depot).await?;
     let conn = db::pool();
 
-    let mut select = Users::find();
+    let mut select = scope.find::<Users>().data_scope(&data_scope);
 
     // Apply username filter if provided
     if let Some(username) = query.username.as_ref() {
@@ -164,3 +200,67 @@ pub async fn list_users(query: &mut Request) -> JsonResult<UserListResponse> {
         page_size: query.page_size,
     })
 }
+
+#[endpoint(tags("users"), parameters(("user_id", description = "user id")))]
+pub async fn list_user_roles(
+    user_id: PathParam<String>,
+    depot: &mut Depot,
+) -> JsonResult<Vec<Role>> {
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    let roles = scope
+        .find::<Roles>()
+        .inner_join(UserRoles)
+        .filter(user_roles::Column::UserId.eq(user_id.into_inner()))
+        .all(conn)
+        .await?
+        .into_iter()
+        .map(Role::from)
+        .collect();
+    json_ok(roles)
+}
+
+#[derive(Deserialize, Debug, ToSchema)]
+pub struct AssignRolesInData {
+    pub role_ids: Vec<String>,
+}
+/// Replace the roles granted to a user.
+#[endpoint(tags("users"), parameters(("user_id", description = "user id")))]
+pub async fn assign_user_roles(
+    user_id: PathParam<String>,
+    idata: JsonBody<AssignRolesInData>,
+    depot: &mut Depot,
+) -> EmptyResult {
+    let user_id = user_id.into_inner();
+    let AssignRolesInData { role_ids } = idata.into_inner();
+    let scope = TenantScope::from_depot(depot)?;
+    let conn = db::pool();
+    if cache::user::find_by_id(&scope, &user_id).await?.is_none() {
+        return Err(code::USER_NOT_EXISTS.into());
+    }
+    let known_roles = scope
+        .find::<Roles>()
+        .filter(roles::Column::Id.is_in(role_ids.clone()))
+        .count(conn)
+        .await?;
+    if known_roles != role_ids.len() as u64 {
+        return Err(code::ROLE_SOME_NOT_EXISTS.into());
+    }
+
+    let txn = conn.begin().await?;
+    UserRoles::delete_many()
+        .filter(user_roles::Column::UserId.eq(user_id.clone()))
+        .exec(&txn)
+        .await?;
+    if !role_ids.is_empty() {
+        UserRoles::insert_many(role_ids.into_iter().map(|role_id| user_roles::ActiveModel {
+            user_id: Set(user_id.clone()),
+            role_id: Set(role_id),
+        }))
+        .exec(&txn)
+        .await?;
+    }
+    txn.commit().await?;
+    permission::invalidate_all().await;
+    empty_ok()
+}
@@ -0,0 +1,217 @@
+use std::sync::{Arc, LazyLock};
+use std::time::Duration;
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use redis::AsyncCommands;
+use salvo::async_trait;
+use ulid::Ulid;
+
+use crate::cache::{self, Redis};
+use crate::config;
+
+mod render;
+
+use render::ALPHABET;
+
+const LENGTH: usize = 4;
+
+pub struct Captcha {
+    pub id: String,
+    /// `data:` URL of a PNG image.
+    pub image: String,
+}
+
+/// Where answers wait for their login. Each is handed out at most once.
+#[async_trait]
+pub trait AnswerStore: Send + Sync {
+    async fn put(&self, id: &str, answer: &str, ttl: Duration) -> anyhow::Result<()>;
+    /// Remove and return the answer of `id`, unless it expired.
+    async fn take(&self, id: &str) -> anyhow::Result<Option<String>>;
+}
+
+/// Answers shared by every instance, in the keys `<prefix>captcha:{id}`, so a challenge may be
+/// answered on another instance than the one that drew it.
+pub struct RedisAnswers {
+    redis: Redis,
+}
+
+impl RedisAnswers {
+    pub fn new(redis: Redis) -> Self {
+        Self { redis }
+    }
+
+    fn key(&self, id: &str) -> String {
+        self.redis.key(&format!("captcha:{id}"))
+    }
+}
+
+#[async_trait]
+impl AnswerStore for RedisAnswers {
+    async fn put(&self, id: &str, answer: &str, ttl: Duration) -> anyhow::Result<()> {
+        let _: () = self
+            .redis
+            .conn()
+            .pset_ex(self.key(id), answer, ttl.as_millis() as u64)
+            .await?;
+        Ok(())
+    }
+
+    async fn take(&self, id: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.redis.conn().get_del(self.key(id)).await?)
+    }
+}
+
+static ANSWERS: LazyLock<Arc<dyn AnswerStore>> =
+    LazyLock::new(|| Arc::new(RedisAnswers::new(cache::redis().clone())));
+
+fn ttl() -> Duration {
+    Duration::from_secs(config::system().login.captcha_ttl)
+}
+
+/// Create a challenge, valid once for `login.captcha_ttl` seconds.
+pub async fn generate() -> anyhow::Result<Captcha> {
+    let answer = (0..LENGTH)
+        .map(|_| ALPHABET[rand::random_range(0..ALPHABET.len())] as char)
+        .collect::<String>();
+    issue(ANSWERS.as_ref(), &answer, ttl()).await
+}
+
+/// Check an answer. The challenge is consumed whatever the outcome.
+pub async fn verify(id: &str, answer: &str) -> bool {
+    check(ANSWERS.as_ref(), id, answer).await
+}
+
+async fn issue(store: &dyn AnswerStore, answer: &str, ttl: Duration) -> anyhow::Result<Captcha> {
+    let id = Ulid::new().to_string();
+    store.put(&id, answer, ttl).await?;
+    Ok(Captcha {
+        id,
+        image: format!(
+            "data:image/png;base64,{}",
+            STANDARD.encode(render::render(answer))
+        ),
+    })
+}
+
+async fn check(store: &dyn AnswerStore, id: &str, answer: &str) -> bool {
+    match store.take(id).await {
+        Ok(expected) => {
+            expected.is_some_and(|expected| expected.eq_ignore_ascii_case(answer.trim()))
+        }
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to load captcha answer");
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, PoisonError};
+    use std::time::Instant;
+
+    use daoyi_framework::cache::testing::RedisServer;
+
+    use super::*;
+
+    /// Answers of this process, for tests.
+    #[derive(Default)]
+    struct MemoryAnswers {
+        answers: Mutex<HashMap<String, (Instant, String)>>,
+    }
+
+    #[async_trait]
+    impl AnswerStore for MemoryAnswers {
+        async fn put(&self, id: &str, answer: &str, ttl: Duration) -> anyhow::Result<()> {
+            let mut answers = self.answers.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+            answers.retain(|_, (expires, _)| *expires > now);
+            answers.insert(id.to_owned(), (now + ttl, answer.to_owned()));
+            Ok(())
+        }
+
+        async fn take(&self, id: &str) -> anyhow::Result<Option<String>> {
+            let mut answers = self.answers.lock().unwrap_or_else(PoisonError::into_inner);
+            Ok(answers
+                .remove(id)
+                .filter(|(expires, _)| *expires > Instant::now())
+                .map(|(_, answer)| answer))
+        }
+    }
+
+    fn decode(captcha: &Captcha) -> (png::OutputInfo, Vec<u8>) {
+        let data = captcha
+            .image
+            .strip_prefix("data:image/png;base64,")
+            .expect("the image should be a PNG data URL");
+        let png = STANDARD.decode(data).unwrap();
+        let mut reader = png::Decoder::new(png.as_slice()).read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        (info, pixels)
+    }
+
+    #[tokio::test]
+    async fn issues_a_png_challenge() {
+        let store = MemoryAnswers::default();
+        let captcha = issue(&store, "K7QX", Duration::from_secs(60))
+            .await
+            .unwrap();
+        let (info, pixels) = decode(&captcha);
+        assert_eq!(
+            (info.width, info.height),
+            (render::WIDTH as u32, render::HEIGHT as u32)
+        );
+        assert_eq!(info.color_type, png::ColorType::Rgb);
+        // Text and noise are drawn in dark colors on a light background.
+        let dark = pixels
+            .chunks(3)
+            .filter(|rgb| rgb.iter().all(|&c| c < 130))
+            .count();
+        assert!(dark > render::WIDTH * render::HEIGHT / 20);
+    }
+
+    #[tokio::test]
+    async fn answers_are_accepted_once_and_ignore_case() {
+        let store = MemoryAnswers::default();
+        let ttl = Duration::from_secs(60);
+        let captcha = issue(&store, "K7QX", ttl).await.unwrap();
+        assert!(check(&store, &captcha.id, " k7qx ").await);
+        assert!(!check(&store, &captcha.id, "K7QX").await);
+
+        let captcha = issue(&store, "K7QX", ttl).await.unwrap();
+        assert!(!check(&store, &captcha.id, "K7QY").await);
+        assert!(!check(&store, &captcha.id, "K7QX").await);
+        assert!(!check(&store, "unknown", "K7QX").await);
+    }
+
+    #[tokio::test]
+    async fn answers_expire() {
+        let store = MemoryAnswers::default();
+        let captcha = issue(&store, "K7QX", Duration::from_millis(50))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!check(&store, &captcha.id, "K7QX").await);
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn answers_in_redis_are_single_use_and_expire() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let store = RedisAnswers::new(server.connect().await);
+        let captcha = issue(&store, "K7QX", Duration::from_secs(60))
+            .await
+            .unwrap();
+        assert!(check(&store, &captcha.id, "k7qx").await);
+        assert!(!check(&store, &captcha.id, "k7qx").await);
+
+        let captcha = issue(&store, "K7QX", Duration::from_millis(50))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        assert!(!check(&store, &captcha.id, "K7QX").await);
+    }
+}
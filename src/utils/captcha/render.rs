@@ -0,0 +1,171 @@
+//! Raster challenge images. The characters only exist as distorted pixels among noise, so the
+//! answer cannot be read from the payload.
+
+use std::f32::consts::TAU;
+
+pub(super) const WIDTH: usize = 160;
+pub(super) const HEIGHT: usize = 60;
+
+/// Characters that are hard to confuse with each other when distorted.
+pub(super) const ALPHABET: &[u8] = b"23456789ABCDEFGHJKLMNPQRSTUVWXYZ";
+
+/// 5x7 bitmaps of `ALPHABET`, one row per byte with the leftmost column in bit 4.
+const GLYPHS: [[u8; 7]; 32] = [
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x0A, 0x04, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+];
+
+type Rgb = [u8; 3];
+
+struct Canvas {
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    fn new() -> Self {
+        let mut pixels = Vec::with_capacity(WIDTH * HEIGHT * 3);
+        for _ in 0..WIDTH * HEIGHT {
+            let shade = rand::random_range(225..=250);
+            pixels.extend_from_slice(&[shade, shade, rand::random_range(225..=250)]);
+        }
+        Self { pixels }
+    }
+
+    fn set(&mut self, x: i32, y: i32, color: Rgb) {
+        if (0..WIDTH as i32).contains(&x) && (0..HEIGHT as i32).contains(&y) {
+            let at = (y as usize * WIDTH + x as usize) * 3;
+            self.pixels[at..at + 3].copy_from_slice(&color);
+        }
+    }
+}
+
+fn dark() -> Rgb {
+    [
+        rand::random_range(10..130),
+        rand::random_range(10..130),
+        rand::random_range(10..130),
+    ]
+}
+
+fn glyph(ch: u8) -> &'static [u8; 7] {
+    let index = ALPHABET
+        .iter()
+        .position(|&c| c == ch)
+        .expect("captcha answers only use the alphabet");
+    &GLYPHS[index]
+}
+
+/// PNG of `answer`: every character scaled, rotated and shifted on its own, the whole text
+/// bent by a wave, then crossed by curves and sprinkled with dots of the text's colors.
+pub(super) fn render(answer: &str) -> Vec<u8> {
+    let mut canvas = Canvas::new();
+    let amplitude = rand::random_range(2.0..4.0f32);
+    let wavelength = rand::random_range(25.0..45.0f32);
+    let phase = rand::random_range(0.0..TAU);
+    let slot = WIDTH as f32 / answer.len() as f32;
+    let mut colors = Vec::with_capacity(answer.len());
+
+    for (i, ch) in answer.bytes().enumerate() {
+        let bitmap = glyph(ch);
+        let color = dark();
+        colors.push(color);
+        let scale = rand::random_range(5.0..6.5f32);
+        let (sin, cos) = rand::random_range(-0.4..0.4f32).sin_cos();
+        let cx = slot * (i as f32 + 0.5) + rand::random_range(-4.0..4.0f32);
+        let cy = HEIGHT as f32 / 2.0 + rand::random_range(-5.0..5.0f32);
+        let reach = (scale * 5.0) as i32;
+        for y in cy as i32 - reach..=cy as i32 + reach {
+            for x in cx as i32 - reach..=cx as i32 + reach {
+                let dx = x as f32 - cx + amplitude * (y as f32 * TAU / wavelength + phase).sin();
+                let dy = y as f32 - cy;
+                // Where the pixel falls in the upright bitmap.
+                let u = (dx * cos + dy * sin) / scale + 2.5;
+                let v = (dy * cos - dx * sin) / scale + 3.5;
+                if (0.0..5.0).contains(&u)
+                    && (0.0..7.0).contains(&v)
+                    && bitmap[v as usize] & (0x10 >> u as usize) != 0
+                {
+                    canvas.set(x, y, color);
+                }
+            }
+        }
+    }
+
+    // Curves in the text's colors, as thick as its strokes, so they cannot be told apart by
+    // color or width alone.
+    for _ in 0..3 {
+        let color = colors[rand::random_range(0..colors.len())];
+        let start = rand::random_range(0.0..HEIGHT as f32);
+        let end = rand::random_range(0.0..HEIGHT as f32);
+        let bend = rand::random_range(-12.0..12.0f32);
+        let thickness = rand::random_range(2..=4);
+        for x in 0..WIDTH {
+            let t = x as f32 / WIDTH as f32;
+            let y = start + (end - start) * t + bend * (t * TAU / 2.0).sin();
+            for dy in 0..thickness {
+                canvas.set(x as i32, y as i32 + dy, color);
+            }
+        }
+    }
+    for _ in 0..WIDTH * HEIGHT / 12 {
+        let color = if rand::random_range(0..3) == 0 {
+            colors[rand::random_range(0..colors.len())]
+        } else {
+            dark()
+        };
+        canvas.set(
+            rand::random_range(0..WIDTH as i32),
+            rand::random_range(0..HEIGHT as i32),
+            color,
+        );
+    }
+
+    encode(&canvas.pixels)
+}
+
+fn encode(pixels: &[u8]) -> Vec<u8> {
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, WIDTH as u32, HEIGHT as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder
+        .write_header()
+        .expect("writing a PNG into memory should succeed");
+    writer
+        .write_image_data(pixels)
+        .expect("writing a PNG into memory should succeed");
+    writer
+        .finish()
+        .expect("writing a PNG into memory should succeed");
+    png
+}
@@ -0,0 +1,342 @@
+use std::sync::{Arc, LazyLock};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use redis::AsyncCommands;
+use salvo::async_trait;
+
+use crate::cache::{self, Redis};
+use crate::config::{self, LoginConfig};
+
+/// Failed attempts counted under one key.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Failures {
+    pub count: u32,
+    /// Unix milliseconds until which logins are refused.
+    pub locked_until: Option<u64>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Check {
+    Allowed { captcha_required: bool },
+    Locked(Duration),
+}
+
+/// Where failure counters are kept. Entries disappear `keep` after they were last written.
+#[async_trait]
+pub trait FailureStore: Send + Sync {
+    /// Failures of each of `keys`, in order.
+    async fn load(&self, keys: &[String]) -> anyhow::Result<Vec<Failures>>;
+    /// Count one more failure of `key` and return the new count.
+    async fn add(&self, key: &str, keep: Duration) -> anyhow::Result<u32>;
+    async fn lock(&self, key: &str, until: u64, keep: Duration) -> anyhow::Result<()>;
+    async fn clear(&self, key: &str) -> anyhow::Result<()>;
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// Counters shared by every instance and kept across restarts, in the hashes
+/// `<prefix>login-failures:{key}`.
+pub struct RedisFailures {
+    redis: Redis,
+}
+
+impl RedisFailures {
+    pub fn new(redis: Redis) -> Self {
+        Self { redis }
+    }
+
+    fn key(&self, key: &str) -> String {
+        self.redis.key(&format!("login-failures:{key}"))
+    }
+}
+
+#[async_trait]
+impl FailureStore for RedisFailures {
+    async fn load(&self, keys: &[String]) -> anyhow::Result<Vec<Failures>> {
+        let mut pipe = redis::pipe();
+        for key in keys {
+            pipe.cmd("HMGET")
+                .arg(self.key(key))
+                .arg("count")
+                .arg("locked_until");
+        }
+        let rows: Vec<(Option<u32>, Option<u64>)> =
+            pipe.query_async(&mut self.redis.conn()).await?;
+        Ok(rows
+            .into_iter()
+            .map(|(count, locked_until)| Failures {
+                count: count.unwrap_or_default(),
+                locked_until,
+            })
+            .collect())
+    }
+
+    async fn add(&self, key: &str, keep: Duration) -> anyhow::Result<u32> {
+        let key = self.key(key);
+        let (count, _): (u32, bool) = redis::pipe()
+            .atomic()
+            .hincr(&key, "count", 1)
+            .pexpire(&key, keep.as_millis() as i64)
+            .query_async(&mut self.redis.conn())
+            .await?;
+        Ok(count)
+    }
+
+    async fn lock(&self, key: &str, until: u64, keep: Duration) -> anyhow::Result<()> {
+        let key = self.key(key);
+        let _: (u32, bool) = redis::pipe()
+            .atomic()
+            .hset(&key, "locked_until", until)
+            .pexpire(&key, keep.as_millis() as i64)
+            .query_async(&mut self.redis.conn())
+            .await?;
+        Ok(())
+    }
+
+    async fn clear(&self, key: &str) -> anyhow::Result<()> {
+        let _: usize = self.redis.conn().del(self.key(key)).await?;
+        Ok(())
+    }
+}
+
+/// Failed login counters, keyed by account and by client address.
+///
+/// If the store fails, logins are neither throttled nor counted rather than refused.
+pub struct LoginGuard {
+    config: LoginConfig,
+    store: Arc<dyn FailureStore>,
+}
+
+static GUARD: LazyLock<LoginGuard> = LazyLock::new(|| {
+    LoginGuard::new(
+        config::system().login.clone(),
+        Arc::new(RedisFailures::new(cache::redis().clone())),
+    )
+});
+
+pub fn guard() -> &'static LoginGuard {
+    &GUARD
+}
+
+impl LoginGuard {
+    pub fn new(config: LoginConfig, store: Arc<dyn FailureStore>) -> Self {
+        Self { config, store }
+    }
+
+    /// Whether a login for all of `keys` may be attempted now.
+    pub async fn check(&self, keys: &[String]) -> Check {
+        let failures = match self.store.load(keys).await {
+            Ok(failures) => failures,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to load login failures");
+                Vec::new()
+            }
+        };
+        let now = now_millis();
+        let mut captcha_required = false;
+        let mut locked_for = 0;
+        for entry in failures {
+            if let Some(until) = entry.locked_until {
+                locked_for = locked_for.max(until.saturating_sub(now));
+            }
+            captcha_required |= entry.count >= self.config.captcha_after;
+        }
+        if locked_for == 0 {
+            Check::Allowed { captcha_required }
+        } else {
+            Check::Locked(Duration::from_millis(locked_for))
+        }
+    }
+
+    /// Count a failed attempt against every key, locking those over the threshold.
+    ///
+    /// The lockout doubles with each failure past `lock_after`, up to `max_lockout`.
+    pub async fn record_failure(&self, keys: &[String]) {
+        let forget_after = Duration::from_secs(self.config.forget_after);
+        for key in keys {
+            let count = match self.store.add(key, forget_after).await {
+                Ok(count) => count,
+                Err(e) => {
+                    tracing::warn!(key, error = %e, "failed to count login failure");
+                    continue;
+                }
+            };
+            if count < self.config.lock_after {
+                continue;
+            }
+            let exponent = (count - self.config.lock_after).min(31);
+            let lockout = Duration::from_secs(
+                self.config
+                    .base_lockout
+                    .saturating_mul(1 << exponent)
+                    .min(self.config.max_lockout),
+            );
+            let until = now_millis() + lockout.as_millis() as u64;
+            if let Err(e) = self.store.lock(key, until, forget_after.max(lockout)).await {
+                tracing::warn!(key, error = %e, "failed to lock out logins");
+            }
+        }
+    }
+
+    /// Forget the failures of `key` after a successful login.
+    pub async fn record_success(&self, key: &str) {
+        if let Err(e) = self.store.clear(key).await {
+            tracing::warn!(key, error = %e, "failed to clear login failures");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::sync::{Mutex, PoisonError};
+
+    use daoyi_framework::cache::testing::RedisServer;
+
+    use super::*;
+
+    /// Counters of this process, for tests.
+    #[derive(Default)]
+    struct MemoryFailures {
+        /// Failures and when they are forgotten, in Unix milliseconds.
+        entries: Mutex<HashMap<String, (Failures, u64)>>,
+    }
+
+    impl MemoryFailures {
+        fn entries(&self) -> std::sync::MutexGuard<'_, HashMap<String, (Failures, u64)>> {
+            let mut entries = self.entries.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = now_millis();
+            entries.retain(|_, (_, expires)| *expires > now);
+            entries
+        }
+    }
+
+    #[async_trait]
+    impl FailureStore for MemoryFailures {
+        async fn load(&self, keys: &[String]) -> anyhow::Result<Vec<Failures>> {
+            let entries = self.entries();
+            Ok(keys
+                .iter()
+                .map(|key| entries.get(key).map(|(failures, _)| *failures))
+                .map(Option::unwrap_or_default)
+                .collect())
+        }
+
+        async fn add(&self, key: &str, keep: Duration) -> anyhow::Result<u32> {
+            let mut entries = self.entries();
+            let entry = entries.entry(key.to_owned()).or_default();
+            entry.0.count += 1;
+            entry.1 = now_millis() + keep.as_millis() as u64;
+            Ok(entry.0.count)
+        }
+
+        async fn lock(&self, key: &str, until: u64, keep: Duration) -> anyhow::Result<()> {
+            let mut entries = self.entries();
+            let entry = entries.entry(key.to_owned()).or_default();
+            entry.0.locked_until = Some(until);
+            entry.1 = now_millis() + keep.as_millis() as u64;
+            Ok(())
+        }
+
+        async fn clear(&self, key: &str) -> anyhow::Result<()> {
+            self.entries().remove(key);
+            Ok(())
+        }
+    }
+
+    fn guard(store: Arc<dyn FailureStore>) -> LoginGuard {
+        LoginGuard::new(
+            LoginConfig {
+                captcha_after: 2,
+                lock_after: 3,
+                base_lockout: 60,
+                max_lockout: 200,
+                forget_after: 3600,
+                captcha_ttl: 120,
+            },
+            store,
+        )
+    }
+
+    fn locked_secs(check: Check) -> u64 {
+        match check {
+            Check::Locked(remaining) => remaining.as_millis().div_ceil(1000) as u64,
+            Check::Allowed { .. } => 0,
+        }
+    }
+
+    async fn captcha_then_exponential_lockout(guard: LoginGuard) {
+        let keys = ["account:t:alice".to_owned(), "ip:127.0.0.1".to_owned()];
+        guard.record_failure(&keys).await;
+        assert_eq!(
+            guard.check(&keys).await,
+            Check::Allowed {
+                captcha_required: false
+            }
+        );
+        guard.record_failure(&keys).await;
+        assert_eq!(
+            guard.check(&keys).await,
+            Check::Allowed {
+                captcha_required: true
+            }
+        );
+        guard.record_failure(&keys).await;
+        assert_eq!(locked_secs(guard.check(&keys).await), 60);
+        guard.record_failure(&keys).await;
+        assert_eq!(locked_secs(guard.check(&keys).await), 120);
+        guard.record_failure(&keys).await;
+        assert_eq!(locked_secs(guard.check(&keys).await), 200);
+    }
+
+    #[tokio::test]
+    async fn captcha_then_exponential_lockout_in_memory() {
+        captcha_then_exponential_lockout(guard(Arc::new(MemoryFailures::default()))).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn captcha_then_exponential_lockout_in_redis() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let store = Arc::new(RedisFailures::new(server.connect().await));
+        captcha_then_exponential_lockout(guard(store)).await;
+    }
+
+    #[tokio::test]
+    #[ignore = "requires redis-server on PATH"]
+    async fn instances_share_the_counters() {
+        let server = RedisServer::spawn().expect("redis-server should be installed");
+        let first = guard(Arc::new(RedisFailures::new(server.connect().await)));
+        let second = guard(Arc::new(RedisFailures::new(server.connect().await)));
+        let keys = ["account:t:bob".to_owned()];
+        for _ in 0..3 {
+            first.record_failure(&keys).await;
+        }
+        assert!(matches!(second.check(&keys).await, Check::Locked(_)));
+    }
+
+    #[tokio::test]
+    async fn address_failures_apply_to_other_accounts() {
+        let guard = guard(Arc::new(MemoryFailures::default()));
+        for user in ["a", "b", "c"] {
+            guard
+                .record_failure(&[format!("account:t:{user}"), "ip:10.0.0.1".to_owned()])
+                .await;
+        }
+        let keys = ["account:t:d".to_owned(), "ip:10.0.0.1".to_owned()];
+        assert!(matches!(guard.check(&keys).await, Check::Locked(_)));
+
+        guard.record_success("ip:10.0.0.1").await;
+        assert_eq!(
+            guard.check(&keys).await,
+            Check::Allowed {
+                captcha_required: false
+            }
+        );
+    }
+}